@@ -1,30 +1,188 @@
 #![allow(dead_code)]
 
+pub mod compiled;
+pub mod completion;
+pub mod dependency_graph;
+pub mod dual;
+pub mod error;
+pub mod interval;
+pub mod monte_carlo;
+pub mod rpn;
+pub mod table;
+pub mod tokenizer;
+pub mod validation;
+
 use serde::{Deserialize, Serialize};
 
 use crate::vputils;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+pub use compiled::{CompiledFormula, Scalar};
+pub use completion::{
+    Completion, CompletionKind, FunctionSignature, SignatureHelp, FUNCTION_SIGNATURES,
+};
+pub use dependency_graph::DependencyGraph;
+pub use dual::Dual;
+pub use error::EquationError;
+pub use interval::Interval;
+pub use monte_carlo::{Distribution, Histogram, MonteCarloResult, MonteCarloSettings};
+pub use table::Table;
+pub use tokenizer::{Token, TokenKind};
 
 pub const MATH_OPERATORS: &[&str] = &[
-    "sqrt", "abs", "sin", "cos", "tan", "acos", "asin", "atan", "log", "log10",
+    "sqrt", "abs", "sin", "cos", "tan", "acos", "asin", "atan", "log", "log10", "integrate", "sum",
+    "prod", "piecewise", "lookup", "interp",
+];
+
+/// Math operators that bind a variable in their first argument (e.g. `sum(i^2; i; 1; 10)`). The
+/// bound variable hides the variable with the same name inside the first argument.
+pub const BINDING_OPERATORS: &[&str] = &["integrate", "sum", "prod"];
+
+/// Math operators that take a table name (see [`EquationHandler::add_table`]) as the first
+/// argument (e.g. `interp(kc; lambda)`)
+pub const TABLE_OPERATORS: &[&str] = &["lookup", "interp"];
+
+/// Comparison operators. They have the lowest precedence and their result is 1 if the comparison
+/// is true and 0 otherwise (e.g. `piecewise(x<=5; 1; 2)`).
+pub const COMPARISON_OPERATORS: &[&str] = &["<", ">", "<=", ">=", "==", "!="];
+
+/// Built-in constants that can be used in the formulas. The names are case insensitive and
+/// variables with the same name override the constants.
+pub const CONSTANTS: &[(&str, f64)] = &[
+    ("pi", std::f64::consts::PI),
+    ("e", std::f64::consts::E),
 ];
 
 /// EquationHandler is a struct that handles equations. It can calculate the result of a given
 /// formula string. The formula string can contain variables that are set by the user. 
-/// The keys are all converted to lowercase (so all keys are case invariable).
+/// By default the keys are all converted to lowercase (so all keys are case invariable). See
+/// [`KeyPolicy`] for case sensitive keys.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "EquationHandlerData")]
 pub struct EquationHandler {
+    #[serde(default)]
+    key_policy: KeyPolicy,
+    #[serde(default)]
+    limits: EvaluationLimits,
     variables: HashMap<String, f64>,
+    /// Variable scopes on top of the variables. The last scope is the current scope.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    scopes: Vec<HashMap<String, f64>>,
+    /// Probability distributions of the variables for the Monte Carlo simulation (see
+    /// [`EquationHandler::monte_carlo`])
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    distributions: HashMap<String, Distribution>,
+    /// Tables for the table operators (see [`TABLE_OPERATORS`])
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    tables: HashMap<String, Table>,
+    /// Parent handler where the variables are searched from if they're not found in this handler
+    #[serde(skip)]
+    parent: Option<Arc<EquationHandler>>,
+}
+
+/// Defines how the variable keys are handled in the [`EquationHandler`]. The policy is applied to
+/// all the variable accessors and to the variables in the formula strings. Math operator names
+/// (see [`MATH_OPERATORS`]) are always case insensitive.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyPolicy {
+    /// The keys are converted to lowercase (e.g. `M` and `m` are the same variable)
+    #[default]
+    CaseInsensitive,
+    /// The keys are used as is (e.g. `M` and `m` are different variables)
+    CaseSensitive,
+}
+
+impl KeyPolicy {
+    /// Normalizes the given key with the policy
+    pub fn normalize(&self, key: &str) -> String {
+        match self {
+            KeyPolicy::CaseInsensitive => key.to_lowercase(),
+            KeyPolicy::CaseSensitive => key.to_string(),
+        }
+    }
+}
+
+/// Limits for the formula calculations. Used to guard against hostile or runaway formulas (e.g.
+/// formulas from end users). If a limit is exceeded, the calculation is stopped and an
+/// [`EquationError`] is returned.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvaluationLimits {
+    /// Maximum length of the formula string (in characters)
+    pub max_formula_length: usize,
+    /// Maximum number of tokens (numbers, variables and operators) in the formula
+    pub max_token_count: usize,
+    /// Maximum nesting depth of the parentheses (including the math operators parentheses)
+    pub max_nesting_depth: usize,
+    /// Maximum nesting depth of the math operators (e.g. sqrt(abs(x)) has depth of 2)
+    pub max_function_depth: usize,
+    /// Maximum number of evaluation steps in one calculation. The terms of `sum` and `prod` and
+    /// the integrand evaluations of `integrate` are steps, and they are counted over all the
+    /// nested operators (e.g. a sum inside a sum).
+    pub max_evaluation_steps: usize,
+}
+
+impl EvaluationLimits {
+    /// Limits that are never exceeded
+    pub fn unlimited() -> Self {
+        EvaluationLimits {
+            max_formula_length: usize::MAX,
+            max_token_count: usize::MAX,
+            max_nesting_depth: usize::MAX,
+            max_function_depth: usize::MAX,
+            max_evaluation_steps: usize::MAX,
+        }
+    }
+}
+
+impl Default for EvaluationLimits {
+    fn default() -> Self {
+        EvaluationLimits {
+            max_formula_length: 65536,
+            max_token_count: 16384,
+            max_nesting_depth: 128,
+            max_function_depth: 64,
+            max_evaluation_steps: 10_000_000,
+        }
+    }
+}
+
+impl Default for EquationHandler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl EquationHandler {
     pub fn new() -> Self {
+        Self::new_with_key_policy(KeyPolicy::default())
+    }
+
+    pub fn new_with_key_policy(key_policy: KeyPolicy) -> Self {
         EquationHandler {
+            key_policy,
+            limits: EvaluationLimits::default(),
             variables: HashMap::new(),
+            scopes: Vec::new(),
+            distributions: HashMap::new(),
+            tables: HashMap::new(),
+            parent: None,
         }
     }
 
+    /// Creates a new equation handler that searches the variables from the given parent if they
+    /// are not set in the new handler. The parents variables are not copied. The key policy and
+    /// the evaluation limits are inherited from the parent.
+    pub fn new_child(parent: Arc<EquationHandler>) -> Self {
+        let mut eq = EquationHandler::new_with_key_policy(parent.key_policy);
+        eq.limits = parent.limits;
+        eq.parent = Some(parent);
+        eq
+    }
+
     pub fn from<const N: usize>(arr: [(&str, f64); N]) -> Self {
         let mut eq = EquationHandler::new();
         for (k, v) in arr {
@@ -33,111 +191,327 @@ impl EquationHandler {
         eq
     }
 
-    /// Adds a variable to the variables hashmap. The key is converted to lowercase.
-    /// Duplicate keys are not added. False is returned if the key already exists.
+    /// Returns the parent handler if this handler was created with [`EquationHandler::new_child`]
+    pub fn get_parent(&self) -> Option<&Arc<EquationHandler>> {
+        self.parent.as_ref()
+    }
+
+    /// Returns the key policy of the equation handler
+    pub fn get_key_policy(&self) -> KeyPolicy {
+        self.key_policy
+    }
+
+    /// Returns the evaluation limits of the equation handler
+    pub fn get_limits(&self) -> EvaluationLimits {
+        self.limits
+    }
+
+    /// Sets the evaluation limits used in the calculations
+    pub fn set_limits(&mut self, limits: EvaluationLimits) {
+        self.limits = limits;
+    }
+
+    /// Sets the key policy and normalizes the existing keys with it. Note! When changing to
+    /// [`KeyPolicy::CaseInsensitive`], variables that differ only by case are merged and only
+    /// one of the values is kept. The parents key policy is not changed.
+    pub fn set_key_policy(&mut self, key_policy: KeyPolicy) {
+        self.key_policy = key_policy;
+        for scope in std::iter::once(&mut self.variables).chain(self.scopes.iter_mut()) {
+            let variables = std::mem::take(scope);
+            for (k, v) in variables {
+                scope.insert(key_policy.normalize(&k), v);
+            }
+        }
+        let distributions = std::mem::take(&mut self.distributions);
+        for (k, v) in distributions {
+            self.distributions.insert(key_policy.normalize(&k), v);
+        }
+        let tables = std::mem::take(&mut self.tables);
+        for (k, v) in tables {
+            self.tables.insert(key_policy.normalize(&k), v);
+        }
+    }
+
+    /// Pushes a new empty variable scope. Variables set after this are set to the new scope and
+    /// they hide the variables with the same name in the lower scopes until the scope is popped.
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Pops the current variable scope and returns its variables. Returns None if there are no
+    /// scopes pushed (the base variables can't be popped).
+    pub fn pop_scope(&mut self) -> Option<HashMap<String, f64>> {
+        self.scopes.pop()
+    }
+
+    /// Returns the number of pushed scopes
+    pub fn scope_depth(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Adds a variable to the current scope. The key is normalized with the key policy.
+    /// Duplicate keys are not added. False is returned if the key already exists in the current
+    /// scope. The variables in the lower scopes or in the parent can be hidden with this.
     pub fn add_variable(&mut self, name: &str, value: f64) -> bool {
-        let key = String::from(name).to_lowercase();
-        if !self.variables.contains_key(&key) {
-            self.variables.insert(key, value);
+        let key = self.key_policy.normalize(name);
+        if let Entry::Vacant(e) = self.current_scope_mut().entry(key) {
+            e.insert(value);
             return true;
         }
         false
     }
 
-    /// Checks if the variable exists
+    /// Checks if the variable exists in any scope or in the parent
     pub fn variable_is_set(&self, variable: &str) -> bool {
-        self.variables.contains_key(variable)
+        self.get_variable(variable).is_some()
     }
 
-    /// Clears all the variables
+    /// Clears all the variables in the current scope
     pub fn clear_variables(&mut self) {
-        self.variables.clear()
+        self.current_scope_mut().clear()
     }
 
-    /// Sets multiple variables to the variables hashmap. The keys are converted to lowercase.
+    /// Sets multiple variables to the current scope. The old variables in the current scope are
+    /// removed. The keys are normalized with the key policy.
     pub fn set_variables(&mut self, variables: HashMap<String, f64>) {
-        self.variables.clear();
+        let key_policy = self.key_policy;
+        let scope = self.current_scope_mut();
+        scope.clear();
         for (k, v) in variables {
-            let key = String::from(k).to_lowercase();
-            self.variables.insert(key, v);
+            let key = key_policy.normalize(&k);
+            scope.insert(key, v);
         }
     }
 
-    /// Returns the value of the variable if it exists in the variables hashmap
+    /// Returns the value of the variable if it exists. The variable is searched from the current
+    /// scope first, then from the lower scopes and finally from the parent.
     pub fn get_variable(&self, variable: &str) -> Option<f64> {
-        self.variables.get(variable).cloned()
+        self.find_variable(&self.key_policy.normalize(variable))
     }
 
-    /// Sets a variable
+    /// Sets a variable to the current scope
     pub fn set_variable(&mut self, variable: &str, value: f64) {
-        let key = String::from(variable).to_lowercase();
-        self.variables.insert(key, value);
+        let key = self.key_policy.normalize(variable);
+        self.current_scope_mut().insert(key, value);
     }
 
-    /// Removes a variable
+    /// Removes a variable from the current scope
     pub fn remove_variable(&mut self, variable: &str) {
-        self.variables.remove(variable);
+        let key = self.key_policy.normalize(variable);
+        self.current_scope_mut().remove(&key);
+    }
+
+    /// Searches the variable with already normalized key from the scopes and the parent
+    fn find_variable(&self, key: &str) -> Option<f64> {
+        self.scopes
+            .iter()
+            .rev()
+            .chain(std::iter::once(&self.variables))
+            .find_map(|scope| scope.get(key))
+            .copied()
+            .or_else(|| self.parent.as_ref().and_then(|p| p.get_variable(key)))
+    }
+
+    fn current_scope_mut(&mut self) -> &mut HashMap<String, f64> {
+        match self.scopes.last_mut() {
+            Some(scope) => scope,
+            None => &mut self.variables,
+        }
     }
 
-    /// Calculates the given formula string. Returns None if the formula is invalid.
+    /// Calculates the given formula string. Returns None if the formula is invalid. See
+    /// [`EquationHandler::try_calculate_formula`] for the reason of the failure.
     pub fn calculate_formula(&self, formula_string: &str) -> Option<f64> {
+        self.try_calculate_formula(formula_string).ok()
+    }
+
+    /// Calculates the given formula string. Returns an error if the formula is invalid or if it
+    /// exceeds the evaluation limits (see [`EvaluationLimits`]).
+    pub fn try_calculate_formula(&self, formula_string: &str) -> Result<f64, EquationError> {
+        self.compile(formula_string)?.evaluate(self)
+    }
+
+    /// Compiles the given formula string so that it can be evaluated multiple times with
+    /// different variable values without parsing it again. Returns an error if the formula
+    /// exceeds the evaluation limits (see [`EvaluationLimits`]).
+    pub fn compile(&self, formula_string: &str) -> Result<CompiledFormula, EquationError> {
+        let length = formula_string.chars().count();
+        if length > self.limits.max_formula_length {
+            let max = self.limits.max_formula_length;
+            return Err(EquationError::FormulaTooLong { length, max });
+        }
+        let depth = Self::get_nesting_depth(formula_string);
+        if depth > self.limits.max_nesting_depth {
+            let max = self.limits.max_nesting_depth;
+            return Err(EquationError::NestingTooDeep { depth, max });
+        }
+        self.compile_internal(formula_string, 0, &mut 0)
+    }
+
+    /// Compiles the formula string. `function_depth` tells how deep in the math operators the
+    /// formula string is and `token_count` is the running count of the factors of the whole
+    /// formula, including the arguments of the math operators.
+    fn compile_internal(
+        &self,
+        formula_string: &str,
+        function_depth: usize,
+        token_count: &mut usize,
+    ) -> Result<CompiledFormula, EquationError> {
+        if function_depth > self.limits.max_function_depth {
+            return Err(EquationError::FunctionRecursionTooDeep {
+                depth: function_depth,
+                max: self.limits.max_function_depth,
+            });
+        }
+        let formatted_formula_string = Self::handle_string_formatting(formula_string);
+        let formatted_formula_string = formatted_formula_string.as_str();
+        let factors =
+            self.populate_lists(formatted_formula_string, function_depth, token_count, None, None)?;
+        *token_count += factors.len();
+        if *token_count > self.limits.max_token_count {
+            let max = self.limits.max_token_count;
+            return Err(EquationError::TooManyTokens { count: *token_count, max });
+        }
+        let factors = Self::get_prefix_notation(factors);
+        let max_steps = self.limits.max_evaluation_steps;
+        Ok(CompiledFormula::new(formula_string, factors, max_steps))
+    }
+
+    /// Calculates all the given formula strings. See [`EquationHandler::calculate_formula`]
+    pub fn calculate_formulas<S: AsRef<str>>(&self, formula_strings: &[S]) -> Vec<Option<f64>> {
+        formula_strings
+            .iter()
+            .map(|f| self.calculate_formula(f.as_ref()))
+            .collect()
+    }
+
+    /// Calculates all the given formula strings in parallel with rayon. The results are in the
+    /// same order as the formula strings. See [`EquationHandler::calculate_formula`]
+    #[cfg(feature = "parallel")]
+    pub fn calculate_formulas_parallel<S: AsRef<str> + Sync>(
+        &self,
+        formula_strings: &[S],
+    ) -> Vec<Option<f64>> {
+        use rayon::prelude::*;
+        formula_strings
+            .par_iter()
+            .map(|f| self.calculate_formula(f.as_ref()))
+            .collect()
+    }
+
+    /// Creates a snapshot of the variables visible to this handler (see
+    /// [`EquationHandler::get_variables`]). The snapshot has no scopes or parent, so it can be
+    /// shared between threads and used as a parent with [`EquationHandler::new_child`] without
+    /// being affected by later changes to this handler.
+    pub fn snapshot(&self) -> Arc<EquationHandler> {
+        let mut eq = EquationHandler::new_with_key_policy(self.key_policy);
+        eq.limits = self.limits;
+        eq.variables = self.get_variables();
+        eq.distributions = self.distributions.clone();
+        eq.tables = self.get_tables();
+        Arc::new(eq)
+    }
+
+    /// Analyzes the given formula string without calculating it. Returns the variables (known
+    /// and unknown), math operators and operators used in the formula, whether it contains unit
+    /// comments (e.g. `10 [kN]`) and the maximum parenthesis nesting depth.
+    pub fn analyze(&self, formula_string: &str) -> FormulaInfo {
+        let mut info = FormulaInfo::default();
         let formatted_formula_string = Self::handle_string_formatting(formula_string);
-        let factors = self.populate_lists_streaming(formatted_formula_string.as_str());
-        let input: Vec<Factor> = Self::get_prefix_notation(factors);
-        let result = self.calculate_prefix_notation(input);
-        match result {
-            Some(r) => Some(r),
-            None => {
-                println!("Error with calculation! Formula: {}", formula_string);
-                None
-            },
+        // Math operators are not evaluated when the info is collected, so this can't fail
+        let factors = self
+            .populate_lists(formatted_formula_string.as_str(), 0, &mut 0, Some(&mut info), None)
+            .unwrap_or_default();
+        let mut depth = 0;
+        // E-notation is converted to '$' operator followed by the exponent in parentheses in the
+        // string formatting (e.g. 2E-3 => 2$(0-3)). Those are not written by the user, so they are
+        // skipped.
+        let mut in_exponent = false;
+        let mut exponent_next = false;
+        for f in factors.iter().filter(|f| f.factor_type == FactorType::Operator) {
+            match f.key.as_str() {
+                "$" => exponent_next = true,
+                "(" if exponent_next => {
+                    exponent_next = false;
+                    in_exponent = true;
+                }
+                ")" if in_exponent => in_exponent = false,
+                _ if in_exponent => {}
+                "(" => {
+                    depth += 1;
+                    info.max_depth = info.max_depth.max(depth);
+                }
+                ")" => depth = usize::saturating_sub(depth, 1),
+                _ => {
+                    info.operators.insert(f.key.clone());
+                }
+            }
         }
+        let (bound_variables, tables) = (&info.bound_variables, &info.tables);
+        info.unknown_variables.retain(|k| !bound_variables.contains(k) && !tables.contains(k));
+        info
     }
 
-    /// Returns a clone of the variables hashmap
+    /// Returns all the variables visible to this handler (the parents variables and all the
+    /// scopes). The variables in the upper scopes override the ones in the lower scopes.
     pub fn get_variables(&self) -> HashMap<String, f64> {
-        self.variables.clone()
+        let mut variables = match &self.parent {
+            Some(parent) => parent.get_variables(),
+            None => HashMap::new(),
+        };
+        for scope in std::iter::once(&self.variables).chain(self.scopes.iter()) {
+            variables.extend(scope.iter().map(|(k, v)| (k.clone(), *v)));
+        }
+        variables
     }
 
     /// Handles initial string formatting for parser. Adds zero prefixes to values starting with
     /// negative sign (dash) and converts E-notation values to use ^ operator
     fn handle_string_formatting(s: &str) -> String {
-        let mut temp: String;
+        Self::handle_string_formatting_with_offsets(s).0
+    }
+
+    /// Formats the string like [`EquationHandler::handle_string_formatting`]. Returns also the
+    /// byte index in the original string for each character of the formatted string. The
+    /// characters added by the formatting (e.g. the zero prefixes) don't have an index.
+    fn handle_string_formatting_with_offsets(s: &str) -> (String, Vec<Option<usize>>) {
+        let mut temp: Vec<(char, Option<usize>)> = Vec::with_capacity(s.len() + 1);
         // Parser can't handle a string starting with negative sign, but it can handle it if there is a zero in front of the negative sign
-        if s.starts_with("-") {
-            temp = "0".to_string() + &s;
-        } else {
-            temp = s.to_string();
+        if s.starts_with('-') {
+            temp.push(('0', None));
         }
         // Remove the whitespace
-        temp.retain(|c| !c.is_whitespace());
+        let non_whitespace = s.char_indices().filter(|(_, c)| !c.is_whitespace());
+        temp.extend(non_whitespace.map(|(i, c)| (c, Some(i))));
 
-        let mut result: String = String::with_capacity(temp.len());
-        let mut chars = temp.chars().peekable();
-        let mut bracket_count = 0;
+        let mut result: Vec<(char, Option<usize>)> = Vec::with_capacity(temp.len());
+        let push_added = |result: &mut Vec<(char, Option<usize>)>, added: &str| {
+            result.extend(added.chars().map(|c| (c, None)));
+        };
+        let mut chars = temp.into_iter().peekable();
         let mut char_index = 0;
         let mut prev_char: char = ' ';
-        while let Some(c) = chars.next() {
-            let next_char = chars.peek();
+        while let Some((c, offset)) = chars.next() {
+            let next_char = chars.peek().map(|(c, _)| *c);
             // Parser can't handle values like (-5) or ^-5, but it can handle if there is a zero in
             // front of the negative sign (0-5) and ^0-5
-            if c == '^' && next_char == Some(&'-') {
-                result.push_str("^(0-");
+            if c == '^' && next_char == Some('-') {
+                result.push((c, offset));
+                push_added(&mut result, "(0");
+                // Keep the '-' character
+                result.extend(chars.next());
                 let mut closing_bracket_set = false;
-                // Skip the '-' character
-                chars.next();
-                while let Some(c_inner) = chars.clone().next() {
+                while let Some(&(c_inner, _)) = chars.peek() {
                     // Search for the number value. After no digit or decimal characters are found
-                    // add the closing bracket and break the inner loop and continue the outer loop
-                    // Maybetodo: Add check for multiple decimal separators
+                    // add the closing bracket and break the inner loop and continue the outer loop.
+                    // Multiple decimal separators are reported by the validation.
                     if Self::is_number_or_decimal_separator(c_inner) {
-                        result.push(c_inner);
                         // Move the original chars iterator to the next char
-                        chars.next();
+                        result.extend(chars.next());
                     } else {
                         // Don't move the original chars iterator so that all characters
                         // are handled with this if else test
-                        result.push(')');
+                        push_added(&mut result, ")");
                         closing_bracket_set = true;
                         break;
                     }
@@ -146,44 +520,46 @@ impl EquationHandler {
                 // Got to the end of the string and no end parentheses has been set
                 // -> Set it and we're done
                 if !closing_bracket_set {
-                    result.push(')');
+                    push_added(&mut result, ")");
                 }
-            } else if c == '(' && next_char == Some(&'-') {
-                result.push_str("(0");
-                bracket_count += 1;
+            } else if c == '(' && next_char == Some('-') {
+                result.push((c, offset));
+                push_added(&mut result, "0");
+            } else if matches!(c, '<' | '>' | '=') && next_char == Some('-') {
+                // Comparisons have the lowest precedence, so x<-5 can be handled as x<0-5
+                result.push((c, offset));
+                push_added(&mut result, "0");
             } else if char_index > 0
                 && (c == 'E' || c == 'e')
                 && Self::is_number_or_decimal_separator(prev_char)
-                && (next_char == Some(&'+')
-                    || next_char == Some(&'-')
-                    || next_char.unwrap().is_ascii_digit())
+                && (next_char == Some('+')
+                    || next_char == Some('-')
+                    || next_char.is_some_and(|c| c.is_ascii_digit()))
             {
                 // E notation found e.g. 1E+004, 1e04 1e-4
                 // Replace E with next line
                 // result.push_str("*10^(0");
-                result.push_str("$(0");
+                push_added(&mut result, "$(0");
 
                 let mut closing_bracket_set = false;
 
                 // If the char after E is negative or plus sign the jump needs to be 2.
                 // Otherwise, the next char must be a number so jump needs to be only 1 char
-                if next_char == Some(&'+') || next_char == Some(&'-') {
-                    result.push(*next_char.unwrap());
-                    chars.next();
+                if next_char == Some('+') || next_char == Some('-') {
+                    result.extend(chars.next());
                 }
 
-                while let Some(c_inner) = chars.clone().next() {
+                while let Some(&(c_inner, _)) = chars.peek() {
                     // Search for the number value. After no digit or decimal characters are found
-                    // add the closing bracket and break the inner loop and continue the outer loop
-                    // Maybetodo: Add check for multiple decimal separators
+                    // add the closing bracket and break the inner loop and continue the outer loop.
+                    // Multiple decimal separators are reported by the validation.
                     if Self::is_number_or_decimal_separator(c_inner) {
-                        result.push(c_inner);
                         // Move the original chars iterator to the next char
-                        chars.next();
+                        result.extend(chars.next());
                     } else {
                         // Don't move the original chars iterator so that all characters
                         // are handled with this if else test
-                        result.push(')');
+                        push_added(&mut result, ")");
                         closing_bracket_set = true;
                         break;
                     }
@@ -192,39 +568,60 @@ impl EquationHandler {
                 // Got to the end of the string and no end parentheses has been set
                 // -> Set it and we're done
                 if !closing_bracket_set {
-                    result.push(')');
+                    push_added(&mut result, ")");
                 }
-            } else if c == ')' {
-                bracket_count -= 1;
-                result.push(c);
             } else {
-                result.push(c);
+                result.push((c, offset));
             }
 
             prev_char = c;
             char_index += 1;
         }
-        if bracket_count > 0 {
-            for _ in 0..(bracket_count - 1) {
-                result.push(')');
-            }
-        }
+        // Missing closing parentheses are not added, they are reported as unbalanced instead
+        result.into_iter().unzip()
+    }
 
-        result
+    fn populate_lists_streaming(&self, formula_string: &str) -> Result<Vec<Factor>, EquationError> {
+        self.populate_lists(formula_string, 0, &mut 0, None, None)
     }
 
-    fn populate_lists_streaming(&self, formula_string: &str) -> Vec<Factor> {
+    /// Splits the formula string into factors. The arguments of the math operators are compiled
+    /// recursively, `function_depth` tells how deep in the math operators the formula string is
+    /// and the factors of the arguments are added to `token_count`.
+    ///
+    /// If `info` is given, the math operators are not compiled. Instead their names are
+    /// collected into the info and their parentheses are left in the factor list as operators.
+    /// The found variables (known and unknown) and unit comments are also collected into the info.
+    ///
+    /// If `tokens` is given, the scanned tokens are added to it (see
+    /// [`EquationHandler::tokenize`]). The spans of the tokens and the indexes of the factors are
+    /// character indexes in the formula string.
+    fn populate_lists(
+        &self,
+        formula_string: &str,
+        function_depth: usize,
+        token_count: &mut usize,
+        mut info: Option<&mut FormulaInfo>,
+        mut tokens: Option<&mut Vec<Token>>,
+    ) -> Result<Vec<Factor>, EquationError> {
         let mut result = Vec::new();
 
         let mut current_factor_type = FactorType::None;
-        let formula_string = formula_string.to_lowercase();
+        let formula_string = self.key_policy.normalize(formula_string);
         let mut buffer = String::with_capacity(formula_string.len());
-        let mut running_index = 0;
+        // Character indexes of the start and the end of the buffer and the comment
+        let (mut buffer_start, mut buffer_end, mut comment_start) = (0, 0, 0);
         let mut brackets = false;
-        let mut chars = formula_string.chars().peekable();
+        let mut chars = FormulaChars::new(&formula_string);
         let mut peeked_none_found_counter = 0;
+        let mut add_token = |kind: TokenKind, span: std::ops::Range<usize>| {
+            if let Some(tokens) = tokens.as_deref_mut() {
+                tokens.push(Token::new(kind, span));
+            }
+        };
         loop {
             let peeked_opt = chars.peek();
+            let position = chars.position;
             let current;
             match peeked_opt {
                 // We have iterated through the string, but buffer is not empty
@@ -232,30 +629,45 @@ impl EquationHandler {
                 // of the match with current_factor_type
                 None => {
                     current = ' ';
+                    if brackets {
+                        // The comment is not closed
+                        add_token(TokenKind::UnitComment, comment_start..position);
+                        brackets = false;
+                    }
                     // If peeked was None (= iteration at the end of string) and buffer is empty,
                     // break the loop. If buffer still has stuff in it, run one more lap to clean it
                     // up (if possible)
                     if buffer.is_empty() {
-                        return result;
+                        return Ok(result);
                     }
                     peeked_none_found_counter += 1;
                     // Infinite loop safeguard. None should be only found with peek when
                     // buffer is not emptied, but string is already fully iterated
                     if peeked_none_found_counter > 1 {
-                        return result;
+                        return Ok(result);
                     }
                 }
                 Some(c) => {
-                    current = *c;
+                    current = c;
                     // Everything in brackets are considered to be comments (useful if equation needs
                     // to have units, e.g. 10 [kN] * 5 [m])
-                    if current == '[' {
+                    if current == '[' && !brackets {
                         brackets = true;
-                        continue;
+                        comment_start = position;
+                        if let Some(info) = info.as_deref_mut() {
+                            info.has_unit_comments = true;
+                        }
                     } else if current == ']' {
+                        match brackets {
+                            true => add_token(TokenKind::UnitComment, comment_start..position + 1),
+                            false => add_token(TokenKind::Invalid, position..position + 1),
+                        }
                         brackets = false;
+                        chars.next();
+                        continue;
                     }
                     if brackets {
+                        chars.next();
                         continue;
                     }
                 }
@@ -266,93 +678,144 @@ impl EquationHandler {
                     if Self::is_number_or_decimal_separator(current) {
                         // Next is guaranteed to be some in Some(current) = chars.peek();
                         buffer.push(chars.next().unwrap());
+                        buffer_end = chars.position;
                         continue; // Make sure that chars.peek is checked before continuing
                     } else {
+                        add_token(TokenKind::Number, buffer_start..buffer_end);
                         let buffer_as_double = vputils::s_to_double(buffer.as_str());
-                        match buffer_as_double {
-                            Some(d) => {
-                                result.push(Factor::new_number(
-                                    running_index,
-                                    buffer.len() as isize,
-                                    d,
-                                ));
-                            }
-                            _ => {}
+                        if let Some(d) = buffer_as_double {
+                            result.push(Factor::new_number(
+                                buffer_start as isize,
+                                (buffer_end - buffer_start) as isize,
+                                d,
+                            ));
                         }
                         current_factor_type = FactorType::None;
-                        running_index += 1;
                     }
                 }
                 FactorType::Variable => {
                     // A math operator found. (sqrt, cos, sin, ...)
-                    if current == '(' {
-                        if MATH_OPERATORS.contains(&buffer.as_str()) {
-                            chars.next(); // Skip the opening parenthesis
-                            let mut open_parenthesis_count = 0;
-                            let mut val_buffer = String::new();
-                            while let Some(temp_c) = chars.next() {
-                                if temp_c == ')' && open_parenthesis_count == 0 {
-                                    let val = self.get_value_from_special_math_op(
-                                        buffer.as_str(),
-                                        val_buffer.as_str(),
-                                    );
-                                    if val.is_none() {
-                                        println!("Value is none!");
-                                        break;
-                                    }
-                                    result.push(Factor::new_number(
-                                        running_index,
-                                        val_buffer.len() as isize,
-                                        val.unwrap(),
-                                    ));
-                                    current_factor_type = FactorType::None;
-                                    running_index += 1;
-                                    // The closing parenthesis is ok to be consumed. We only
-                                    // want the number from the math operation
-                                    break;
-                                } else if temp_c == ')' {
-                                    open_parenthesis_count -= 1;
-                                } else if temp_c == '(' {
-                                    open_parenthesis_count += 1;
-                                }
-                                val_buffer.push(temp_c);
+                    let math_operator = if current == '(' {
+                        Self::get_math_operator(buffer.as_str())
+                    } else {
+                        None
+                    };
+                    if let (Some(operator), Some(info)) = (math_operator, info.as_deref_mut()) {
+                        // Only collect the operator. The parenthesis is handled as a normal
+                        // operator so that the factors inside it are collected too
+                        info.functions.insert(operator.to_string());
+                        add_token(TokenKind::Function, buffer_start..buffer_end);
+                        let arguments = || Self::get_function_arguments(chars.clone().skip(1));
+                        // The names are missing if the arguments are (e.g. `lookup()`)
+                        let name = |i| arguments().into_iter().nth(i).filter(|n| !n.is_empty());
+                        if BINDING_OPERATORS.contains(&operator) {
+                            if let Some(variable) = name(1) {
+                                info.bound_variables.insert(variable);
                             }
+                        } else if TABLE_OPERATORS.contains(&operator) {
+                            if let Some(table) = name(0) {
+                                info.tables.insert(table);
+                            }
+                        }
+                        buffer.clear();
+                    } else if let Some(operator) = math_operator {
+                        chars.next(); // Skip the opening parenthesis
+                        let mut open_parenthesis_count = 0;
+                        let mut val_buffer = String::new();
+                        let mut closing_parenthesis_found = false;
+                        for temp_c in chars.by_ref() {
+                            if temp_c == ')' && open_parenthesis_count == 0 {
+                                // Equations inside math operations (e.g. sin(alpha-50)) are
+                                // compiled with the same handler
+                                let function = self.compile_function(
+                                    operator,
+                                    val_buffer.as_str(),
+                                    function_depth + 1,
+                                    token_count,
+                                    buffer_start as isize,
+                                )?;
+                                result.push(function);
+                                current_factor_type = FactorType::None;
+                                closing_parenthesis_found = true;
+                                // The closing parenthesis is ok to be consumed. The math
+                                // operation is a single factor
+                                break;
+                            } else if temp_c == ')' {
+                                open_parenthesis_count -= 1;
+                            } else if temp_c == '(' {
+                                open_parenthesis_count += 1;
+                            }
+                            val_buffer.push(temp_c);
                         }
+                        if !closing_parenthesis_found {
+                            let formula = format!("{operator}({val_buffer}");
+                            return Err(EquationError::InvalidFormula { formula });
+                        }
+                        buffer.clear();
                     }
-                    if !Self::is_operator(current) && current != ' ' {
+                    if !Self::is_operator(current) && current != ' ' && current != ';' {
                         // Next is guaranteed to be some in Some(current) = chars.peek();
                         buffer.push(chars.next().unwrap());
+                        buffer_end = chars.position;
                         continue; // Make sure that chars.peek is checked before continuing
                     } else {
-                        if self.variables.contains_key(buffer.as_str()) {
-                            // A variable has been found. Add new factor and set its double
-                            // value to the value saved under the variable key
-                            result.push(Factor::new_number(
-                                running_index,
-                                buffer.len() as isize,
-                                self.variables[buffer.as_str()],
+                        if !buffer.is_empty() {
+                            // A variable has been found. The value is resolved when the
+                            // formula is evaluated, so the current value is only informative
+                            let variable = self.find_variable(buffer.as_str());
+                            result.push(Factor::new_variable(
+                                buffer_start as isize,
+                                (buffer_end - buffer_start) as isize,
+                                buffer.clone(),
+                                variable.unwrap_or(0.0),
                             ));
-                            running_index += 1;
+                            let constant = Self::get_constant(buffer.as_str());
+                            let kind = match variable.or(constant) {
+                                Some(_) => TokenKind::VariableKnown,
+                                None => TokenKind::VariableUnknown,
+                            };
+                            add_token(kind, buffer_start..buffer_end);
+                            if let Some(info) = info.as_deref_mut() {
+                                if variable.is_some() {
+                                    info.variables.insert(buffer.clone());
+                                } else if constant.is_none() {
+                                    info.unknown_variables.insert(buffer.clone());
+                                }
+                            }
                         }
                         current_factor_type = FactorType::None;
                     }
                 }
                 // If the current factor type is none, try to check which factor type the current
-                // character should be and continue the parsing
-                FactorType::None => {
+                // character should be and continue the parsing. Functions are never parsed
+                // character by character
+                FactorType::None | FactorType::Function => {
                     buffer.clear();
+                    (buffer_start, buffer_end) = (position, position);
                     if Self::is_number_or_decimal_separator(current) {
                         current_factor_type = FactorType::Number;
+                    } else if current == ';' {
+                        // Argument separators are only used inside the math operators
+                        chars.next();
+                        add_token(TokenKind::Separator, position..chars.position);
                     } else if Self::is_operator(current) {
-                        let next = chars.next().unwrap();
+                        let mut key = chars.next().unwrap().to_string();
+                        // Comparison operators can have two characters (<=, >=, ==, !=)
+                        if matches!(current, '<' | '>' | '=' | '!') && chars.peek() == Some('=') {
+                            key.push(chars.next().unwrap());
+                        }
+                        let kind = match current {
+                            '(' | ')' => TokenKind::Paren,
+                            _ => TokenKind::Operator,
+                        };
+                        add_token(kind, position..chars.position);
                         result.push(Factor::new(
-                            running_index,
-                            1,
-                            next.to_string(),
+                            position as isize,
+                            (chars.position - position) as isize,
+                            key,
                             FactorType::Operator,
                         ));
                         current_factor_type = FactorType::None;
-                        running_index += 1;
                     } else {
                         current_factor_type = FactorType::Variable;
                     }
@@ -369,7 +832,10 @@ impl EquationHandler {
         let mut output_queue: Vec<Factor> = Vec::new();
 
         for f in factors {
-            if f.factor_type == FactorType::Number || f.factor_type == FactorType::Variable {
+            if matches!(
+                f.factor_type,
+                FactorType::Number | FactorType::Variable | FactorType::Function
+            ) {
                 output_queue.push(f);
             } else if f.factor_type == FactorType::Operator {
                 if f.key == "(" {
@@ -387,17 +853,16 @@ impl EquationHandler {
                 } else {
                     // Put all operands that have bigger or equals value compared to current operand
                     // to the output queue
-                    while operand_stack.len() > 0 {
+                    while !operand_stack.is_empty() {
                         // Take a peek of the last item (top of the stack)
-                        let peek = operand_stack.last();
-                        if peek.is_some() {
+                        if let Some(peek) = operand_stack.last() {
                             // If the current factors operand value is less or equals to the next in
                             // stack push the next into the output queue
                             // If both operands are '^' characters don't flush the operand stack
                             // because the order of operations is not the same as in dividing and multiplying
                             // e.g. 5^5^5 is the same as (5^(5^5))
-                            if f.get_operand_value() <= peek.unwrap().get_operand_value()
-                                && !(f.key == "^" && peek.unwrap().key == "^")
+                            if f.get_operand_value() <= peek.get_operand_value()
+                                && !(f.key == "^" && peek.key == "^")
                             {
                                 let op = operand_stack.pop().unwrap();
                                 output_queue.push(op);
@@ -411,67 +876,117 @@ impl EquationHandler {
             }
         }
 
-        while operand_stack.len() > 0 {
-            output_queue.push(operand_stack.pop().unwrap());
+        while let Some(element) = operand_stack.pop() {
+            output_queue.push(element);
         }
 
         output_queue
     }
 
-    fn calculate_prefix_notation(&self, mut input_queue: Vec<Factor>) -> Option<f64> {
-        if input_queue.len() == 1 {
-            return Some(input_queue.pop().unwrap().double_value);
-        }
-        let mut output_stack = Vec::new();
-        for current in input_queue {
-            if current.factor_type == FactorType::Number
-                || current.factor_type == FactorType::Variable
-            {
-                output_stack.push(current.double_value);
-            } else if current.factor_type == FactorType::Operator {
-                if output_stack.len() < 2 {
-                    println!("ERROR with prefix notation calculations!");
-                    return None;
+    /// Gets the maximum nesting depth of the parentheses in the formula string. Parentheses in
+    /// the comments (brackets) are skipped.
+    fn get_nesting_depth(formula_string: &str) -> usize {
+        let (mut depth, mut max_depth) = (0usize, 0usize);
+        let mut brackets = false;
+        for c in formula_string.chars() {
+            match c {
+                '[' => brackets = true,
+                ']' => brackets = false,
+                '(' if !brackets => {
+                    depth += 1;
+                    max_depth = max_depth.max(depth);
                 }
-                let pop1 = output_stack.pop().unwrap();
-                let pop2 = output_stack.pop().unwrap();
-                let temp_calc = Factor::perform_calculation(pop2, current, pop1);
-                output_stack.push(temp_calc);
+                ')' if !brackets => depth = depth.saturating_sub(1),
+                _ => {}
             }
         }
+        max_depth
+    }
 
-        if output_stack.len() != 1 {
-            println!("ERROR with prefix notation calculations!");
-            return None;
-        }
-        Some(output_stack.pop().unwrap())
-    }
-
-    fn get_value_from_special_math_op(&self, operation: &str, valuestr: &str) -> Option<f64> {
-        // Create an inner equation handler to handle equations inside math operations
-        // e.g. sin(alpha-50)
-        let mut eq: EquationHandler = EquationHandler::new();
-        // Add the variables from current equation handler to temporary inner equation handler
-        for v in self.variables.iter() {
-            eq.add_variable(v.0, *v.1);
-        }
-        let value = eq.calculate_formula(valuestr);
-        if value.is_none() {
-            return None;
-        }
-        match operation {
-            "sqrt" => Some(value.unwrap().sqrt()),
-            "abs" => Some(value.unwrap().abs()),
-            "sin" => Some(value.unwrap().sin()),
-            "cos" => Some(value.unwrap().cos()),
-            "tan" => Some(value.unwrap().tan()),
-            "acos" => Some(value.unwrap().acos()),
-            "asin" => Some(value.unwrap().asin()),
-            "atan" => Some(value.unwrap().atan()),
-            "log" => Some(value.unwrap().log(std::f64::consts::E)),
-            "log10" => Some(value.unwrap().log10()),
-            _ => None,
+    /// Returns the math operator (see [`MATH_OPERATORS`]) matching the given name. The name is
+    /// case insensitive.
+    fn get_math_operator(name: &str) -> Option<&'static str> {
+        MATH_OPERATORS.iter().find(|op| op.eq_ignore_ascii_case(name)).copied()
+    }
+
+    /// Compiles the math operator call into a function factor. The arguments are separated with
+    /// semicolons. Returns an error if the number of arguments doesn't match the operator
+    /// signature, if the bound variable of a binding operator (see [`BINDING_OPERATORS`]) is not
+    /// a name or if the table of a table operator (see [`TABLE_OPERATORS`]) is not found.
+    fn compile_function(
+        &self,
+        operator: &str,
+        arguments_string: &str,
+        function_depth: usize,
+        token_count: &mut usize,
+        index: isize,
+    ) -> Result<Factor, EquationError> {
+        let invalid = || EquationError::InvalidFormula {
+            formula: format!("{operator}({arguments_string})"),
+        };
+        let arguments = Self::get_function_arguments(arguments_string.chars());
+        let signature = FUNCTION_SIGNATURES.iter().find(|f| f.name == operator);
+        if !signature.is_some_and(|f| f.accepts_arguments(arguments.len())) {
+            return Err(invalid());
+        }
+        let mut args = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            args.push(self.compile_internal(argument.as_str(), function_depth, token_count)?);
+        }
+        let length = arguments_string.len() as isize;
+        self.new_function_factor(operator, args, index, length, &invalid)
+    }
+
+    /// Creates the function factor from the compiled arguments. The number of the arguments
+    /// must already be checked. Returns an error if the bound variable of a binding operator is
+    /// not a name or if the table of a table operator is not found.
+    fn new_function_factor(
+        &self,
+        operator: &str,
+        args: Vec<CompiledFormula>,
+        index: isize,
+        length: isize,
+        invalid: &dyn Fn() -> EquationError,
+    ) -> Result<Factor, EquationError> {
+        let mut table = None;
+        if BINDING_OPERATORS.contains(&operator) && args[1].get_variable_name().is_none() {
+            return Err(invalid());
+        } else if TABLE_OPERATORS.contains(&operator) {
+            let name = args[0].get_variable_name().ok_or_else(invalid)?;
+            let found = self.find_table(name);
+            let found = found.ok_or_else(|| EquationError::UnknownTable { name: name.to_string() })?;
+            table = Some(Arc::new(found.clone()));
+        }
+        let mut function = Factor::new_function(index, length, operator.to_string(), args);
+        function.table = table;
+        Ok(function)
+    }
+
+    /// Splits the arguments of a math operator at the semicolons that are not inside
+    /// parentheses. The characters after the closing parenthesis of the operator are ignored.
+    fn get_function_arguments(chars: impl Iterator<Item = char>) -> Vec<String> {
+        let mut result = vec![String::new()];
+        let mut open_parenthesis_count = 0;
+        for c in chars {
+            match c {
+                ')' if open_parenthesis_count == 0 => break,
+                ';' if open_parenthesis_count == 0 => {
+                    result.push(String::new());
+                    continue;
+                }
+                '(' => open_parenthesis_count += 1,
+                ')' => open_parenthesis_count -= 1,
+                _ => {}
+            }
+            result.last_mut().unwrap().push(c);
         }
+        result
+    }
+
+    /// Returns the value of the built-in constant (see [`CONSTANTS`]) matching the given name.
+    /// The name is case insensitive.
+    fn get_constant(name: &str) -> Option<f64> {
+        CONSTANTS.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| *v)
     }
 
     fn is_number_or_decimal_separator(c: char) -> bool {
@@ -482,35 +997,163 @@ impl EquationHandler {
         c.is_ascii_alphanumeric() || c == '_' || c == ','
     }
 
+    /// Checks if the name is scanned as a single variable in the formulas (e.g. `x_1` or `Ø`)
+    fn is_variable_name(name: &str) -> bool {
+        let is_name_char = |c: char| {
+            !Self::is_operator(c) && !c.is_whitespace() && !matches!(c, ';' | '[' | ']')
+        };
+        name.chars().next().is_some_and(|c| !Self::is_number_or_decimal_separator(c))
+            && name.chars().all(is_name_char)
+    }
+
     fn is_operator(c: char) -> bool {
         c == '+' || c == '-' || c == '/' || c == '*' || c == '(' || c == ')' || c == '^' || c == '$'
+            || Self::is_comparison_operator(c)
+    }
+
+    fn is_comparison_operator(c: char) -> bool {
+        c == '<' || c == '>' || c == '=' || c == '!'
+    }
+}
+
+/// Iterator over the characters of the formula string that keeps track of the character index
+/// of the next character
+#[derive(Clone)]
+struct FormulaChars<'a> {
+    chars: std::str::Chars<'a>,
+    position: usize,
+}
+
+impl<'a> FormulaChars<'a> {
+    fn new(formula_string: &'a str) -> Self {
+        FormulaChars { chars: formula_string.chars(), position: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.clone().next()
+    }
+}
+
+impl Iterator for FormulaChars<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.position += 1;
+        Some(c)
     }
 }
 
+// EquationHandler is shared between threads (see EquationHandler::snapshot), so make sure it stays
+// Send and Sync
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<EquationHandler>();
+};
+
 impl Clone for EquationHandler {
     fn clone(&self) -> Self {
-        let mut new_eq = EquationHandler::new();
-        for (k, v) in self.variables.iter() {
-            new_eq.add_variable(k, *v);
+        EquationHandler {
+            key_policy: self.key_policy,
+            limits: self.limits,
+            variables: self.variables.clone(),
+            scopes: self.scopes.clone(),
+            distributions: self.distributions.clone(),
+            tables: self.tables.clone(),
+            parent: self.parent.clone(),
         }
-        new_eq
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// The serialized form of [`EquationHandler`]. The keys are normalized with the key policy when
+/// deserializing.
+#[derive(Deserialize)]
+struct EquationHandlerData {
+    #[serde(default)]
+    key_policy: KeyPolicy,
+    #[serde(default)]
+    limits: EvaluationLimits,
+    variables: HashMap<String, f64>,
+    #[serde(default)]
+    scopes: Vec<HashMap<String, f64>>,
+    #[serde(default)]
+    distributions: HashMap<String, Distribution>,
+    #[serde(default)]
+    tables: HashMap<String, Table>,
+}
+
+impl From<EquationHandlerData> for EquationHandler {
+    fn from(data: EquationHandlerData) -> Self {
+        let mut eq = EquationHandler::new_with_key_policy(data.key_policy);
+        eq.limits = data.limits;
+        eq.set_variables(data.variables);
+        for scope in data.scopes {
+            eq.push_scope();
+            eq.set_variables(scope);
+        }
+        for (k, v) in data.distributions {
+            eq.set_distribution(&k, v);
+        }
+        for (k, v) in data.tables {
+            eq.add_table(&k, v);
+        }
+        eq
+    }
+}
+
+/// Information about the contents of a formula. See [`EquationHandler::analyze`].
+/// The variable names are normalized with the [`KeyPolicy`] of the [`EquationHandler`] and the
+/// math operator names are lowercase.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormulaInfo {
+    /// Variables used in the formula that are set in the equation handler
+    pub variables: BTreeSet<String>,
+    /// Variables used in the formula that are not set in the equation handler (built-in
+    /// constants are not included, see [`CONSTANTS`])
+    pub unknown_variables: BTreeSet<String>,
+    /// Math operators (see [`MATH_OPERATORS`]) called in the formula
+    pub functions: BTreeSet<String>,
+    /// Operators (+, -, *, /, ^) used in the formula
+    pub operators: BTreeSet<String>,
+    /// Variables bound by the binding operators (see [`BINDING_OPERATORS`]). They are not
+    /// included in the unknown variables.
+    pub bound_variables: BTreeSet<String>,
+    /// Tables used by the table operators (see [`TABLE_OPERATORS`]). They are not included in
+    /// the unknown variables.
+    pub tables: BTreeSet<String>,
+    /// True if the formula contains comments in brackets (e.g. units `10 [kN] * 5 [m]`)
+    pub has_unit_comments: bool,
+    /// The maximum nesting depth of parentheses (including the math operators parentheses)
+    pub max_depth: usize,
+}
+
+impl FormulaInfo {
+    /// Returns all the variables used in the formula (known and unknown)
+    pub fn all_variables(&self) -> BTreeSet<String> {
+        self.variables.union(&self.unknown_variables).cloned().collect()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum FactorType {
     Number = 1,
     Variable = 2,
     Operator = 3,
+    Function = 4,
     None = 0,
 }
 
+#[derive(Clone)]
 pub struct Factor {
     index: isize,
     length: isize,
     double_value: f64,
     key: String,
     factor_type: FactorType,
+    /// The compiled arguments of a function factor
+    args: Vec<CompiledFormula>,
+    /// The table of a table operator (see [`TABLE_OPERATORS`])
+    table: Option<Arc<Table>>,
 }
 
 impl Factor {
@@ -521,6 +1164,8 @@ impl Factor {
             double_value: 0.0,
             key,
             factor_type,
+            args: Vec::new(),
+            table: None,
         }
     }
 
@@ -531,6 +1176,20 @@ impl Factor {
             double_value,
             key,
             factor_type: FactorType::Variable,
+            args: Vec::new(),
+            table: None,
+        }
+    }
+
+    pub fn new_function(index: isize, length: isize, key: String, args: Vec<CompiledFormula>) -> Self {
+        Factor {
+            index,
+            length,
+            double_value: 0.0,
+            key,
+            factor_type: FactorType::Function,
+            args,
+            table: None,
         }
     }
 
@@ -541,6 +1200,8 @@ impl Factor {
             double_value,
             key: "\0".to_string(),
             factor_type: FactorType::Number,
+            args: Vec::new(),
+            table: None,
         }
     }
 
@@ -548,33 +1209,47 @@ impl Factor {
         match self.factor_type {
             FactorType::Operator => {
                 if self.key == "(" || self.key == ")" {
-                    return 0;
+                    0
+                } else if COMPARISON_OPERATORS.contains(&self.key.as_str()) {
+                    1
                 } else if self.key == "+" || self.key == "-" {
-                    return 1;
+                    2
                 } else if self.key == "*" || self.key == "/" {
-                    return 2;
-                } else {
                     3
+                } else {
+                    4
                 }
             }
-            _ => 3,
+            _ => 4,
         }
     }
 
     pub fn perform_calculation(value: f64, op: Factor, f2: f64) -> f64 {
         match op.factor_type {
-            FactorType::Operator => match op.key.as_str() {
-                "+" => value + f2,
-                "-" => value - f2,
-                "*" => value * f2,
-                "/" => value / f2,
-                "^" => value.powf(f2),
-                "$" => value*10f64.powf(f2),
-                _ => 0.0,
-            },
+            FactorType::Operator => Self::apply_operator(op.key.as_str(), value, f2).unwrap_or(0.0),
             _ => 0.0,
         }
     }
+
+    /// Applies the operator with the given key to the values. Returns None if the key is not an
+    /// operator.
+    pub fn apply_operator<T: Scalar>(key: &str, value: T, f2: T) -> Option<T> {
+        match key {
+            "+" => Some(value + f2),
+            "-" => Some(value - f2),
+            "*" => Some(value * f2),
+            "/" => Some(value / f2),
+            "^" => Some(value.pow(f2)),
+            "$" => Some(value * T::from_f64(10.0).pow(f2)),
+            "<" => Some(value.less_than(&f2)),
+            ">" => Some(f2.less_than(&value)),
+            "<=" => Some(T::from_f64(1.0) - f2.less_than(&value)),
+            ">=" => Some(T::from_f64(1.0) - value.less_than(&f2)),
+            "==" => Some(value.equals(&f2)),
+            "!=" => Some(T::from_f64(1.0) - value.equals(&f2)),
+            _ => None,
+        }
+    }
 }
 
 impl Debug for Factor {
@@ -589,6 +1264,9 @@ impl Debug for Factor {
             FactorType::Operator => {
                 write!(f, "{:.1}", self.key)
             }
+            FactorType::Function => {
+                write!(f, "{}({:?})", self.key, self.args)
+            }
             FactorType::None => {
                 write!(f, "None!")
             }
@@ -598,14 +1276,16 @@ impl Debug for Factor {
 
 #[cfg(test)]
 pub mod tests {
-    use crate::equation_handler::EquationHandler;
+    use crate::equation_handler::{EquationError, EquationHandler, EvaluationLimits, KeyPolicy};
+    use std::collections::BTreeSet;
+    use std::sync::Arc;
 
     #[test]
     fn equation_handler() {
         let mut equation_handler = EquationHandler::new();
         equation_handler.add_variable("x", 1.0);
         equation_handler.add_variable("y", 1.0);
-        assert_eq!(equation_handler.add_variable("x", 1.0), false);
+        assert!(!equation_handler.add_variable("x", 1.0));
         assert_eq!(equation_handler.calculate_formula("x+y"), Some(2.0));
     }
 
@@ -620,7 +1300,8 @@ pub mod tests {
         let mut equation_handler = EquationHandler::new();
         equation_handler.add_variable("TESTI", 1.0);
         let factors = equation_handler
-            .populate_lists_streaming(EquationHandler::handle_string_formatting(s).as_str());
+            .populate_lists_streaming(EquationHandler::handle_string_formatting(s).as_str())
+            .unwrap();
         assert_eq!(factors.len(), expected);
     }
 
@@ -734,6 +1415,10 @@ pub mod tests {
             EquationHandler::handle_string_formatting("3,49199E-06"),
             "3,49199$(0-06)"
         );
+        assert_eq!(
+            EquationHandler::handle_string_formatting("(-5+(-3"),
+            "(0-5+(0-3"
+        );
     }
 
     #[test]
@@ -912,6 +1597,17 @@ pub mod tests {
         );        
     }
 
+    #[test]
+    fn e_notation_at_end() {
+        let equation_handler = EquationHandler::new();
+        // The 'e' is the constant, not an exponent
+        assert!(equation_handler.calculate_formula("5e").is_none());
+        assert_eq!(
+            equation_handler.calculate_formula("5*e"),
+            Some(5.0 * std::f64::consts::E)
+        );
+    }
+
     #[test] 
     fn test_unicode_variables() {
         let mut equation_handler = EquationHandler::new();
@@ -920,6 +1616,227 @@ pub mod tests {
             (equation_handler.calculate_formula("Ø*60").unwrap() - 960.0).abs() < 0.00001 );
     }
 
+    #[test]
+    fn case_insensitive_keys() {
+        let mut equation_handler = EquationHandler::new();
+        equation_handler.add_variable("TESTI", 10.0);
+        assert!(equation_handler.variable_is_set("TESTI"));
+        assert!(equation_handler.variable_is_set("testi"));
+        assert_eq!(equation_handler.get_variable("TESTI"), Some(10.0));
+        assert_eq!(equation_handler.calculate_formula("Testi*2"), Some(20.0));
+        equation_handler.remove_variable("TESTI");
+        assert!(!equation_handler.variable_is_set("testi"));
+    }
+
+    #[test]
+    fn case_sensitive_keys() {
+        let mut equation_handler = EquationHandler::new_with_key_policy(KeyPolicy::CaseSensitive);
+        equation_handler.add_variable("M", 10.0);
+        equation_handler.add_variable("m", 2.0);
+        assert_eq!(equation_handler.get_variable("M"), Some(10.0));
+        assert_eq!(equation_handler.get_variable("m"), Some(2.0));
+        assert_eq!(equation_handler.calculate_formula("M/m"), Some(5.0));
+        assert_eq!(equation_handler.calculate_formula("SQRT(M*m/5)+Abs(0-m)"), Some(4.0));
+        equation_handler.remove_variable("M");
+        assert!(!equation_handler.variable_is_set("M"));
+        assert!(equation_handler.variable_is_set("m"));
+
+        equation_handler.set_variable("M", 1.0);
+        equation_handler.set_key_policy(KeyPolicy::CaseInsensitive);
+        assert_eq!(equation_handler.get_variables().len(), 1);
+        assert!(equation_handler.variable_is_set("M"));
+    }
+
+    #[test]
+    fn serde_key_policy() {
+        let mut original = EquationHandler::new_with_key_policy(KeyPolicy::CaseSensitive);
+        original.add_variable("M", 10.0);
+        original.add_variable("m", 2.0);
+        let serialized = serde_json::to_string(&original).unwrap();
+        let deserialized: EquationHandler = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.get_key_policy(), KeyPolicy::CaseSensitive);
+        assert_eq!(deserialized.get_variable("M"), Some(10.0));
+        assert_eq!(deserialized.get_variable("m"), Some(2.0));
+
+        // Keys are normalized when deserializing and the policy defaults to case insensitive
+        let deserialized: EquationHandler =
+            serde_json::from_str(r#"{"variables":{"TESTI":5.0}}"#).unwrap();
+        assert_eq!(deserialized.get_key_policy(), KeyPolicy::CaseInsensitive);
+        assert_eq!(deserialized.get_variables().get("testi"), Some(&5.0));
+    }
+
+    #[test]
+    fn scopes() {
+        let mut equation_handler = EquationHandler::from([("a", 1.0), ("b", 2.0)]);
+        equation_handler.push_scope();
+        equation_handler.set_variable("a", 10.0);
+        assert!(equation_handler.add_variable("c", 3.0));
+        assert_eq!(equation_handler.scope_depth(), 1);
+        assert_eq!(equation_handler.calculate_formula("a+b+c"), Some(15.0));
+        assert_eq!(equation_handler.calculate_formula("sqrt(a*10)+c"), Some(13.0));
+        assert_eq!(equation_handler.get_variables().get("a"), Some(&10.0));
+
+        let popped = equation_handler.pop_scope().unwrap();
+        assert_eq!(popped.len(), 2);
+        assert_eq!(equation_handler.calculate_formula("a+b"), Some(3.0));
+        assert!(!equation_handler.variable_is_set("c"));
+        assert!(equation_handler.pop_scope().is_none());
+    }
+
+    #[test]
+    fn parent_chaining() {
+        let project = Arc::new(EquationHandler::from([("gamma", 1.5), ("fy", 355.0)]));
+        let mut element = EquationHandler::new_child(project.clone());
+        element.set_variable("A", 100.0);
+        assert_eq!(element.calculate_formula("A*fy/gamma"), Some(100.0 * 355.0 / 1.5));
+
+        // Load case override hides the project level value
+        element.push_scope();
+        element.set_variable("gamma", 1.0);
+        assert_eq!(element.calculate_formula("A*fy/gamma"), Some(35500.0));
+        element.pop_scope();
+        assert_eq!(element.get_variable("gamma"), Some(1.5));
+        assert_eq!(element.get_variables().len(), 3);
+        assert_eq!(project.get_variables().len(), 2);
+    }
+
+    #[test]
+    fn concurrent_calculations() {
+        let formulas = ["a*b+c", "sqrt(a*a+b*b)", "(a+b)^2/c", "sin(a)*cos(b)", "log10(c*1000)"];
+        let mut equation_handler = EquationHandler::from([("a", 3.0), ("b", 4.0), ("c", 5.0)]);
+        equation_handler.push_scope();
+        equation_handler.set_variable("c", 10.0);
+        let shared = equation_handler.snapshot();
+        let expected = equation_handler.calculate_formulas(&formulas);
+
+        // Later changes to the original handler don't affect the snapshot
+        equation_handler.set_variable("a", 0.0);
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    let shared = Arc::clone(&shared);
+                    scope.spawn(move || shared.calculate_formulas(&formulas))
+                })
+                .collect();
+            for handle in handles {
+                assert_eq!(handle.join().unwrap(), expected);
+            }
+        });
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_calculations() {
+        let equation_handler = EquationHandler::from([("x", 2.0)]);
+        let formulas: Vec<String> = (0..1000).map(|i| format!("x*{i}+sqrt({i})")).collect();
+        assert_eq!(
+            equation_handler.calculate_formulas_parallel(&formulas),
+            equation_handler.calculate_formulas(&formulas)
+        );
+    }
+
+    #[test]
+    fn evaluation_limits() {
+        let mut equation_handler = EquationHandler::from([("x", 4.0)]);
+        equation_handler.set_limits(EvaluationLimits {
+            max_formula_length: 40,
+            max_token_count: 9,
+            max_nesting_depth: 3,
+            max_function_depth: 2,
+            max_evaluation_steps: 100,
+        });
+        assert_eq!(equation_handler.try_calculate_formula("sqrt(abs(x))"), Ok(2.0));
+        assert_eq!(
+            equation_handler.try_calculate_formula("sqrt(abs(sqrt(x)))"),
+            Err(EquationError::FunctionRecursionTooDeep { depth: 3, max: 2 })
+        );
+        assert_eq!(
+            equation_handler.try_calculate_formula("((((x))))"),
+            Err(EquationError::NestingTooDeep { depth: 4, max: 3 })
+        );
+        assert_eq!(
+            equation_handler.try_calculate_formula("1+1+1+1+1+1"),
+            Err(EquationError::TooManyTokens { count: 11, max: 9 })
+        );
+        // The factors of the math operator arguments are counted together with the rest
+        assert_eq!(
+            equation_handler.try_calculate_formula("abs(1+1+1)+abs(1+1+1)"),
+            Err(EquationError::TooManyTokens { count: 10, max: 9 })
+        );
+        assert_eq!(
+            equation_handler.try_calculate_formula(&"1".repeat(41)),
+            Err(EquationError::FormulaTooLong { length: 41, max: 40 })
+        );
+        assert_eq!(equation_handler.calculate_formula("((((x))))"), None);
+        assert_eq!(equation_handler.try_calculate_formula("sum(i;i;1;100)"), Ok(5050.0));
+        assert_eq!(
+            equation_handler.try_calculate_formula("sum(i;i;1;101)"),
+            Err(EquationError::TooManyEvaluationSteps { max: 100 })
+        );
+        assert!(matches!(
+            equation_handler.try_calculate_formula("x+"),
+            Err(EquationError::InvalidFormula { .. })
+        ));
+
+        // Runaway recursion is stopped with the default limits instead of overflowing the stack
+        let equation_handler = EquationHandler::new();
+        let formula = format!("{}1{}", "abs(".repeat(5000), ")".repeat(5000));
+        assert!(equation_handler.try_calculate_formula(&formula).is_err());
+        // The steps of the nested operators are counted together
+        let formula = "sum(sum(1; j; 1; 9999999); i; 1; 9999999)";
+        assert_eq!(
+            equation_handler.try_calculate_formula(formula),
+            Err(EquationError::TooManyEvaluationSteps { max: 10_000_000 })
+        );
+        let formula = "integrate(integrate(x*y; y; 0; 1); x; 0; 1)";
+        assert!((equation_handler.try_calculate_formula(formula).unwrap() - 0.25).abs() < 1e-9);
+        let mut equation_handler = EquationHandler::new();
+        equation_handler.set_limits(EvaluationLimits::unlimited());
+        let formula = format!("{}1{}", "abs(".repeat(20), ")".repeat(20));
+        assert_eq!(equation_handler.calculate_formula(&formula), Some(1.0));
+    }
+
+    #[test]
+    fn constants() {
+        let mut equation_handler = EquationHandler::new();
+        assert_eq!(equation_handler.calculate_formula("2*PI"), Some(2.0 * std::f64::consts::PI));
+        assert_eq!(equation_handler.calculate_formula("log(e)"), Some(1.0));
+        assert!(equation_handler.analyze("pi*r^2").unknown_variables.contains("r"));
+        assert!(!equation_handler.analyze("pi*r^2").unknown_variables.contains("pi"));
+        // Variables override the constants
+        equation_handler.add_variable("e", 5.0);
+        assert_eq!(equation_handler.calculate_formula("e*2"), Some(10.0));
+    }
+
+    #[test]
+    fn unit_comments() {
+        let equation_handler = EquationHandler::from([("L", 5.0)]);
+        assert_eq!(equation_handler.calculate_formula("10 [kN] * 5 [m]"), Some(50.0));
+        assert_eq!(equation_handler.calculate_formula("10[kN]*L[m]+1"), Some(51.0));
+    }
+
+    #[test]
+    fn analyze() {
+        let equation_handler = EquationHandler::from([("x", 1.0), ("y", 2.0)]);
+        let info = equation_handler.analyze("sqrt(X*(y+z)) * 2E-3 [kN] ^ abs(q)");
+        assert_eq!(info.variables, BTreeSet::from(["x".to_string(), "y".to_string()]));
+        assert_eq!(info.unknown_variables, BTreeSet::from(["q".to_string(), "z".to_string()]));
+        assert_eq!(info.functions, BTreeSet::from(["abs".to_string(), "sqrt".to_string()]));
+        assert_eq!(
+            info.operators,
+            BTreeSet::from(["*".to_string(), "+".to_string(), "^".to_string()])
+        );
+        assert!(info.has_unit_comments);
+        assert_eq!(info.max_depth, 2);
+        assert_eq!(info.all_variables().len(), 4);
+
+        let info = equation_handler.analyze("x+y");
+        assert!(!info.has_unit_comments);
+        assert_eq!(info.max_depth, 0);
+        assert!(info.functions.is_empty());
+    }
+
     #[test]
     fn clone() {
         let original = EquationHandler::from([("x", 1.0), ("y", 2.0)]);
@@ -942,7 +1859,6 @@ pub mod tests {
     fn serde() {
         let original = EquationHandler::from([("x", 1.0), ("y", 2.0)]);
         let serialized = serde_json::to_string(&original).unwrap();
-        println!("{}", serialized);
         let deserialized: EquationHandler = serde_json::from_str(&serialized).unwrap();
         assert_eq!(original.get_variable("x"), Some(1.0));
         assert_eq!(original.get_variable("y"), Some(2.0));
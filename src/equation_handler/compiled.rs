@@ -0,0 +1,586 @@
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::equation_handler::{
+    EquationError, EquationHandler, Factor, FactorType, BINDING_OPERATORS, TABLE_OPERATORS,
+};
+
+/// A number type that the compiled formulas can be evaluated with (e.g. `f64` or
+/// [`Interval`](crate::equation_handler::Interval))
+pub trait Scalar:
+    Clone + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self>
+{
+    fn from_f64(value: f64) -> Self;
+    /// Returns the nominal value. Used for the decisions in the evaluation (e.g. the loop
+    /// bounds of `sum` and the error estimates of `integrate`).
+    fn to_f64(&self) -> f64;
+    /// Returns true if the value is undefined (e.g. NaN or an empty interval)
+    fn is_nan(&self) -> bool;
+    fn pow(self, exponent: Self) -> Self;
+    fn sqrt(self) -> Self;
+    fn abs(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn acos(self) -> Self;
+    fn asin(self) -> Self;
+    fn atan(self) -> Self;
+    fn ln(self) -> Self;
+    fn log10(self) -> Self;
+    /// Returns 1 if the value is less than the other value and 0 otherwise
+    fn less_than(&self, other: &Self) -> Self;
+    /// Returns 1 if the values are equal and 0 otherwise
+    fn equals(&self, other: &Self) -> Self;
+    /// Returns true if the value is not zero. Returns None if it can't be decided (e.g. an
+    /// interval that contains zero).
+    fn truth(&self) -> Option<bool>;
+    /// Returns a value that contains both values. Only needed if [`Scalar::truth`] can return
+    /// None.
+    fn hull(self, other: Self) -> Self;
+}
+
+impl Scalar for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+    fn is_nan(&self) -> bool {
+        f64::is_nan(*self)
+    }
+    fn pow(self, exponent: Self) -> Self {
+        self.powf(exponent)
+    }
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+    fn abs(self) -> Self {
+        f64::abs(self)
+    }
+    fn sin(self) -> Self {
+        f64::sin(self)
+    }
+    fn cos(self) -> Self {
+        f64::cos(self)
+    }
+    fn tan(self) -> Self {
+        f64::tan(self)
+    }
+    fn acos(self) -> Self {
+        f64::acos(self)
+    }
+    fn asin(self) -> Self {
+        f64::asin(self)
+    }
+    fn atan(self) -> Self {
+        f64::atan(self)
+    }
+    fn ln(self) -> Self {
+        f64::ln(self)
+    }
+    fn log10(self) -> Self {
+        f64::log10(self)
+    }
+    fn less_than(&self, other: &Self) -> Self {
+        if self < other {
+            1.0
+        } else {
+            0.0
+        }
+    }
+    fn equals(&self, other: &Self) -> Self {
+        if self == other {
+            1.0
+        } else {
+            0.0
+        }
+    }
+    fn truth(&self) -> Option<bool> {
+        Some(*self != 0.0)
+    }
+    fn hull(self, _other: Self) -> Self {
+        self
+    }
+}
+
+/// A parsed formula that can be evaluated multiple times. See [`EquationHandler::compile`]
+#[derive(Debug, Clone)]
+pub struct CompiledFormula {
+    formula: String,
+    /// The factors in reverse polish notation
+    pub(super) factors: Vec<Factor>,
+    /// Maximum number of evaluation steps in one evaluation (see
+    /// [`EvaluationLimits`](crate::equation_handler::EvaluationLimits))
+    max_steps: usize,
+}
+
+impl CompiledFormula {
+    pub(crate) fn new(formula: &str, factors: Vec<Factor>, max_steps: usize) -> Self {
+        CompiledFormula {
+            formula: formula.to_string(),
+            factors,
+            max_steps,
+        }
+    }
+
+    /// Returns the formula string the formula was compiled from
+    pub fn get_formula(&self) -> &str {
+        &self.formula
+    }
+
+    /// Returns the variable name if the formula is just a single variable (e.g. the bound
+    /// variable of `sum` or the table name of `lookup`)
+    pub(crate) fn get_variable_name(&self) -> Option<&str> {
+        match self.factors.as_slice() {
+            [factor] if factor.factor_type == FactorType::Variable => Some(factor.key.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Returns the (normalized) names of all the variables used in the formula, including the
+    /// variables inside the math operators. Constants are included if they are used.
+    pub fn variables(&self) -> BTreeSet<String> {
+        let mut result = BTreeSet::new();
+        for factor in &self.factors {
+            match factor.factor_type {
+                FactorType::Variable => {
+                    result.insert(factor.key.clone());
+                }
+                FactorType::Function => {
+                    let bound = factor.args.get(1).and_then(|arg| arg.get_variable_name());
+                    for (i, arg) in factor.args.iter().enumerate() {
+                        let mut variables = arg.variables();
+                        if BINDING_OPERATORS.contains(&factor.key.as_str()) {
+                            // The bound variable is not a free variable of the formula
+                            if i == 1 {
+                                continue;
+                            } else if i == 0 {
+                                variables.retain(|k| Some(k.as_str()) != bound);
+                            }
+                        } else if TABLE_OPERATORS.contains(&factor.key.as_str()) && i == 0 {
+                            continue;
+                        }
+                        result.extend(variables);
+                    }
+                }
+                _ => {}
+            }
+        }
+        result
+    }
+
+    /// Evaluates the formula with the current variables of the handler
+    pub fn evaluate(&self, equation_handler: &EquationHandler) -> Result<f64, EquationError> {
+        self.evaluate_with(&|key: &str| equation_handler.find_variable(key))
+    }
+
+    /// Evaluates the formula with the given variable resolver. The keys given to the resolver
+    /// are already normalized. Constants are used for the keys the resolver doesn't know.
+    pub fn evaluate_with<T: Scalar>(
+        &self,
+        resolver: &dyn Fn(&str) -> Option<T>,
+    ) -> Result<T, EquationError> {
+        self.evaluate_steps(resolver, &Cell::new(self.max_steps))
+    }
+
+    /// Evaluates the formula. `steps` is the number of evaluation steps left, shared with the
+    /// nested math operators.
+    fn evaluate_steps<T: Scalar>(
+        &self,
+        resolver: &dyn Fn(&str) -> Option<T>,
+        steps: &Cell<usize>,
+    ) -> Result<T, EquationError> {
+        let invalid = || EquationError::InvalidFormula {
+            formula: self.formula.clone(),
+        };
+        let mut output_stack: Vec<T> = Vec::new();
+        for current in &self.factors {
+            match current.factor_type {
+                FactorType::Number => output_stack.push(T::from_f64(current.double_value)),
+                FactorType::Variable => {
+                    let key = current.key.as_str();
+                    let value = resolver(key)
+                        .or_else(|| EquationHandler::get_constant(key).map(T::from_f64))
+                        .ok_or_else(|| EquationError::UnknownVariable {
+                            name: key.to_string(),
+                        })?;
+                    output_stack.push(value);
+                }
+                FactorType::Function if BINDING_OPERATORS.contains(&current.key.as_str()) => {
+                    let value = self.evaluate_binding(current, resolver, steps)?;
+                    output_stack.push(value);
+                }
+                FactorType::Function if current.key == "piecewise" => {
+                    let value = Self::evaluate_piecewise(&current.args, resolver, steps)?;
+                    output_stack.push(value);
+                }
+                FactorType::Function if current.table.is_some() => {
+                    let table = current.table.as_deref().unwrap();
+                    let x = current.args[1].evaluate_steps(resolver, steps)?;
+                    let y = match current.args.get(2) {
+                        Some(arg) => Some(arg.evaluate_steps(resolver, steps)?),
+                        None => None,
+                    };
+                    let value = match current.key.as_str() {
+                        "lookup" => table.lookup(x, y),
+                        _ => table.interp(x, y),
+                    };
+                    output_stack.push(value.ok_or_else(invalid)?);
+                }
+                FactorType::Function => {
+                    let mut args = Vec::with_capacity(current.args.len());
+                    for arg in &current.args {
+                        args.push(arg.evaluate_steps(resolver, steps)?);
+                    }
+                    let value = apply_function(current.key.as_str(), &args).ok_or_else(invalid)?;
+                    output_stack.push(value);
+                }
+                FactorType::Operator => {
+                    if output_stack.len() < 2 {
+                        return Err(invalid());
+                    }
+                    let pop1 = output_stack.pop().unwrap();
+                    let pop2 = output_stack.pop().unwrap();
+                    let value = Factor::apply_operator(current.key.as_str(), pop2, pop1)
+                        .ok_or_else(invalid)?;
+                    output_stack.push(value);
+                }
+                FactorType::None => {}
+            }
+        }
+        if output_stack.len() != 1 {
+            return Err(invalid());
+        }
+        Ok(output_stack.pop().unwrap())
+    }
+
+    /// Evaluates `piecewise(cond1; value1; cond2; value2; ...; default)`. Only the values of the
+    /// conditions that are needed are evaluated. If a condition can't be decided (e.g. with
+    /// intervals), the result contains both the value and the rest of the alternatives.
+    fn evaluate_piecewise<T: Scalar>(
+        args: &[CompiledFormula],
+        resolver: &dyn Fn(&str) -> Option<T>,
+        steps: &Cell<usize>,
+    ) -> Result<T, EquationError> {
+        match args {
+            [default] => default.evaluate_steps(resolver, steps),
+            [condition, value, rest @ ..] => {
+                match condition.evaluate_steps(resolver, steps)?.truth() {
+                    Some(true) => value.evaluate_steps(resolver, steps),
+                    Some(false) => Self::evaluate_piecewise(rest, resolver, steps),
+                    None => {
+                        let value = value.evaluate_steps(resolver, steps)?;
+                        Ok(value.hull(Self::evaluate_piecewise(rest, resolver, steps)?))
+                    }
+                }
+            }
+            [] => Err(EquationError::InvalidFormula {
+                formula: "piecewise()".to_string(),
+            }),
+        }
+    }
+
+    /// Evaluates a binding operator (`integrate`, `sum` or `prod`). The bound variable is
+    /// resolved like a variable in a scope on top of the resolver. Each term of `sum` and `prod`
+    /// and each evaluation of the integrand takes one of the evaluation steps left.
+    fn evaluate_binding<T: Scalar>(
+        &self,
+        factor: &Factor,
+        resolver: &dyn Fn(&str) -> Option<T>,
+        steps: &Cell<usize>,
+    ) -> Result<T, EquationError> {
+        let invalid = || EquationError::InvalidFormula {
+            formula: self.formula.clone(),
+        };
+        let [expression, variable, from, to] = factor.args.as_slice() else {
+            return Err(invalid());
+        };
+        let variable = variable.get_variable_name().ok_or_else(invalid)?;
+        let from = from.evaluate_steps(resolver, steps)?;
+        let to = to.evaluate_steps(resolver, steps)?;
+        let evaluate = |x: T| {
+            let resolver = |key: &str| {
+                if key == variable {
+                    Some(x.clone())
+                } else {
+                    resolver(key)
+                }
+            };
+            expression.evaluate_steps(&resolver, steps)
+        };
+        match factor.key.as_str() {
+            "integrate" => {
+                let f = |x: T| {
+                    self.take_steps(steps, 1.0)?;
+                    evaluate(x)
+                };
+                integrate(&f, from, to)
+            }
+            "sum" | "prod" => {
+                let (from, to) = (from.to_f64().round(), to.to_f64().round());
+                if !from.is_finite() || !to.is_finite() {
+                    return Err(invalid());
+                }
+                // All the terms are taken before the evaluation, so the nested operators only
+                // get the steps that are left
+                self.take_steps(steps, (to - from + 1.0).max(0.0))?;
+                let product = factor.key == "prod";
+                let mut result = T::from_f64(if product { 1.0 } else { 0.0 });
+                let mut i = from;
+                while i <= to {
+                    let value = evaluate(T::from_f64(i))?;
+                    result = if product {
+                        result * value
+                    } else {
+                        result + value
+                    };
+                    i += 1.0;
+                }
+                Ok(result)
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Takes the given number of evaluation steps from the steps left. Returns an error if there
+    /// are not enough steps left.
+    fn take_steps(&self, steps: &Cell<usize>, count: f64) -> Result<(), EquationError> {
+        if count > steps.get() as f64 {
+            return Err(EquationError::TooManyEvaluationSteps {
+                max: self.max_steps,
+            });
+        }
+        steps.set(steps.get().saturating_sub(count as usize));
+        Ok(())
+    }
+}
+
+/// Maximum number of bisections in `integrate`
+const MAX_INTEGRATION_DEPTH: usize = 30;
+
+/// Nodes of the 15-point Kronrod rule on [-1, 1] (the positive half, the last one is zero). The
+/// nodes with odd indices are the nodes of the 7-point Gauss rule.
+#[allow(clippy::excessive_precision)]
+const KRONROD_NODES: [f64; 8] = [
+    0.991455371120812639206854697526329,
+    0.949107912342758524526189684047851,
+    0.864864423359769072789712788640926,
+    0.741531185599394439863864773280788,
+    0.586087235467691130294144845693013,
+    0.405845151377397166906606412076961,
+    0.207784955007898467600689403773245,
+    0.0,
+];
+
+#[allow(clippy::excessive_precision)]
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022935322010529224963732008058970,
+    0.063092092629978553290700663189204,
+    0.104790010322250183839876322541518,
+    0.140653259715525918745189590510238,
+    0.169004726639267902826583426598550,
+    0.190350578064785409913256402421014,
+    0.204432940075298892414161999234649,
+    0.209482141084727828012999174891714,
+];
+
+#[allow(clippy::excessive_precision)]
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.129484966168869693270611432679082,
+    0.279705391489276667901467771423780,
+    0.381830050505118944950369775488975,
+    0.417959183673469387755102040816327,
+];
+
+/// Integrates the function from `a` to `b` with the adaptive Gauss-Kronrod (G7-K15) quadrature.
+/// The integration is done over `t` in [0, 1] where `x = a + (b - a) * t`, so the bounds can be
+/// any scalars (e.g. dual numbers for the derivatives with respect to the bounds).
+fn integrate<T: Scalar>(
+    f: &dyn Fn(T) -> Result<T, EquationError>,
+    a: T,
+    b: T,
+) -> Result<T, EquationError> {
+    let length = b - a.clone();
+    let g = |t: f64| f(a.clone() + length.clone() * T::from_f64(t));
+    let (kronrod, gauss) = gauss_kronrod(&g, 0.0, 1.0)?;
+    let tolerance = 1e-10 * kronrod.to_f64().abs().max(1e-10);
+    let result = integrate_adaptive(&g, 0.0, 1.0, kronrod, gauss, tolerance, 0)?;
+    Ok(result * length)
+}
+
+fn integrate_adaptive<T: Scalar>(
+    g: &dyn Fn(f64) -> Result<T, EquationError>,
+    t0: f64,
+    t1: f64,
+    kronrod: T,
+    gauss: T,
+    tolerance: f64,
+    depth: usize,
+) -> Result<T, EquationError> {
+    let error = (kronrod.to_f64() - gauss.to_f64()).abs();
+    if error <= tolerance || depth >= MAX_INTEGRATION_DEPTH || !error.is_finite() {
+        return Ok(kronrod);
+    }
+    let middle = (t0 + t1) / 2.0;
+    let (k1, g1) = gauss_kronrod(g, t0, middle)?;
+    let (k2, g2) = gauss_kronrod(g, middle, t1)?;
+    let left = integrate_adaptive(g, t0, middle, k1, g1, tolerance / 2.0, depth + 1)?;
+    let right = integrate_adaptive(g, middle, t1, k2, g2, tolerance / 2.0, depth + 1)?;
+    Ok(left + right)
+}
+
+/// Calculates the 15-point Kronrod and the 7-point Gauss estimates of the integral from `t0` to
+/// `t1`
+fn gauss_kronrod<T: Scalar>(
+    g: &dyn Fn(f64) -> Result<T, EquationError>,
+    t0: f64,
+    t1: f64,
+) -> Result<(T, T), EquationError> {
+    let half = (t1 - t0) / 2.0;
+    let center = t0 + half;
+    let center_value = g(center)?;
+    let mut kronrod = center_value.clone() * T::from_f64(KRONROD_WEIGHTS[7] * half);
+    let mut gauss = center_value * T::from_f64(GAUSS_WEIGHTS[3] * half);
+    for i in 0..7 {
+        let pair = g(center - half * KRONROD_NODES[i])? + g(center + half * KRONROD_NODES[i])?;
+        kronrod = kronrod + pair.clone() * T::from_f64(KRONROD_WEIGHTS[i] * half);
+        if i % 2 == 1 {
+            gauss = gauss + pair * T::from_f64(GAUSS_WEIGHTS[i / 2] * half);
+        }
+    }
+    Ok((kronrod, gauss))
+}
+
+/// Applies the math operator with the given name to the arguments. Returns None if the
+/// operator is unknown or the number of arguments is wrong.
+fn apply_function<T: Scalar>(name: &str, args: &[T]) -> Option<T> {
+    let value = match args {
+        [value] => value.clone(),
+        _ => return None,
+    };
+    let result = match name {
+        "sqrt" => value.sqrt(),
+        "abs" => value.abs(),
+        "sin" => value.sin(),
+        "cos" => value.cos(),
+        "tan" => value.tan(),
+        "acos" => value.acos(),
+        "asin" => value.asin(),
+        "atan" => value.atan(),
+        "log" => value.ln(),
+        "log10" => value.log10(),
+        _ => return None,
+    };
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equation_handler::Interval;
+    use std::collections::HashMap;
+
+    #[test]
+    fn compiled_formula() {
+        let mut equation_handler = EquationHandler::from([("a", 2.0), ("B", 3.0)]);
+        let compiled = equation_handler.compile("a*b+sqrt(A*8)+pi*0").unwrap();
+        assert_eq!(compiled.evaluate(&equation_handler), Ok(10.0));
+        equation_handler.set_variable("a", 4.0);
+        assert_eq!(
+            compiled.evaluate(&equation_handler),
+            Ok(12.0 + 32f64.sqrt())
+        );
+        let expected: BTreeSet<String> = ["a", "b", "pi"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(compiled.variables(), expected);
+
+        let resolver = |key: &str| if key == "a" { Some(1.0) } else { None };
+        assert_eq!(
+            compiled.evaluate_with(&resolver),
+            Err(EquationError::UnknownVariable {
+                name: "b".to_string()
+            })
+        );
+        assert!(equation_handler.compile("sqrt(2").is_err());
+        assert!(equation_handler
+            .compile("2+")
+            .unwrap()
+            .evaluate(&equation_handler)
+            .is_err());
+    }
+
+    #[test]
+    fn integrate_sum_prod() {
+        let equation_handler = EquationHandler::from([("q", 2.0), ("L", 6.0), ("x", 100.0)]);
+        let calculate = |f: &str| equation_handler.try_calculate_formula(f).unwrap();
+        // Resultant and moment of a triangular load
+        assert!((calculate("integrate(q*x/L; x; 0; L)") - 6.0).abs() < 1e-9);
+        assert!((calculate("integrate(q*x/L*x; x; 0; L)") - 24.0).abs() < 1e-9);
+        // The bound variable doesn't change the variable outside
+        assert!((calculate("integrate(sin(x); x; 0; pi) + x") - 102.0).abs() < 1e-9);
+        // Singularity at the lower bound
+        assert!((calculate("integrate(1/sqrt(t); t; 0; 1)") - 2.0).abs() < 1e-5);
+        assert!((calculate("integrate(exp_x; exp_x; 2; 0-1)") + 1.5).abs() < 1e-9);
+        assert_eq!(calculate("sum(i^2; i; 1; 10)"), 385.0);
+        assert_eq!(calculate("sum(i; i; 5; 1)"), 0.0);
+        assert_eq!(calculate("prod(i; i; 1; 5)"), 120.0);
+        assert_eq!(calculate("sum(sum(i*j; j; 1; 3); i; 1; 2)"), 18.0);
+        assert_eq!(calculate("sum(i; i; 0-2; 2,4)"), 0.0);
+
+        let compiled = equation_handler.compile("sum(i*q; i; 1; x)").unwrap();
+        let expected: BTreeSet<String> = ["q", "x"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(compiled.variables(), expected);
+
+        assert!(equation_handler.compile("sum(i; 2; 1; 5)").is_err());
+        assert!(equation_handler.compile("sum(i; i; 1)").is_err());
+        assert!(equation_handler.compile("sqrt(1; 2)").is_err());
+        assert!(equation_handler
+            .try_calculate_formula("sum(i; i; 1; 1e9)")
+            .is_err());
+
+        let info = equation_handler.analyze("integrate(q*t; t; 0; L)");
+        assert_eq!(info.bound_variables, BTreeSet::from(["t".to_string()]));
+        assert!(info.unknown_variables.is_empty());
+    }
+
+    #[test]
+    fn comparisons_and_piecewise() {
+        let mut equation_handler = EquationHandler::from([("lambda", 0.1)]);
+        let calculate = |eq: &EquationHandler, f: &str| eq.try_calculate_formula(f).unwrap();
+        assert_eq!(calculate(&equation_handler, "lambda < 0.2"), 1.0);
+        assert_eq!(calculate(&equation_handler, "lambda*10 >= 1"), 1.0);
+        assert_eq!(calculate(&equation_handler, "1+1 == 2"), 1.0);
+        assert_eq!(calculate(&equation_handler, "1+1 != 2"), 0.0);
+        assert_eq!(calculate(&equation_handler, "2 <= 1"), 0.0);
+        assert_eq!(calculate(&equation_handler, "2 > -1"), 1.0);
+        assert!(equation_handler.try_calculate_formula("2 = 1").is_err());
+
+        let formula = "piecewise(lambda<0.2; 1; lambda<=1; 1.2-lambda; 0.2/lambda)";
+        assert_eq!(calculate(&equation_handler, formula), 1.0);
+        equation_handler.set_variable("lambda", 0.5);
+        assert!((calculate(&equation_handler, formula) - 0.7).abs() < 1e-12);
+        equation_handler.set_variable("lambda", 2.0);
+        assert!((calculate(&equation_handler, formula) - 0.1).abs() < 1e-12);
+        assert_eq!(calculate(&equation_handler, "piecewise(5)"), 5.0);
+        // The values that are not needed are not evaluated
+        assert_eq!(
+            calculate(&equation_handler, "piecewise(1; 2; unknown)"),
+            2.0
+        );
+        assert!(equation_handler.compile("piecewise(1; 2)").is_err());
+
+        // Conditions that can't be decided with intervals give both alternatives
+        let intervals = HashMap::from([("lambda".to_string(), Interval::new(0.1, 0.5))]);
+        let result = equation_handler
+            .calculate_interval(formula, &intervals)
+            .unwrap();
+        // The branches are not narrowed by the conditions, so 1.2-lambda gives up to 1.1
+        assert!(result.contains(1.0) && result.contains(0.7) && result.hi < 1.11);
+        let intervals = HashMap::from([("lambda".to_string(), Interval::new(0.3, 0.5))]);
+        let result = equation_handler
+            .calculate_interval(formula, &intervals)
+            .unwrap();
+        assert!(result.contains(0.9) && result.contains(0.7) && result.hi < 0.91);
+    }
+}
@@ -0,0 +1,379 @@
+use std::ops::Range;
+
+use crate::equation_handler::{EquationHandler, TokenKind, CONSTANTS};
+
+/// Signature of a function that can be used in the formulas. The parameters are separated with
+/// semicolons in the formula.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FunctionSignature {
+    pub name: &'static str,
+    pub parameters: &'static [&'static str],
+    pub description: &'static str,
+}
+
+impl FunctionSignature {
+    /// Returns the minimum number of arguments. `piecewise` can be called with only the default
+    /// value and the table operators without the second coordinate for the 1D tables.
+    pub fn min_arity(&self) -> usize {
+        match self.name {
+            "piecewise" => 1,
+            "lookup" | "interp" => 2,
+            _ => self.parameters.len(),
+        }
+    }
+
+    /// Returns the maximum number of arguments or None if there is no maximum (`piecewise`
+    /// takes any number of condition and value pairs)
+    pub fn max_arity(&self) -> Option<usize> {
+        match self.name {
+            "piecewise" => None,
+            _ => Some(self.parameters.len()),
+        }
+    }
+
+    /// Returns true if the function can be called with different numbers of arguments
+    pub fn is_variadic(&self) -> bool {
+        self.max_arity() != Some(self.min_arity())
+    }
+
+    /// Checks if the function can be called with the given number of arguments. The condition
+    /// and value pairs of `piecewise` are followed by the default value, so the number of its
+    /// arguments is odd.
+    pub fn accepts_arguments(&self, count: usize) -> bool {
+        let in_range = (self.min_arity()..=self.max_arity().unwrap_or(usize::MAX)).contains(&count);
+        in_range && (self.name != "piecewise" || count % 2 == 1)
+    }
+
+    /// Returns the signature as it's written in the formula (e.g. `sqrt(x)`)
+    pub fn label(&self) -> String {
+        format!("{}({})", self.name, self.parameters.join("; "))
+    }
+}
+
+/// Signatures of all the functions that can be used in the formulas
+pub const FUNCTION_SIGNATURES: &[FunctionSignature] = &[
+    FunctionSignature {
+        name: "sqrt",
+        parameters: &["x"],
+        description: "Square root of x",
+    },
+    FunctionSignature {
+        name: "abs",
+        parameters: &["x"],
+        description: "Absolute value of x",
+    },
+    FunctionSignature {
+        name: "sin",
+        parameters: &["x"],
+        description: "Sine of x (radians)",
+    },
+    FunctionSignature {
+        name: "cos",
+        parameters: &["x"],
+        description: "Cosine of x (radians)",
+    },
+    FunctionSignature {
+        name: "tan",
+        parameters: &["x"],
+        description: "Tangent of x (radians)",
+    },
+    FunctionSignature {
+        name: "acos",
+        parameters: &["x"],
+        description: "Arccosine of x (radians)",
+    },
+    FunctionSignature {
+        name: "asin",
+        parameters: &["x"],
+        description: "Arcsine of x (radians)",
+    },
+    FunctionSignature {
+        name: "atan",
+        parameters: &["x"],
+        description: "Arctangent of x (radians)",
+    },
+    FunctionSignature {
+        name: "log",
+        parameters: &["x"],
+        description: "Natural logarithm of x",
+    },
+    FunctionSignature {
+        name: "log10",
+        parameters: &["x"],
+        description: "Base 10 logarithm of x",
+    },
+    FunctionSignature {
+        name: "integrate",
+        parameters: &["expr", "var", "a", "b"],
+        description: "Integral of expr over var from a to b",
+    },
+    FunctionSignature {
+        name: "sum",
+        parameters: &["expr", "var", "from", "to"],
+        description: "Sum of expr with integer var from from to to",
+    },
+    FunctionSignature {
+        name: "prod",
+        parameters: &["expr", "var", "from", "to"],
+        description: "Product of expr with integer var from from to to",
+    },
+    FunctionSignature {
+        name: "piecewise",
+        parameters: &["cond1", "value1", "...", "default"],
+        description: "Value of the first true condition or the default",
+    },
+    FunctionSignature {
+        name: "lookup",
+        parameters: &["table", "x", "y"],
+        description: "Table value at the last row (and column) not greater than x (and y)",
+    },
+    FunctionSignature {
+        name: "interp",
+        parameters: &["table", "x", "y"],
+        description: "Linearly interpolated table value at x (and y). y is for 2D tables",
+    },
+];
+
+/// The kind of a [`Completion`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompletionKind {
+    Variable,
+    Function,
+    Constant,
+}
+
+/// A completion item for the formula input. See [`EquationHandler::completions`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub kind: CompletionKind,
+    /// The name of the variable, function or constant
+    pub name: String,
+    /// The current value of the variable or constant
+    pub value: Option<f64>,
+    /// The signature of the function
+    pub signature: Option<FunctionSignature>,
+    /// The byte range in the formula string that is replaced with the name
+    pub span: Range<usize>,
+}
+
+/// Parameter hints for a function call. See [`EquationHandler::signature_help`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureHelp {
+    pub signature: FunctionSignature,
+    /// Index of the parameter the cursor is at
+    pub active_parameter: usize,
+}
+
+impl EquationHandler {
+    /// Returns the completions for the word at the cursor (byte index in the formula string).
+    /// The variables (with their current values), functions and constants that start with the
+    /// word are returned in that order. The comparison is case insensitive. If the cursor is not
+    /// at a word (e.g. after an operator), all of them are returned. No completions are returned
+    /// if the cursor is in a number or in a comment.
+    pub fn completions(&self, formula_string: &str, cursor: usize) -> Vec<Completion> {
+        let cursor = cursor.min(formula_string.len());
+        if !formula_string.is_char_boundary(cursor) {
+            return vec![];
+        }
+        let tokens = self.tokenize(formula_string);
+        let token = tokens
+            .iter()
+            .find(|t| t.span.start < cursor && cursor <= t.span.end);
+        let span = match token {
+            Some(t) => match t.kind {
+                TokenKind::VariableKnown
+                | TokenKind::VariableUnknown
+                | TokenKind::VariableBound
+                | TokenKind::Function => t.span.clone(),
+                TokenKind::UnitComment
+                    if cursor == t.span.end && t.text(formula_string).ends_with(']') =>
+                {
+                    cursor..cursor
+                }
+                TokenKind::UnitComment | TokenKind::Number => return vec![],
+                _ => cursor..cursor,
+            },
+            None => cursor..cursor,
+        };
+        let prefix = formula_string[span.start..cursor].to_lowercase();
+        let matches = |name: &str| name.to_lowercase().starts_with(&prefix);
+
+        let mut result = Vec::new();
+        let variables = self.get_variables();
+        let mut names: Vec<&String> = variables.keys().filter(|k| matches(k)).collect();
+        names.sort();
+        for name in names {
+            result.push(Completion {
+                kind: CompletionKind::Variable,
+                name: name.clone(),
+                value: Some(variables[name]),
+                signature: None,
+                span: span.clone(),
+            });
+        }
+        for signature in FUNCTION_SIGNATURES.iter().filter(|f| matches(f.name)) {
+            result.push(Completion {
+                kind: CompletionKind::Function,
+                name: signature.name.to_string(),
+                value: None,
+                signature: Some(*signature),
+                span: span.clone(),
+            });
+        }
+        for (name, value) in CONSTANTS.iter().filter(|(k, _)| matches(k)) {
+            // Variables with the same name override the constants
+            if self.variable_is_set(name) {
+                continue;
+            }
+            result.push(Completion {
+                kind: CompletionKind::Constant,
+                name: name.to_string(),
+                value: Some(*value),
+                signature: None,
+                span: span.clone(),
+            });
+        }
+        result
+    }
+
+    /// Returns the parameter hints if the cursor (byte index in the formula string) is inside
+    /// a function call. With nested calls, the innermost call is used.
+    pub fn signature_help(&self, formula_string: &str, cursor: usize) -> Option<SignatureHelp> {
+        let tokens = self.tokenize(formula_string);
+        // Open parentheses before the cursor. Function signature (if the parenthesis starts a
+        // function call) and the index of the current parameter.
+        let mut stack: Vec<(Option<FunctionSignature>, usize)> = Vec::new();
+        let mut previous_kind = None;
+        for token in tokens.iter().take_while(|t| t.span.end <= cursor) {
+            let text = token.text(formula_string);
+            match token.kind {
+                TokenKind::Whitespace | TokenKind::UnitComment => continue,
+                TokenKind::Paren if text == "(" => {
+                    let signature = if previous_kind == Some(TokenKind::Function) {
+                        Self::get_function_signature(&tokens, formula_string, token.span.start)
+                    } else {
+                        None
+                    };
+                    stack.push((signature, 0));
+                }
+                TokenKind::Paren => {
+                    stack.pop();
+                }
+                _ => {
+                    if let Some(last) = stack.last_mut() {
+                        last.1 += text.matches(';').count();
+                    }
+                }
+            }
+            previous_kind = Some(token.kind);
+        }
+        stack
+            .into_iter()
+            .rev()
+            .find_map(|(signature, active_parameter)| {
+                Some(SignatureHelp {
+                    signature: signature?,
+                    active_parameter,
+                })
+            })
+    }
+
+    /// Gets the signature of the function whose name is the last function token before the
+    /// given byte index
+    fn get_function_signature(
+        tokens: &[super::Token],
+        formula_string: &str,
+        index: usize,
+    ) -> Option<FunctionSignature> {
+        let name = tokens
+            .iter()
+            .rev()
+            .find(|t| t.span.end <= index && t.kind == TokenKind::Function)?
+            .text(formula_string);
+        FUNCTION_SIGNATURES
+            .iter()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(completions: &[Completion]) -> Vec<&str> {
+        completions.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn completions() {
+        let equation_handler = EquationHandler::from([("alpha", 0.5), ("area", 100.0), ("b", 2.0)]);
+        let formula_string = "b*A";
+        let completions = equation_handler.completions(formula_string, 3);
+        assert_eq!(
+            names(&completions),
+            vec!["alpha", "area", "abs", "acos", "asin", "atan"]
+        );
+        assert_eq!(completions[1].value, Some(100.0));
+        assert_eq!(completions[1].span, 2..3);
+        assert_eq!(completions[2].kind, CompletionKind::Function);
+        let signature = completions[2].signature.unwrap();
+        assert_eq!((signature.min_arity(), signature.max_arity()), (1, Some(1)));
+        assert!(!signature.is_variadic());
+
+        // Cursor in the middle of the word
+        let completions = equation_handler.completions("sqrt(x)", 1);
+        assert_eq!(names(&completions), vec!["sqrt", "sin", "sum"]);
+        assert_eq!(completions[0].span, 0..4);
+
+        let completions = equation_handler.completions("2*pi", 4);
+        assert_eq!(names(&completions), vec!["piecewise", "pi"]);
+        assert_eq!(completions[1].kind, CompletionKind::Constant);
+
+        // All the items after an operator
+        let count =
+            equation_handler.get_variables().len() + FUNCTION_SIGNATURES.len() + CONSTANTS.len();
+        assert_eq!(equation_handler.completions("b+", 2).len(), count);
+
+        assert!(equation_handler.completions("12", 1).is_empty());
+        assert!(equation_handler.completions("5 [kN", 4).is_empty());
+    }
+
+    #[test]
+    fn signature_help() {
+        let equation_handler = EquationHandler::new();
+        let help = equation_handler.signature_help("2*sqrt(5+", 9).unwrap();
+        assert_eq!(help.signature.name, "sqrt");
+        assert_eq!(help.signature.label(), "sqrt(x)");
+        assert_eq!(help.active_parameter, 0);
+
+        // Innermost call is used
+        let help = equation_handler
+            .signature_help("sin(1+ abs (2*(3", 16)
+            .unwrap();
+        assert_eq!(help.signature.name, "abs");
+        let help = equation_handler
+            .signature_help("sin(1+ abs (2*(3))+", 19)
+            .unwrap();
+        assert_eq!(help.signature.name, "sin");
+
+        assert!(equation_handler.signature_help("sqrt(2)+", 8).is_none());
+        assert!(equation_handler.signature_help("(2+", 3).is_none());
+    }
+
+    #[test]
+    fn variadic_signatures() {
+        let signature = |name| FUNCTION_SIGNATURES.iter().find(|f| f.name == name).unwrap();
+        let piecewise = signature("piecewise");
+        assert_eq!((piecewise.min_arity(), piecewise.max_arity()), (1, None));
+        assert!(piecewise.is_variadic());
+        assert!(piecewise.accepts_arguments(5));
+        assert!(!piecewise.accepts_arguments(4));
+        let lookup = signature("lookup");
+        assert_eq!((lookup.min_arity(), lookup.max_arity()), (2, Some(3)));
+        assert!(lookup.is_variadic());
+        assert!(!lookup.accepts_arguments(1));
+        assert!(!lookup.accepts_arguments(4));
+        assert!(!signature("sum").is_variadic());
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::equation_handler::EquationHandler;
+
+/// Dependency graph of named formulas. See [`EquationHandler::dependency_graph`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    /// Formula names and the names they reference (other formulas, variables or undefined names)
+    pub dependencies: BTreeMap<String, BTreeSet<String>>,
+    /// Referenced names that are variables in the equation handler
+    pub variables: BTreeSet<String>,
+    /// Referenced names that are neither formulas nor variables
+    pub undefined: BTreeSet<String>,
+    /// Groups of formulas that depend on each other in a cycle. Each group is sorted by name.
+    pub cycles: Vec<Vec<String>>,
+}
+
+impl DependencyGraph {
+    /// Checks if the graph has undefined references or cycles
+    pub fn has_errors(&self) -> bool {
+        !self.undefined.is_empty() || !self.cycles.is_empty()
+    }
+
+    /// Checks if the given formula is part of a cycle
+    pub fn in_cycle(&self, name: &str) -> bool {
+        self.cycles.iter().any(|c| c.iter().any(|n| n == name))
+    }
+
+    /// Returns the graph in Graphviz DOT format. The edges point from the formula to its
+    /// dependencies. Formulas are drawn as boxes, variables as ellipses and undefined
+    /// references as red dashed ellipses. Edges between formulas in a cycle are red.
+    pub fn to_dot(&self) -> String {
+        let mut result = String::from("digraph formulas {\n");
+        for name in self.dependencies.keys() {
+            let color = if self.in_cycle(name) {
+                ", color=red"
+            } else {
+                ""
+            };
+            let _ = writeln!(result, "    {} [shape=box{color}];", Self::dot_id(name));
+        }
+        for name in &self.variables {
+            let _ = writeln!(result, "    {} [shape=ellipse];", Self::dot_id(name));
+        }
+        for name in &self.undefined {
+            let id = Self::dot_id(name);
+            let _ = writeln!(result, "    {id} [shape=ellipse, color=red, style=dashed];");
+        }
+        for (name, dependencies) in &self.dependencies {
+            for dependency in dependencies {
+                let same_cycle = self
+                    .cycles
+                    .iter()
+                    .any(|c| c.contains(name) && c.contains(dependency));
+                let color = if same_cycle { " [color=red]" } else { "" };
+                let (from, to) = (Self::dot_id(name), Self::dot_id(dependency));
+                let _ = writeln!(result, "    {from} -> {to}{color};");
+            }
+        }
+        result.push_str("}\n");
+        result
+    }
+
+    /// Returns the graph as a JSON string
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Quotes the name to be used as a node id in DOT format
+    fn dot_id(name: &str) -> String {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+impl EquationHandler {
+    /// Builds a dependency graph from the given formulas (name => formula string). The free
+    /// variables of each formula (see [`CompiledFormula::variables`]) are resolved to another
+    /// formula, to a variable in this handler or to an undefined reference. The variables bound
+    /// by the binding operators and the table names are not references. The names are
+    /// normalized with the key policy. Built-in constants are not included in the graph.
+    ///
+    /// [`CompiledFormula::variables`]: crate::equation_handler::CompiledFormula::variables
+    pub fn dependency_graph(&self, formulas: &HashMap<String, String>) -> DependencyGraph {
+        let formulas: BTreeMap<String, &String> = formulas
+            .iter()
+            .map(|(k, v)| (self.key_policy.normalize(k), v))
+            .collect();
+        let mut graph = DependencyGraph::default();
+        for (name, formula_string) in &formulas {
+            let mut dependencies = BTreeSet::new();
+            for reference in self.get_references(formula_string) {
+                if formulas.contains_key(&reference) {
+                    // Formulas override the variables with the same name
+                } else if self.variable_is_set(&reference) {
+                    graph.variables.insert(reference.clone());
+                } else if Self::get_constant(&reference).is_some() {
+                    continue;
+                } else {
+                    graph.undefined.insert(reference.clone());
+                }
+                dependencies.insert(reference);
+            }
+            graph.dependencies.insert(name.clone(), dependencies);
+        }
+        graph.cycles = Self::find_cycles(&graph.dependencies);
+        graph
+    }
+
+    /// Returns the free variables of the formula. If the formula can't be compiled (e.g. it uses
+    /// a table that is not added yet), the variables are collected with
+    /// [`EquationHandler::analyze`] instead.
+    fn get_references(&self, formula_string: &str) -> BTreeSet<String> {
+        if let Ok(compiled) = self.compile(formula_string) {
+            return compiled.variables();
+        }
+        let info = self.analyze(formula_string);
+        let mut references = info.all_variables();
+        references.retain(|k| !info.bound_variables.contains(k) && !info.tables.contains(k));
+        references
+    }
+
+    /// Finds the cycles with Tarjan's strongly connected components algorithm. Components with
+    /// more than one formula or a formula referencing itself are cycles.
+    fn find_cycles(dependencies: &BTreeMap<String, BTreeSet<String>>) -> Vec<Vec<String>> {
+        struct Tarjan<'a> {
+            dependencies: &'a BTreeMap<String, BTreeSet<String>>,
+            index: usize,
+            indices: HashMap<&'a str, usize>,
+            low_links: HashMap<&'a str, usize>,
+            stack: Vec<&'a str>,
+            on_stack: BTreeSet<&'a str>,
+            cycles: Vec<Vec<String>>,
+        }
+
+        impl<'a> Tarjan<'a> {
+            fn visit(&mut self, node: &'a str) {
+                self.indices.insert(node, self.index);
+                self.low_links.insert(node, self.index);
+                self.index += 1;
+                self.stack.push(node);
+                self.on_stack.insert(node);
+
+                let dependencies = self.dependencies;
+                for next in dependencies[node].iter() {
+                    // Only formulas can be part of a cycle
+                    if !dependencies.contains_key(next) {
+                        continue;
+                    }
+                    if !self.indices.contains_key(next.as_str()) {
+                        self.visit(next);
+                        let low = self.low_links[node].min(self.low_links[next.as_str()]);
+                        self.low_links.insert(node, low);
+                    } else if self.on_stack.contains(next.as_str()) {
+                        let low = self.low_links[node].min(self.indices[next.as_str()]);
+                        self.low_links.insert(node, low);
+                    }
+                }
+
+                if self.low_links[node] == self.indices[node] {
+                    let mut component = Vec::new();
+                    while let Some(n) = self.stack.pop() {
+                        self.on_stack.remove(n);
+                        component.push(n.to_string());
+                        if n == node {
+                            break;
+                        }
+                    }
+                    let self_reference = dependencies[node].contains(node);
+                    if component.len() > 1 || self_reference {
+                        component.sort();
+                        self.cycles.push(component);
+                    }
+                }
+            }
+        }
+
+        let mut tarjan = Tarjan {
+            dependencies,
+            index: 0,
+            indices: HashMap::new(),
+            low_links: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: BTreeSet::new(),
+            cycles: Vec::new(),
+        };
+        for node in dependencies.keys() {
+            if !tarjan.indices.contains_key(node.as_str()) {
+                tarjan.visit(node);
+            }
+        }
+        tarjan.cycles.sort();
+        tarjan.cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equation_handler::Table;
+
+    fn formulas(arr: &[(&str, &str)]) -> HashMap<String, String> {
+        arr.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn dependency_graph() {
+        let equation_handler = EquationHandler::from([("fy", 355.0), ("gamma", 1.1)]);
+        let graph = equation_handler.dependency_graph(&formulas(&[
+            ("A", "b*h"),
+            ("b", "200 [mm]"),
+            ("h", "2*b+unknown"),
+            ("N", "A*fy/gamma*pi"),
+        ]));
+        assert_eq!(
+            graph.dependencies["n"],
+            BTreeSet::from(["a", "fy", "gamma"].map(String::from))
+        );
+        assert_eq!(
+            graph.dependencies["h"],
+            BTreeSet::from(["b", "unknown"].map(String::from))
+        );
+        assert!(graph.dependencies["b"].is_empty());
+        assert_eq!(
+            graph.variables,
+            BTreeSet::from(["fy", "gamma"].map(String::from))
+        );
+        assert_eq!(graph.undefined, BTreeSet::from(["unknown".to_string()]));
+        assert!(graph.cycles.is_empty());
+        assert!(graph.has_errors());
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph formulas {\n"));
+        assert!(dot.contains("    \"n\" -> \"a\";\n"));
+        assert!(dot.contains("    \"unknown\" [shape=ellipse, color=red, style=dashed];\n"));
+
+        let json: DependencyGraph = serde_json::from_str(&graph.to_json()).unwrap();
+        assert_eq!(json, graph);
+    }
+
+    #[test]
+    fn dependency_graph_cycles() {
+        let equation_handler = EquationHandler::new();
+        let graph = equation_handler.dependency_graph(&formulas(&[
+            ("a", "b+1"),
+            ("b", "c*2"),
+            ("c", "a-1"),
+            ("d", "d+a"),
+            ("e2", "a"),
+        ]));
+        assert_eq!(graph.cycles, vec![vec!["a", "b", "c"], vec!["d"]]);
+        assert!(graph.in_cycle("d"));
+        assert!(!graph.in_cycle("e2"));
+        assert!(graph.undefined.is_empty());
+        assert!(graph.to_dot().contains("    \"c\" -> \"a\" [color=red];\n"));
+        assert!(graph.to_dot().contains("    \"e2\" -> \"a\";\n"));
+    }
+
+    #[test]
+    fn dependency_graph_bound_variables_and_tables() {
+        let mut equation_handler = EquationHandler::from([("i", 1.0), ("q", 2.0)]);
+        let graph = equation_handler.dependency_graph(&formulas(&[
+            ("s", "sum(i*n; i; 1; 10)"),
+            ("m", "integrate(q*x; x; 0; l) + i"),
+            ("n", "2"),
+        ]));
+        assert_eq!(graph.dependencies["s"], BTreeSet::from(["n".to_string()]));
+        assert_eq!(
+            graph.dependencies["m"],
+            BTreeSet::from(["i", "l", "q"].map(String::from))
+        );
+        assert_eq!(graph.undefined, BTreeSet::from(["l".to_string()]));
+
+        // Table names are not references, whether the table is added or not
+        let formulas = formulas(&[("k", "lookup(kc; a) * interp(kc; a; 2)"), ("a", "3")]);
+        let graph = equation_handler.dependency_graph(&formulas);
+        assert_eq!(graph.dependencies["k"], BTreeSet::from(["a".to_string()]));
+        assert!(!graph.has_errors());
+        let table = Table::new_1d(vec![0.0, 5.0], vec![1.0, 2.0]).unwrap();
+        equation_handler.add_table("kc", table);
+        let graph = equation_handler.dependency_graph(&formulas);
+        assert_eq!(graph.dependencies["k"], BTreeSet::from(["a".to_string()]));
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::f64::consts::LN_10;
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::equation_handler::{EquationError, EquationHandler, Scalar};
+
+/// Dual number for the forward-mode automatic differentiation. Holds the value and the partial
+/// derivatives with respect to all the variables, so the gradient is calculated in a single pass.
+/// Constants have an empty gradient, which means all the derivatives are zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dual {
+    pub value: f64,
+    pub gradient: Vec<f64>,
+}
+
+impl Dual {
+    /// Creates a dual number for a constant (all the derivatives are zero)
+    pub fn constant(value: f64) -> Self {
+        Dual {
+            value,
+            gradient: Vec::new(),
+        }
+    }
+
+    /// Creates a dual number for the variable with the given index. `count` is the number of
+    /// variables.
+    pub fn variable(value: f64, index: usize, count: usize) -> Self {
+        let mut gradient = vec![0.0; count];
+        gradient[index] = 1.0;
+        Dual { value, gradient }
+    }
+
+    /// Returns the partial derivative with respect to the variable with the given index
+    pub fn derivative(&self, index: usize) -> f64 {
+        self.gradient.get(index).copied().unwrap_or(0.0)
+    }
+
+    /// Applies a function whose derivative at the value is `derivative` (chain rule)
+    fn chain(self, value: f64, derivative: f64) -> Self {
+        let gradient = self.gradient.into_iter().map(|d| d * derivative).collect();
+        Dual { value, gradient }
+    }
+
+    /// Gets the gradient `a * self + b * other`
+    fn combine(&self, a: f64, other: &Dual, b: f64) -> Vec<f64> {
+        let len = self.gradient.len().max(other.gradient.len());
+        (0..len)
+            .map(|i| a * self.derivative(i) + b * other.derivative(i))
+            .collect()
+    }
+}
+
+impl Add for Dual {
+    type Output = Dual;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Dual {
+            value: self.value + rhs.value,
+            gradient: self.combine(1.0, &rhs, 1.0),
+        }
+    }
+}
+
+impl Sub for Dual {
+    type Output = Dual;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Dual {
+            value: self.value - rhs.value,
+            gradient: self.combine(1.0, &rhs, -1.0),
+        }
+    }
+}
+
+impl Mul for Dual {
+    type Output = Dual;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let gradient = self.combine(rhs.value, &rhs, self.value);
+        Dual {
+            value: self.value * rhs.value,
+            gradient,
+        }
+    }
+}
+
+impl Div for Dual {
+    type Output = Dual;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        let value = self.value / rhs.value;
+        let gradient = self.combine(1.0 / rhs.value, &rhs, -value / rhs.value);
+        Dual { value, gradient }
+    }
+}
+
+impl Scalar for Dual {
+    fn from_f64(value: f64) -> Self {
+        Dual::constant(value)
+    }
+
+    fn to_f64(&self) -> f64 {
+        self.value
+    }
+
+    fn is_nan(&self) -> bool {
+        self.value.is_nan()
+    }
+
+    fn less_than(&self, other: &Self) -> Self {
+        Dual::constant(self.value.less_than(&other.value))
+    }
+
+    fn equals(&self, other: &Self) -> Self {
+        Dual::constant(self.value.equals(&other.value))
+    }
+
+    fn truth(&self) -> Option<bool> {
+        Some(self.value != 0.0)
+    }
+
+    fn hull(self, _other: Self) -> Self {
+        self
+    }
+
+    fn pow(self, exponent: Self) -> Self {
+        let value = self.value.powf(exponent.value);
+        if exponent.gradient.iter().all(|d| *d == 0.0) {
+            // Constant exponent. Also works with negative bases.
+            let derivative = exponent.value * self.value.powf(exponent.value - 1.0);
+            self.chain(value, derivative)
+        } else if self.gradient.iter().all(|d| *d == 0.0) {
+            exponent.chain(value, value * self.value.ln())
+        } else {
+            let (a, b) = (value * exponent.value / self.value, value * self.value.ln());
+            let gradient = self.combine(a, &exponent, b);
+            Dual { value, gradient }
+        }
+    }
+
+    fn sqrt(self) -> Self {
+        let value = self.value.sqrt();
+        self.chain(value, 0.5 / value)
+    }
+
+    fn abs(self) -> Self {
+        let derivative = if self.value == 0.0 {
+            0.0
+        } else {
+            self.value.signum()
+        };
+        let value = self.value.abs();
+        self.chain(value, derivative)
+    }
+
+    fn sin(self) -> Self {
+        let derivative = self.value.cos();
+        let value = self.value.sin();
+        self.chain(value, derivative)
+    }
+
+    fn cos(self) -> Self {
+        let derivative = -self.value.sin();
+        let value = self.value.cos();
+        self.chain(value, derivative)
+    }
+
+    fn tan(self) -> Self {
+        let value = self.value.tan();
+        self.chain(value, 1.0 + value * value)
+    }
+
+    fn acos(self) -> Self {
+        let derivative = -1.0 / (1.0 - self.value * self.value).sqrt();
+        let value = self.value.acos();
+        self.chain(value, derivative)
+    }
+
+    fn asin(self) -> Self {
+        let derivative = 1.0 / (1.0 - self.value * self.value).sqrt();
+        let value = self.value.asin();
+        self.chain(value, derivative)
+    }
+
+    fn atan(self) -> Self {
+        let derivative = 1.0 / (1.0 + self.value * self.value);
+        let value = self.value.atan();
+        self.chain(value, derivative)
+    }
+
+    fn ln(self) -> Self {
+        let derivative = 1.0 / self.value;
+        let value = self.value.ln();
+        self.chain(value, derivative)
+    }
+
+    fn log10(self) -> Self {
+        let derivative = 1.0 / (self.value * LN_10);
+        let value = self.value.log10();
+        self.chain(value, derivative)
+    }
+}
+
+impl EquationHandler {
+    /// Calculates the partial derivatives of the formula with respect to all the variables used
+    /// in it at the current variable values. Returns an empty map if the formula is invalid.
+    pub fn gradient(&self, formula_string: &str) -> HashMap<String, f64> {
+        self.try_gradient(formula_string).unwrap_or_default()
+    }
+
+    /// Calculates the partial derivatives of the formula with respect to all the variables used
+    /// in it (see [`EquationHandler::gradient`]). The derivatives are calculated with the
+    /// forward-mode automatic differentiation, so they are exact up to the rounding errors.
+    /// Built-in constants are not included unless a variable overrides them.
+    pub fn try_gradient(
+        &self,
+        formula_string: &str,
+    ) -> Result<HashMap<String, f64>, EquationError> {
+        let compiled = self.compile(formula_string)?;
+        let variables: Vec<(String, f64)> = compiled
+            .variables()
+            .into_iter()
+            .filter_map(|k| self.find_variable(&k).map(|v| (k, v)))
+            .collect();
+        let count = variables.len();
+        let result = compiled.evaluate_with(&|key: &str| {
+            let index = variables.iter().position(|(k, _)| k == key)?;
+            Some(Dual::variable(variables[index].1, index, count))
+        })?;
+        Ok(variables
+            .into_iter()
+            .enumerate()
+            .map(|(i, (k, _))| (k, result.derivative(i)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compares the gradient with the central differences
+    fn assert_gradient(formula: &str, variables: &[(&str, f64)]) {
+        let mut equation_handler = EquationHandler::new();
+        for (k, v) in variables {
+            equation_handler.add_variable(k, *v);
+        }
+        let gradient = equation_handler.try_gradient(formula).unwrap();
+        assert_eq!(gradient.len(), variables.len(), "{formula}");
+        for (k, v) in variables {
+            let h = 1e-6 * v.abs().max(1.0);
+            equation_handler.set_variable(k, v + h);
+            let f1 = equation_handler.calculate_formula(formula).unwrap();
+            equation_handler.set_variable(k, v - h);
+            let f2 = equation_handler.calculate_formula(formula).unwrap();
+            equation_handler.set_variable(k, *v);
+            let expected = (f1 - f2) / (2.0 * h);
+            let actual = gradient[*k];
+            assert!(
+                (actual - expected).abs() < 1e-5 * expected.abs().max(1.0),
+                "{formula} d/d{k}: {actual} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn gradient() {
+        let equation_handler = EquationHandler::from([("x", 3.0), ("Y", 2.0)]);
+        let gradient = equation_handler.gradient("x^2*y + 5*x - y/x + pi");
+        assert_eq!(gradient.len(), 2);
+        assert!((gradient["x"] - (2.0 * 3.0 * 2.0 + 5.0 + 2.0 / 9.0)).abs() < 1e-12);
+        assert!((gradient["y"] - (9.0 - 1.0 / 3.0)).abs() < 1e-12);
+        assert!(equation_handler.gradient("x+z").is_empty());
+
+        let variables = [("a", 0.3), ("b", 1.7)];
+        assert_gradient("a+b-a*b/(b-a)", &variables);
+        assert_gradient("a^b+b^2+2^a+a^-1", &variables);
+        assert_gradient("2.5e-1*a*b", &variables);
+        assert_gradient("sqrt(b)+abs(a-b)+sin(a*b)+cos(b)+tan(a)", &variables);
+        assert_gradient("acos(a)+asin(a/2)+atan(b)+log(b)+log10(a*b)", &variables);
+        assert_gradient("sin(sqrt(a*b)^2)*log(abs(a-b))", &variables);
+        assert_gradient("(0-b)^3", &[("b", 1.7)]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt::{Display, Formatter};
+
+/// Errors returned from the [`EquationHandler`](crate::equation_handler::EquationHandler)
+#[derive(Debug, Clone, PartialEq)]
+pub enum EquationError {
+    /// The formula string is longer than allowed in the evaluation limits
+    FormulaTooLong { length: usize, max: usize },
+    /// The formula has more tokens (numbers, variables and operators) than allowed
+    TooManyTokens { count: usize, max: usize },
+    /// The parentheses in the formula are nested deeper than allowed
+    NestingTooDeep { depth: usize, max: usize },
+    /// The math operators (e.g. sqrt(sin(x))) are nested deeper than allowed
+    FunctionRecursionTooDeep { depth: usize, max: usize },
+    /// The terms of `sum` and `prod` and the integrand evaluations of `integrate` exceed the
+    /// evaluation steps allowed in one calculation
+    TooManyEvaluationSteps { max: usize },
+    /// The formula couldn't be calculated (e.g. missing operands)
+    InvalidFormula { formula: String },
+    /// The formula uses a variable that is not set
+    UnknownVariable { name: String },
+    /// The formula uses a table that is not added to the equation handler
+    UnknownTable { name: String },
+    /// A parenthesis has no pair. The syntax errors are found with `EquationHandler::validate`.
+    UnbalancedParenthesis { index: usize },
+    /// An operator follows another operator (e.g. `5*/3`)
+    ConsecutiveOperators { index: usize },
+    /// An operator is missing an operand (e.g. `5+` or `(*3)`)
+    MissingOperand { index: usize },
+    /// Two operands are not separated with an operator (e.g. `2(3)`)
+    MissingOperator { index: usize },
+    /// A number has multiple decimal separators (e.g. `1.2.3`)
+    InvalidNumber { text: String, index: usize },
+    /// An argument of a function is empty (e.g. `sqrt()`)
+    EmptyArgument { function: String, index: usize },
+    /// A function is called with a wrong number of arguments (e.g. `sqrt(1; 2)`)
+    WrongArgumentCount {
+        function: String,
+        count: usize,
+        index: usize,
+    },
+    /// A name that is not a math operator is followed by a parenthesis (e.g. `foo(2)`)
+    UnknownFunction { name: String, index: usize },
+    /// A character that can't be used in the formula (e.g. `]` without `[`)
+    UnexpectedCharacter { character: char, index: usize },
+    /// The distribution of a variable has invalid parameters (e.g. a negative standard deviation)
+    InvalidDistribution { name: String },
+    /// The Monte Carlo simulation gave less than two finite results, so the statistics can't be
+    /// calculated
+    TooFewSamples { count: usize },
+}
+
+impl Display for EquationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EquationError::FormulaTooLong { length, max } => {
+                write!(f, "Formula is too long ({length} characters, max {max})")
+            }
+            EquationError::TooManyTokens { count, max } => {
+                write!(f, "Formula has too many tokens ({count}, max {max})")
+            }
+            EquationError::NestingTooDeep { depth, max } => {
+                write!(f, "Parentheses are nested too deep ({depth}, max {max})")
+            }
+            EquationError::FunctionRecursionTooDeep { depth, max } => {
+                write!(f, "Math operators are nested too deep ({depth}, max {max})")
+            }
+            EquationError::TooManyEvaluationSteps { max } => {
+                write!(f, "Calculation needs too many evaluation steps (max {max})")
+            }
+            EquationError::InvalidFormula { formula } => {
+                write!(f, "Error with calculation! Formula: {formula}")
+            }
+            EquationError::UnknownVariable { name } => {
+                write!(f, "Unknown variable: {name}")
+            }
+            EquationError::UnknownTable { name } => {
+                write!(f, "Unknown table: {name}")
+            }
+            EquationError::UnbalancedParenthesis { index } => {
+                write!(f, "Unbalanced parenthesis at {index}")
+            }
+            EquationError::ConsecutiveOperators { index } => {
+                write!(f, "Consecutive operators at {index}")
+            }
+            EquationError::MissingOperand { index } => {
+                write!(f, "Missing operand at {index}")
+            }
+            EquationError::MissingOperator { index } => {
+                write!(f, "Missing operator at {index}")
+            }
+            EquationError::InvalidNumber { text, index } => {
+                write!(f, "Invalid number {text} at {index}")
+            }
+            EquationError::EmptyArgument { function, index } => {
+                write!(f, "Empty argument in {function} at {index}")
+            }
+            EquationError::WrongArgumentCount {
+                function,
+                count,
+                index,
+            } => {
+                write!(
+                    f,
+                    "Wrong number of arguments ({count}) for {function} at {index}"
+                )
+            }
+            EquationError::UnknownFunction { name, index } => {
+                write!(f, "Unknown function {name} at {index}")
+            }
+            EquationError::UnexpectedCharacter { character, index } => {
+                write!(f, "Unexpected character {character} at {index}")
+            }
+            EquationError::InvalidDistribution { name } => {
+                write!(f, "Invalid distribution for variable {name}")
+            }
+            EquationError::TooFewSamples { count } => {
+                write!(f, "Too few finite results ({count}) for the statistics")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EquationError {}
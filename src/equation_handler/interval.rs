@@ -0,0 +1,614 @@
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, Div, Mul, Sub};
+
+use serde::{Deserialize, Serialize};
+
+use crate::equation_handler::{EquationError, EquationHandler, Scalar};
+
+/// Maximum error of the math functions (e.g. `sin` and `ln`) in ulps. Unlike the basic
+/// operations and `sqrt`, they are not correctly rounded, so one ulp is not enough.
+const MATH_FUNCTION_ULPS: f64 = 4.0;
+
+/// Maximum number of the integer exponents that are checked separately when a negative base is
+/// raised to an interval power. The result is the entire real line if there are more of them.
+const MAX_INTEGER_EXPONENTS: f64 = 64.0;
+
+/// Returns the next representable number towards positive infinity. Same as `f64::next_up`,
+/// which needs Rust 1.86.
+fn next_up(value: f64) -> f64 {
+    if value.is_nan() || value == f64::INFINITY {
+        value
+    } else if value == 0.0 {
+        // The smallest positive subnormal number (also after -0.0)
+        f64::from_bits(1)
+    } else if value > 0.0 {
+        f64::from_bits(value.to_bits() + 1)
+    } else {
+        f64::from_bits(value.to_bits() - 1)
+    }
+}
+
+/// Returns the next representable number towards negative infinity (see [`next_up`])
+fn next_down(value: f64) -> f64 {
+    -next_up(-value)
+}
+
+/// A closed interval `[lo, hi]` of real numbers used in the tolerance analysis. The results of
+/// the operations are rounded outwards so that they always enclose the exact result. An empty
+/// interval (e.g. `sqrt([-2, -1])`) has NaN bounds.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Interval {
+    /// Creates a new interval. The bounds are swapped if `lo` is bigger than `hi`.
+    pub fn new(lo: f64, hi: f64) -> Self {
+        if lo > hi {
+            Interval { lo: hi, hi: lo }
+        } else {
+            Interval { lo, hi }
+        }
+    }
+
+    /// Creates an interval that contains only the given value
+    pub fn point(value: f64) -> Self {
+        Interval {
+            lo: value,
+            hi: value,
+        }
+    }
+
+    /// Creates an interval from the nominal value and the symmetric tolerance (`value ± tol`)
+    pub fn new_tolerance(value: f64, tolerance: f64) -> Self {
+        Interval::new(value - tolerance.abs(), value + tolerance.abs())
+    }
+
+    /// The interval that contains all the real numbers
+    pub fn entire() -> Self {
+        Interval {
+            lo: f64::NEG_INFINITY,
+            hi: f64::INFINITY,
+        }
+    }
+
+    /// The empty interval
+    pub fn empty() -> Self {
+        Interval {
+            lo: f64::NAN,
+            hi: f64::NAN,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lo.is_nan() || self.hi.is_nan()
+    }
+
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    pub fn midpoint(&self) -> f64 {
+        self.lo + (self.hi - self.lo) / 2.0
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        self.lo <= value && value <= self.hi
+    }
+
+    /// Creates the interval from the computed bounds and rounds them outwards by one ulp. NaN
+    /// bounds (e.g. from `inf - inf`) are replaced with infinities.
+    fn outward(lo: f64, hi: f64) -> Self {
+        let lo = if lo.is_nan() {
+            f64::NEG_INFINITY
+        } else {
+            next_down(lo)
+        };
+        let hi = if hi.is_nan() {
+            f64::INFINITY
+        } else {
+            next_up(hi)
+        };
+        Interval { lo, hi }
+    }
+
+    /// Same as [`Interval::outward`], but the bounds are first widened by the relative error of
+    /// the given number of ulps
+    fn outward_ulps(lo: f64, hi: f64, ulps: f64) -> Self {
+        let error = ulps * f64::EPSILON;
+        Self::outward(lo - lo.abs() * error, hi + hi.abs() * error)
+    }
+
+    /// Creates the interval from the bounds computed with the math functions (see
+    /// [`MATH_FUNCTION_ULPS`]) and clamps the bounds to the given range
+    fn outward_clamped(lo: f64, hi: f64, min: f64, max: f64) -> Self {
+        let result = Self::outward_ulps(lo, hi, MATH_FUNCTION_ULPS);
+        Interval {
+            lo: result.lo.max(min),
+            hi: result.hi.min(max),
+        }
+    }
+
+    /// Applies an increasing math function to the interval
+    fn increasing(self, f: fn(f64) -> f64) -> Self {
+        Self::outward_ulps(f(self.lo), f(self.hi), MATH_FUNCTION_ULPS)
+    }
+
+    /// Gets the reciprocal `1 / self`. If the interval contains zero, the result is extended
+    /// to infinity (or it's the entire real line if zero is not at the bounds).
+    fn recip(self) -> Self {
+        if self.is_empty() || (self.lo == 0.0 && self.hi == 0.0) {
+            Interval::empty()
+        } else if self.lo > 0.0 || self.hi < 0.0 {
+            Self::outward(1.0 / self.hi, 1.0 / self.lo)
+        } else if self.lo == 0.0 {
+            Interval {
+                lo: next_down(1.0 / self.hi),
+                hi: f64::INFINITY,
+            }
+        } else if self.hi == 0.0 {
+            Interval {
+                lo: f64::NEG_INFINITY,
+                hi: next_up(1.0 / self.lo),
+            }
+        } else {
+            Interval::entire()
+        }
+    }
+
+    /// Raises the interval to an integer power. The power is calculated with repeated
+    /// multiplications and the relative rounding error grows with each of them, so the bounds
+    /// are widened by one ulp per multiplication (`n` in total).
+    fn powi(self, n: i32) -> Self {
+        let ulps = n.unsigned_abs() as f64;
+        if n == 0 {
+            Interval::point(1.0)
+        } else if n < 0 {
+            self.powi(-n).recip()
+        } else if n % 2 == 1 || self.lo >= 0.0 {
+            Self::outward_ulps(self.lo.powi(n), self.hi.powi(n), ulps)
+        } else if self.hi <= 0.0 {
+            Self::outward_ulps(self.hi.powi(n), self.lo.powi(n), ulps)
+        } else {
+            let hi = self.lo.powi(n).max(self.hi.powi(n));
+            Interval {
+                lo: 0.0,
+                hi: Self::outward_ulps(0.0, hi, ulps).hi,
+            }
+        }
+    }
+
+    /// Checks if the interval contains a point `offset + k * period` for some integer k
+    fn contains_periodic(&self, offset: f64, period: f64) -> bool {
+        // The tolerance only makes the result wider, so the enclosure is still guaranteed
+        let tolerance = 1e-12 * self.lo.abs().max(self.hi.abs()).max(1.0);
+        let k = ((self.lo - tolerance - offset) / period).ceil();
+        offset + k * period <= self.hi + tolerance
+    }
+
+    /// Applies a function with the period of 2π whose maximums are at `max_at + 2kπ` and
+    /// minimums at `min_at + 2kπ`
+    fn periodic(self, f: fn(f64) -> f64, max_at: f64, min_at: f64) -> Self {
+        if self.width() >= TAU || self.lo.is_infinite() || self.hi.is_infinite() {
+            return Interval::new(-1.0, 1.0);
+        }
+        let (a, b) = (f(self.lo), f(self.hi));
+        let result = Self::outward_clamped(a.min(b), a.max(b), -1.0, 1.0);
+        Interval {
+            lo: if self.contains_periodic(min_at, TAU) {
+                -1.0
+            } else {
+                result.lo
+            },
+            hi: if self.contains_periodic(max_at, TAU) {
+                1.0
+            } else {
+                result.hi
+            },
+        }
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
+
+impl From<f64> for Interval {
+    fn from(value: f64) -> Self {
+        Interval::point(value)
+    }
+}
+
+impl Add for Interval {
+    type Output = Interval;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        if self.is_empty() || rhs.is_empty() {
+            return Interval::empty();
+        }
+        Interval::outward(self.lo + rhs.lo, self.hi + rhs.hi)
+    }
+}
+
+impl Sub for Interval {
+    type Output = Interval;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        if self.is_empty() || rhs.is_empty() {
+            return Interval::empty();
+        }
+        Interval::outward(self.lo - rhs.hi, self.hi - rhs.lo)
+    }
+}
+
+impl Mul for Interval {
+    type Output = Interval;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        if self.is_empty() || rhs.is_empty() {
+            return Interval::empty();
+        }
+        // 0 * inf is 0 here, because the infinite bounds are limits of finite numbers
+        let mul = |a: f64, b: f64| if a == 0.0 || b == 0.0 { 0.0 } else { a * b };
+        let products = [
+            mul(self.lo, rhs.lo),
+            mul(self.lo, rhs.hi),
+            mul(self.hi, rhs.lo),
+            mul(self.hi, rhs.hi),
+        ];
+        let lo = products.iter().copied().fold(f64::INFINITY, f64::min);
+        let hi = products.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        Interval::outward(lo, hi)
+    }
+}
+
+impl Div for Interval {
+    type Output = Interval;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        Mul::mul(self, rhs.recip())
+    }
+}
+
+impl Scalar for Interval {
+    fn from_f64(value: f64) -> Self {
+        Interval::point(value)
+    }
+
+    fn to_f64(&self) -> f64 {
+        self.midpoint()
+    }
+
+    fn is_nan(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn less_than(&self, other: &Self) -> Self {
+        if self.is_empty() || other.is_empty() {
+            Interval::empty()
+        } else if self.hi < other.lo {
+            Interval::point(1.0)
+        } else if self.lo >= other.hi {
+            Interval::point(0.0)
+        } else {
+            Interval::new(0.0, 1.0)
+        }
+    }
+
+    fn equals(&self, other: &Self) -> Self {
+        if self.is_empty() || other.is_empty() {
+            Interval::empty()
+        } else if self.hi < other.lo || other.hi < self.lo {
+            Interval::point(0.0)
+        } else if self.lo == self.hi && other.lo == other.hi {
+            Interval::point(1.0)
+        } else {
+            Interval::new(0.0, 1.0)
+        }
+    }
+
+    fn truth(&self) -> Option<bool> {
+        if self.is_empty() || (self.contains(0.0) && self.width() > 0.0) {
+            None
+        } else {
+            Some(!self.contains(0.0))
+        }
+    }
+
+    fn hull(self, other: Self) -> Self {
+        if self.is_empty() {
+            other
+        } else if other.is_empty() {
+            self
+        } else {
+            Interval::new(self.lo.min(other.lo), self.hi.max(other.hi))
+        }
+    }
+
+    fn pow(self, exponent: Self) -> Self {
+        if self.is_empty() || exponent.is_empty() {
+            return Interval::empty();
+        }
+        if exponent.lo == exponent.hi
+            && exponent.lo.fract() == 0.0
+            && exponent.lo.abs() <= i32::MAX as f64
+        {
+            return self.powi(exponent.lo as i32);
+        }
+        let mut result = Interval::empty();
+        // Non-integer powers are only defined for the non-negative numbers. x^y is monotonic
+        // in both x and y, so the extremes are at the corners.
+        if self.hi >= 0.0 {
+            let base_lo = self.lo.max(0.0);
+            let corners = [
+                base_lo.powf(exponent.lo),
+                base_lo.powf(exponent.hi),
+                self.hi.powf(exponent.lo),
+                self.hi.powf(exponent.hi),
+            ];
+            let lo = corners.iter().copied().fold(f64::INFINITY, f64::min);
+            let hi = corners.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            result = Interval::outward_clamped(lo, hi, 0.0, f64::INFINITY);
+        }
+        // The negative numbers can be raised to the integer powers in the exponent interval
+        if self.lo < 0.0 {
+            let negative = Interval::new(self.lo, self.hi.min(0.0));
+            let (first, last) = (exponent.lo.ceil(), exponent.hi.floor());
+            if last - first >= MAX_INTEGER_EXPONENTS
+                || first.abs() > i32::MAX as f64
+                || last.abs() > i32::MAX as f64
+            {
+                return Interval::entire();
+            }
+            for k in first as i32..=last as i32 {
+                result = result.hull(negative.powi(k));
+            }
+        }
+        result
+    }
+
+    fn sqrt(self) -> Self {
+        if self.is_empty() || self.hi < 0.0 {
+            return Interval::empty();
+        }
+        Interval::outward_clamped(self.lo.max(0.0).sqrt(), self.hi.sqrt(), 0.0, f64::INFINITY)
+    }
+
+    fn abs(self) -> Self {
+        if self.is_empty() || self.lo >= 0.0 {
+            self
+        } else if self.hi <= 0.0 {
+            Interval::new(-self.hi, -self.lo)
+        } else {
+            Interval::new(0.0, self.hi.max(-self.lo))
+        }
+    }
+
+    fn sin(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        self.periodic(f64::sin, FRAC_PI_2, -FRAC_PI_2)
+    }
+
+    fn cos(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        self.periodic(f64::cos, 0.0, PI)
+    }
+
+    fn tan(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        if self.width() >= PI
+            || self.lo.is_infinite()
+            || self.hi.is_infinite()
+            || self.contains_periodic(FRAC_PI_2, PI)
+        {
+            return Interval::entire();
+        }
+        self.increasing(f64::tan)
+    }
+
+    fn acos(self) -> Self {
+        if self.is_empty() || self.lo > 1.0 || self.hi < -1.0 {
+            return Interval::empty();
+        }
+        let (lo, hi) = (self.hi.min(1.0).acos(), self.lo.max(-1.0).acos());
+        Interval::outward_clamped(lo, hi, 0.0, next_up(PI))
+    }
+
+    fn asin(self) -> Self {
+        if self.is_empty() || self.lo > 1.0 || self.hi < -1.0 {
+            return Interval::empty();
+        }
+        let (lo, hi) = (self.lo.max(-1.0).asin(), self.hi.min(1.0).asin());
+        Interval::outward_clamped(lo, hi, -next_up(FRAC_PI_2), next_up(FRAC_PI_2))
+    }
+
+    fn atan(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        let result = self.increasing(f64::atan);
+        Interval::outward_clamped(
+            result.lo,
+            result.hi,
+            -next_up(FRAC_PI_2),
+            next_up(FRAC_PI_2),
+        )
+    }
+
+    fn ln(self) -> Self {
+        if self.is_empty() || self.hi <= 0.0 {
+            return Interval::empty();
+        }
+        Interval::outward_ulps(self.lo.max(0.0).ln(), self.hi.ln(), MATH_FUNCTION_ULPS)
+    }
+
+    fn log10(self) -> Self {
+        if self.is_empty() || self.hi <= 0.0 {
+            return Interval::empty();
+        }
+        Interval::outward_ulps(
+            self.lo.max(0.0).log10(),
+            self.hi.log10(),
+            MATH_FUNCTION_ULPS,
+        )
+    }
+}
+
+impl EquationHandler {
+    /// Calculates the bounds of the formula when the variables are within the given intervals.
+    /// The result is guaranteed to enclose all the possible values of the formula. The
+    /// variables without an interval use the values set in the handler. If the formula isn't
+    /// defined anywhere in the intervals (e.g. `sqrt(x)` with negative x), the result is empty.
+    /// For `integrate` the result encloses the numerical quadrature, not the exact integral.
+    pub fn calculate_interval(
+        &self,
+        formula_string: &str,
+        intervals: &HashMap<String, Interval>,
+    ) -> Result<Interval, EquationError> {
+        let intervals: HashMap<String, Interval> = intervals
+            .iter()
+            .map(|(k, v)| (self.key_policy.normalize(k), *v))
+            .collect();
+        let compiled = self.compile(formula_string)?;
+        compiled.evaluate_with(&|key: &str| {
+            intervals
+                .get(key)
+                .copied()
+                .or_else(|| self.find_variable(key).map(Interval::point))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the interval result of the formula contains the results calculated at the
+    /// points of a grid inside the intervals
+    fn assert_encloses(formula: &str, intervals: &[(&str, Interval)]) {
+        let equation_handler = EquationHandler::new();
+        let map: HashMap<String, Interval> =
+            intervals.iter().map(|(k, v)| (k.to_string(), *v)).collect();
+        let result = equation_handler.calculate_interval(formula, &map).unwrap();
+        let steps = 20;
+        let count = (steps + 1usize).pow(intervals.len() as u32);
+        for i in 0..count {
+            let mut index = i;
+            let mut handler = EquationHandler::new();
+            for (key, interval) in intervals {
+                let t = (index % (steps + 1)) as f64 / steps as f64;
+                index /= steps + 1;
+                handler.add_variable(key, interval.lo + t * interval.width());
+            }
+            let value = handler.calculate_formula(formula).unwrap();
+            if value.is_finite() {
+                assert!(result.contains(value), "{formula}: {value} not in {result}");
+            }
+        }
+    }
+
+    #[test]
+    fn interval_arithmetic() {
+        let a = Interval::new(1.0, 2.0);
+        let b = Interval::new(-3.0, 4.0);
+        let sum = a + b;
+        assert!(sum.contains(-2.0) && sum.contains(6.0));
+        assert!(sum.lo < -2.0 && sum.hi > 6.0 && sum.width() < 8.0 + 1e-12);
+        let product = a * b;
+        assert!(product.contains(-6.0) && product.contains(8.0) && !product.contains(8.1));
+
+        // Division across zero
+        assert_eq!(a / b, Interval::entire());
+        let half_open = a / Interval::new(0.0, 2.0);
+        assert!(half_open.contains(0.5) && half_open.lo > 0.49 && half_open.hi == f64::INFINITY);
+        assert!((a / Interval::point(0.0)).is_empty());
+
+        // Even powers of intervals containing zero
+        let square = b.pow(Interval::point(2.0));
+        assert!(square.lo == 0.0 && square.contains(16.0) && square.hi < 16.1);
+        let inverse = a.pow(Interval::point(-1.0));
+        assert!(inverse.contains(0.5) && inverse.contains(1.0) && inverse.width() < 0.51);
+
+        assert!(b.sqrt().contains(2.0) && b.sqrt().lo == 0.0);
+        assert!(Interval::new(-2.0, -1.0).sqrt().is_empty());
+        assert_eq!(b.abs(), Interval::new(0.0, 4.0));
+        assert_eq!(Interval::new(0.0, 7.0).sin(), Interval::new(-1.0, 1.0));
+        assert_eq!(Interval::new(1.0, 2.0).tan(), Interval::entire());
+        assert_eq!(Interval::new_tolerance(10.0, 0.5), Interval::new(9.5, 10.5));
+
+        // The rounding errors of large powers are bigger than one ulp (13^34 is exact in u128)
+        let power = Interval::point(13.0).pow(Interval::point(34.0));
+        let exact = 13u128.pow(34);
+        assert!(
+            power.lo as u128 <= exact && exact <= power.hi as u128,
+            "{power}"
+        );
+        assert!(power.width() / power.midpoint() < 1e-13);
+        let inverse = Interval::point(13.0).pow(Interval::point(-34.0));
+        assert!(inverse.contains(1.0 / power.midpoint()));
+
+        // Negative bases with the integers in the exponent interval ((-2)^2 = 4)
+        let power = Interval::new(-3.0, -1.0).pow(Interval::new(1.5, 2.5));
+        assert!(power.contains(1.0) && power.contains(9.0) && power.width() < 8.0 + 1e-9);
+        let power = Interval::new(-3.0, 2.0).pow(Interval::new(1.5, 3.0));
+        assert!(power.contains(-27.0) && power.contains(9.0) && power.hi < 9.1);
+        assert!(Interval::new(-3.0, -1.0)
+            .pow(Interval::new(1.5, 1.9))
+            .is_empty());
+        assert_eq!(
+            Interval::new(-3.0, -1.0).pow(Interval::new(0.5, 100.0)),
+            Interval::entire()
+        );
+    }
+
+    #[test]
+    fn next_representable() {
+        assert_eq!(next_up(1.0), 1.0 + f64::EPSILON);
+        assert_eq!(next_down(1.0), 1.0 - f64::EPSILON / 2.0);
+        assert_eq!(next_up(-1.0), -1.0 + f64::EPSILON / 2.0);
+        assert_eq!(next_up(0.0), f64::from_bits(1));
+        assert_eq!(next_up(-0.0), f64::from_bits(1));
+        assert_eq!(next_down(0.0), -f64::from_bits(1));
+        assert_eq!(next_up(f64::MAX), f64::INFINITY);
+        assert_eq!(next_up(f64::NEG_INFINITY), f64::MIN);
+        assert_eq!(next_up(f64::INFINITY), f64::INFINITY);
+        assert!(next_down(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn calculate_interval() {
+        let mut equation_handler = EquationHandler::new();
+        equation_handler.add_variable("Length", 100.0);
+        let intervals = HashMap::from([("width".to_string(), Interval::new_tolerance(20.0, 0.1))]);
+        let area = equation_handler
+            .calculate_interval("length*WIDTH", &intervals)
+            .unwrap();
+        assert!(area.contains(1990.0) && area.contains(2010.0) && area.width() < 20.0 + 1e-9);
+        assert!(equation_handler
+            .calculate_interval("x*2", &intervals)
+            .is_err());
+
+        let x = Interval::new(-1.5, 2.5);
+        let y = Interval::new(0.5, 3.0);
+        assert_encloses("x^2-2*x+1", &[("x", x)]);
+        assert_encloses("x^3+1/y", &[("x", x), ("y", y)]);
+        assert_encloses("y^x", &[("x", x), ("y", y)]);
+        assert_encloses("x^(y+1)", &[("x", x), ("y", y)]);
+        assert_encloses("sin(x*y)+cos(x)", &[("x", x), ("y", y)]);
+        assert_encloses("tan(x/3)+atan(x)+asin(x/3)+acos(x/4)", &[("x", x)]);
+        assert_encloses("sqrt(y)*log(y)+log10(y)+abs(x)", &[("x", x), ("y", y)]);
+        assert_encloses("x/(y-1)", &[("x", x), ("y", y)]);
+        assert_encloses("2.5e-1*x", &[("x", x)]);
+    }
+}
@@ -0,0 +1,465 @@
+use serde::{Deserialize, Serialize};
+
+use crate::equation_handler::{EquationError, EquationHandler};
+use crate::rng::SplitMix64;
+
+/// Probability distribution of a variable in the Monte Carlo simulation. See
+/// [`EquationHandler::set_distribution`]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Distribution {
+    Normal {
+        mean: f64,
+        std_dev: f64,
+    },
+    /// Lognormal distribution. `mu` and `sigma` are the mean and the standard deviation of the
+    /// natural logarithm of the variable. See [`Distribution::new_lognormal`]
+    LogNormal {
+        mu: f64,
+        sigma: f64,
+    },
+    Uniform {
+        min: f64,
+        max: f64,
+    },
+    Triangular {
+        min: f64,
+        mode: f64,
+        max: f64,
+    },
+}
+
+impl Distribution {
+    /// Creates a lognormal distribution from the mean and the standard deviation of the
+    /// variable itself. The mean must be positive, otherwise the distribution is invalid.
+    pub fn new_lognormal(mean: f64, std_dev: f64) -> Self {
+        let sigma2 = (1.0 + (std_dev / mean).powi(2)).ln();
+        Distribution::LogNormal {
+            mu: mean.ln() - sigma2 / 2.0,
+            sigma: sigma2.sqrt(),
+        }
+    }
+
+    /// Returns the mean of the distribution
+    pub fn mean(&self) -> f64 {
+        match *self {
+            Distribution::Normal { mean, .. } => mean,
+            Distribution::LogNormal { mu, sigma } => (mu + sigma * sigma / 2.0).exp(),
+            Distribution::Uniform { min, max } => (min + max) / 2.0,
+            Distribution::Triangular { min, mode, max } => (min + mode + max) / 3.0,
+        }
+    }
+
+    /// Checks that the parameters are finite, the standard deviations are not negative and the
+    /// mode is between the minimum and the maximum
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Distribution::Normal { mean, std_dev } => {
+                mean.is_finite() && std_dev.is_finite() && std_dev >= 0.0
+            }
+            Distribution::LogNormal { mu, sigma } => {
+                mu.is_finite() && sigma.is_finite() && sigma >= 0.0
+            }
+            Distribution::Uniform { min, max } => min.is_finite() && max.is_finite() && min <= max,
+            Distribution::Triangular { min, mode, max } => {
+                min.is_finite() && max.is_finite() && min <= mode && mode <= max
+            }
+        }
+    }
+
+    /// Draws a random value from the distribution
+    fn sample(&self, rng: &mut SplitMix64) -> f64 {
+        match *self {
+            Distribution::Normal { mean, std_dev } => mean + std_dev * rng.next_standard_normal(),
+            Distribution::LogNormal { mu, sigma } => {
+                (mu + sigma * rng.next_standard_normal()).exp()
+            }
+            Distribution::Uniform { min, max } => min + (max - min) * rng.next_f64(),
+            Distribution::Triangular { min, mode, max } => {
+                // Inverse of the cumulative distribution function
+                let u = rng.next_f64();
+                let width = max - min;
+                if width <= 0.0 {
+                    min
+                } else if u < (mode - min) / width {
+                    min + (u * width * (mode - min)).sqrt()
+                } else {
+                    max - ((1.0 - u) * width * (max - mode)).sqrt()
+                }
+            }
+        }
+    }
+}
+
+/// Settings for [`EquationHandler::monte_carlo`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonteCarloSettings {
+    /// Number of times the formula is calculated
+    pub samples: usize,
+    /// Seed of the random number generator. The same seed gives the same results.
+    pub seed: u64,
+    /// Number of bins in the histogram
+    pub histogram_bins: usize,
+}
+
+impl Default for MonteCarloSettings {
+    fn default() -> Self {
+        MonteCarloSettings {
+            samples: 10000,
+            seed: 0,
+            histogram_bins: 20,
+        }
+    }
+}
+
+/// Histogram of the Monte Carlo results. The bins have equal widths between `min` and `max`.
+/// If all the results are equal, they are counted in the last bin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    fn new(sorted_values: &[f64], bins: usize) -> Self {
+        let (min, max) = match (sorted_values.first(), sorted_values.last()) {
+            (Some(min), Some(max)) => (*min, *max),
+            _ => (f64::NAN, f64::NAN),
+        };
+        let mut counts = vec![0; bins];
+        if bins > 0 {
+            for value in sorted_values {
+                // All the values go to the last bin if they are equal
+                let index = match max > min {
+                    true => ((value - min) / (max - min) * bins as f64) as usize,
+                    false => bins - 1,
+                };
+                // The maximum value goes to the last bin
+                counts[index.min(bins - 1)] += 1;
+            }
+        }
+        Histogram { min, max, counts }
+    }
+
+    pub fn bin_width(&self) -> f64 {
+        (self.max - self.min) / self.counts.len() as f64
+    }
+
+    /// Returns the lower and upper bound of the bin with the given index
+    pub fn bin_range(&self, index: usize) -> (f64, f64) {
+        let width = self.bin_width();
+        (
+            self.min + index as f64 * width,
+            self.min + (index + 1) as f64 * width,
+        )
+    }
+}
+
+/// Results of [`EquationHandler::monte_carlo`]. The statistics are calculated from the finite
+/// results only.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonteCarloResult {
+    pub mean: f64,
+    /// Sample standard deviation
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Number of samples where the result wasn't finite (e.g. sqrt of a negative number)
+    pub invalid_samples: usize,
+    pub histogram: Histogram,
+    /// The finite results in ascending order
+    sorted_values: Vec<f64>,
+}
+
+impl MonteCarloResult {
+    fn new(
+        mut values: Vec<f64>,
+        invalid_samples: usize,
+        bins: usize,
+    ) -> Result<Self, EquationError> {
+        if values.len() < 2 {
+            return Err(EquationError::TooFewSamples {
+                count: values.len(),
+            });
+        }
+        values.sort_by(f64::total_cmp);
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        Ok(MonteCarloResult {
+            mean,
+            std_dev: variance.sqrt(),
+            min: values[0],
+            max: values[values.len() - 1],
+            invalid_samples,
+            histogram: Histogram::new(&values, bins),
+            sorted_values: values,
+        })
+    }
+
+    /// Returns the value below which the given percentage (0..100) of the results fall. The
+    /// values between the samples are interpolated linearly.
+    pub fn percentile(&self, percent: f64) -> f64 {
+        let values = &self.sorted_values;
+        if values.is_empty() {
+            return f64::NAN;
+        }
+        let position = (percent.clamp(0.0, 100.0) / 100.0) * (values.len() - 1) as f64;
+        let index = position.floor() as usize;
+        let next = values[(index + 1).min(values.len() - 1)];
+        values[index] + (next - values[index]) * position.fract()
+    }
+
+    /// Returns the finite results in ascending order
+    pub fn get_values(&self) -> &[f64] {
+        &self.sorted_values
+    }
+}
+
+impl EquationHandler {
+    /// Sets the probability distribution of the variable for the Monte Carlo simulation
+    pub fn set_distribution(&mut self, key: &str, distribution: Distribution) {
+        self.distributions
+            .insert(self.key_policy.normalize(key), distribution);
+    }
+
+    pub fn remove_distribution(&mut self, key: &str) -> Option<Distribution> {
+        self.distributions.remove(&self.key_policy.normalize(key))
+    }
+
+    pub fn get_distribution(&self, key: &str) -> Option<Distribution> {
+        self.distributions
+            .get(&self.key_policy.normalize(key))
+            .copied()
+    }
+
+    /// Calculates the formula `settings.samples` times with random values drawn for the
+    /// variables that have a distribution (see [`EquationHandler::set_distribution`]). The other
+    /// variables use the values set in the handler. The formula is compiled only once.
+    ///
+    /// Returns an error if a distribution has invalid parameters (see [`Distribution::is_valid`])
+    /// or if less than two results are finite.
+    pub fn monte_carlo(
+        &self,
+        formula_string: &str,
+        settings: &MonteCarloSettings,
+    ) -> Result<MonteCarloResult, EquationError> {
+        let compiled = self.compile(formula_string)?;
+        // Sorted by the key, so the same seed always gives the same results
+        let mut sampled: Vec<(String, Distribution, f64)> = compiled
+            .variables()
+            .into_iter()
+            .filter_map(|k| self.distributions.get(&k).map(|d| (k, *d, 0.0)))
+            .collect();
+        if let Some((key, _, _)) = sampled.iter().find(|(_, d, _)| !d.is_valid()) {
+            return Err(EquationError::InvalidDistribution { name: key.clone() });
+        }
+        let mut rng = SplitMix64::new(settings.seed);
+        let mut values = Vec::with_capacity(settings.samples);
+        let mut invalid_samples = 0;
+        for _ in 0..settings.samples {
+            for (_, distribution, value) in sampled.iter_mut() {
+                *value = distribution.sample(&mut rng);
+            }
+            let value = compiled.evaluate_with(&|key: &str| {
+                sampled
+                    .iter()
+                    .find(|(k, _, _)| k == key)
+                    .map(|(_, _, v)| *v)
+                    .or_else(|| self.find_variable(key))
+            })?;
+            if value.is_finite() {
+                values.push(value);
+            } else {
+                invalid_samples += 1;
+            }
+        }
+        MonteCarloResult::new(values, invalid_samples, settings.histogram_bins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monte_carlo() {
+        let mut equation_handler = EquationHandler::from([("a", 0.0), ("b", 0.0), ("c", 2.0)]);
+        equation_handler.set_distribution(
+            "A",
+            Distribution::Normal {
+                mean: 10.0,
+                std_dev: 1.0,
+            },
+        );
+        equation_handler.set_distribution(
+            "b",
+            Distribution::Normal {
+                mean: 5.0,
+                std_dev: 1.0,
+            },
+        );
+        let settings = MonteCarloSettings {
+            samples: 100000,
+            seed: 42,
+            histogram_bins: 10,
+        };
+        let result = equation_handler.monte_carlo("(a+b)*c", &settings).unwrap();
+        assert!((result.mean - 30.0).abs() < 0.05);
+        assert!((result.std_dev - 2.0 * 2f64.sqrt()).abs() < 0.05);
+        assert!((result.percentile(50.0) - 30.0).abs() < 0.05);
+        // 95th percentile of the normal distribution is mean + 1.645 * std dev
+        assert!((result.percentile(95.0) - 34.653).abs() < 0.1);
+        assert_eq!(result.percentile(0.0), result.min);
+        assert_eq!(result.percentile(100.0), result.max);
+        assert_eq!(result.histogram.counts.iter().sum::<usize>(), 100000);
+        assert_eq!(result.invalid_samples, 0);
+
+        // The same seed gives the same results
+        let again = equation_handler.monte_carlo("(a+b)*c", &settings).unwrap();
+        assert_eq!(result, again);
+        let settings = MonteCarloSettings {
+            seed: 1,
+            ..settings
+        };
+        assert_ne!(
+            result,
+            equation_handler.monte_carlo("(a+b)*c", &settings).unwrap()
+        );
+
+        assert!(equation_handler.monte_carlo("a+x", &settings).is_err());
+    }
+
+    #[test]
+    fn distributions() {
+        let mut equation_handler = EquationHandler::from([("x", 0.0)]);
+        let settings = MonteCarloSettings {
+            samples: 100000,
+            seed: 7,
+            histogram_bins: 4,
+        };
+        let distributions = [
+            Distribution::new_lognormal(10.0, 2.0),
+            Distribution::Uniform { min: 2.0, max: 6.0 },
+            Distribution::Triangular {
+                min: 0.0,
+                mode: 1.0,
+                max: 5.0,
+            },
+        ];
+        for distribution in distributions {
+            equation_handler.set_distribution("x", distribution);
+            let result = equation_handler.monte_carlo("x", &settings).unwrap();
+            assert!(
+                (result.mean - distribution.mean()).abs() < 0.05,
+                "{distribution:?}"
+            );
+        }
+        let result = equation_handler.monte_carlo("x", &settings).unwrap();
+        assert!(result.min >= 0.0 && result.max <= 5.0);
+        let width = result.histogram.bin_width();
+        assert_eq!(
+            result.histogram.bin_range(1),
+            (result.min + width, result.min + 2.0 * width)
+        );
+
+        equation_handler.set_distribution("x", Distribution::Uniform { min: 2.0, max: 6.0 });
+        let result = equation_handler.monte_carlo("x", &settings).unwrap();
+        assert!((result.percentile(25.0) - 3.0).abs() < 0.05);
+        assert!((result.std_dev - 4.0 / 12f64.sqrt()).abs() < 0.02);
+        for count in &result.histogram.counts {
+            assert!((*count as f64 - 25000.0).abs() < 1000.0);
+        }
+
+        // Negative values give NaN results
+        equation_handler.set_distribution(
+            "x",
+            Distribution::Uniform {
+                min: -1.0,
+                max: 1.0,
+            },
+        );
+        let result = equation_handler.monte_carlo("sqrt(x)", &settings).unwrap();
+        assert!(result.invalid_samples > 45000 && result.invalid_samples < 55000);
+        assert_eq!(result.get_values().len() + result.invalid_samples, 100000);
+
+        let serialized = serde_json::to_string(&equation_handler).unwrap();
+        let deserialized: EquationHandler = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(
+            deserialized.get_distribution("X"),
+            equation_handler.get_distribution("x")
+        );
+        assert!(equation_handler.remove_distribution("X").is_some());
+    }
+
+    #[test]
+    fn constant_histogram() {
+        let equation_handler = EquationHandler::from([("x", 2.0)]);
+        let settings = MonteCarloSettings {
+            samples: 10,
+            seed: 1,
+            histogram_bins: 4,
+        };
+        let result = equation_handler.monte_carlo("x*3", &settings).unwrap();
+        assert_eq!((result.histogram.min, result.histogram.max), (6.0, 6.0));
+        assert_eq!(result.histogram.counts, vec![0, 0, 0, 10]);
+    }
+
+    #[test]
+    fn invalid_distributions() {
+        let mut equation_handler = EquationHandler::from([("x", 0.0)]);
+        let settings = MonteCarloSettings {
+            samples: 1000,
+            seed: 3,
+            histogram_bins: 4,
+        };
+        let distributions = [
+            Distribution::Normal {
+                mean: 1.0,
+                std_dev: -1.0,
+            },
+            Distribution::new_lognormal(-1.0, 1.0),
+            Distribution::new_lognormal(0.0, 1.0),
+            Distribution::Uniform { min: 2.0, max: 1.0 },
+            Distribution::Triangular {
+                min: 0.0,
+                mode: 3.0,
+                max: 2.0,
+            },
+            Distribution::Uniform {
+                min: 0.0,
+                max: f64::NAN,
+            },
+        ];
+        for distribution in distributions {
+            assert!(!distribution.is_valid(), "{distribution:?}");
+            equation_handler.set_distribution("x", distribution);
+            assert_eq!(
+                equation_handler.monte_carlo("x", &settings),
+                Err(EquationError::InvalidDistribution {
+                    name: "x".to_string()
+                })
+            );
+        }
+
+        equation_handler.set_distribution(
+            "x",
+            Distribution::Uniform {
+                min: -2.0,
+                max: -1.0,
+            },
+        );
+        assert_eq!(
+            equation_handler.monte_carlo("sqrt(x)", &settings),
+            Err(EquationError::TooFewSamples { count: 0 })
+        );
+        let settings = MonteCarloSettings {
+            samples: 1,
+            ..settings
+        };
+        assert_eq!(
+            equation_handler.monte_carlo("x", &settings),
+            Err(EquationError::TooFewSamples { count: 1 })
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use crate::equation_handler::{
+    CompiledFormula, EquationError, EquationHandler, Factor, FactorType, COMPARISON_OPERATORS,
+    FUNCTION_SIGNATURES,
+};
+
+/// The binary operators that can be used in the reverse polish notation. The E notation operator
+/// `$` of the parser (`a$b` is `a*10^b`) is written out with `*` and `^` in the exports.
+const RPN_OPERATORS: &[&str] = &["+", "-", "*", "/", "^"];
+
+/// Precedence of the numbers, variables and functions in the infix conversion
+const ATOM_PRECEDENCE: i32 = 5;
+
+impl CompiledFormula {
+    /// Returns the formula in reverse polish notation. The tokens are separated with spaces and
+    /// the arguments of the functions are written before the function name (e.g.
+    /// `sqrt(x)+2` is `x sqrt 2 +`). The number of the arguments of the variadic functions is
+    /// written after a colon (e.g. `piecewise:3`). The E
+    /// notation is written out (e.g. `2E-5` is `2 10 0 5 - ^ *`).
+    pub fn to_rpn(&self) -> String {
+        let mut result = Vec::with_capacity(self.factors.len());
+        // The indexes in the result where the expressions on the stack start
+        let mut starts: Vec<usize> = Vec::new();
+        for factor in &self.factors {
+            if !matches!(factor.factor_type, FactorType::Operator | FactorType::None) {
+                starts.push(result.len());
+            }
+            match factor.factor_type {
+                FactorType::Number => result.push(factor.double_value.to_string()),
+                FactorType::Variable => result.push(factor.key.clone()),
+                FactorType::Function => {
+                    result.extend(factor.args.iter().map(|arg| arg.to_rpn()));
+                    let signature = FUNCTION_SIGNATURES.iter().find(|f| f.name == factor.key);
+                    if signature.is_some_and(|f| !f.is_variadic()) {
+                        result.push(factor.key.clone());
+                    } else {
+                        result.push(format!("{}:{}", factor.key, factor.args.len()));
+                    }
+                }
+                FactorType::Operator => {
+                    let right = starts.pop().unwrap_or(result.len());
+                    let left = starts.pop().unwrap_or(right);
+                    if factor.key == "$" {
+                        // a$b is a*10^b, so 10 is written before the exponent
+                        result.insert(right, "10".to_string());
+                        result.extend(["^".to_string(), "*".to_string()]);
+                    } else {
+                        result.push(factor.key.clone());
+                    }
+                    starts.push(left);
+                }
+                FactorType::None => {}
+            }
+        }
+        result.join(" ")
+    }
+
+    /// Returns the formula in infix notation. The parentheses are only added where the
+    /// precedence of the operators needs them, so the result can differ from the original
+    /// formula string (e.g. `-x` is written as `0-x` and `2E-5` as `2*10^(0-5)`).
+    pub fn to_infix(&self) -> String {
+        // The expressions with the precedence and the key of their last operator
+        let mut stack: Vec<(String, i32, &str)> = Vec::new();
+        for factor in &self.factors {
+            match factor.factor_type {
+                FactorType::Number if factor.double_value < 0.0 => {
+                    stack.push((format!("({})", factor.double_value), ATOM_PRECEDENCE, ""));
+                }
+                FactorType::Number => {
+                    stack.push((factor.double_value.to_string(), ATOM_PRECEDENCE, ""));
+                }
+                FactorType::Variable => stack.push((factor.key.clone(), ATOM_PRECEDENCE, "")),
+                FactorType::Function => {
+                    let args: Vec<String> = factor.args.iter().map(|arg| arg.to_infix()).collect();
+                    let text = format!("{}({})", factor.key, args.join("; "));
+                    stack.push((text, ATOM_PRECEDENCE, ""));
+                }
+                FactorType::Operator => {
+                    let right = stack.pop().unwrap_or_default();
+                    let left = stack.pop().unwrap_or_default();
+                    if factor.key == "$" {
+                        let ten = (String::from("10"), ATOM_PRECEDENCE, "");
+                        let power = infix_operation(ten, right, "^");
+                        stack.push(infix_operation(left, power, "*"));
+                    } else {
+                        stack.push(infix_operation(left, right, factor.key.as_str()));
+                    }
+                }
+                FactorType::None => {}
+            }
+        }
+        stack.pop().map(|(text, _, _)| text).unwrap_or_default()
+    }
+}
+
+/// Writes the binary operation in infix notation. The operands are given with their precedence
+/// and the key of their last operator, and they are wrapped in parentheses where needed.
+fn infix_operation<'a>(
+    (left, left_precedence, _): (String, i32, &'a str),
+    (right, right_precedence, right_key): (String, i32, &'a str),
+    key: &'a str,
+) -> (String, i32, &'a str) {
+    let precedence = Factor::new(0, 0, key.to_string(), FactorType::Operator).get_operand_value();
+    // '^' is right associative and the other operators are left associative
+    let left_parenthesis =
+        left_precedence < precedence || (left_precedence == precedence && key == "^");
+    let right_parenthesis = right_precedence < precedence
+        || (right_precedence == precedence && !(key == "^" && right_key == "^"));
+    let wrap = |text: String, parenthesis: bool| {
+        if parenthesis {
+            format!("({text})")
+        } else {
+            text
+        }
+    };
+    let text = format!(
+        "{}{key}{}",
+        wrap(left, left_parenthesis),
+        wrap(right, right_parenthesis)
+    );
+    (text, precedence, key)
+}
+
+/// A part of the reverse polish notation that is a complete expression
+struct Segment {
+    factors: Vec<Factor>,
+    /// Index of the first token of the segment
+    start: usize,
+    /// Nesting depth of the functions in the segment
+    function_depth: usize,
+}
+
+impl EquationHandler {
+    /// Converts the formula string to the reverse polish notation (see
+    /// [`CompiledFormula::to_rpn`]). Returns an error if the formula is invalid.
+    pub fn to_rpn(&self, formula_string: &str) -> Result<String, EquationError> {
+        Ok(self.compile(formula_string)?.to_rpn())
+    }
+
+    /// Converts the reverse polish notation to an infix formula string (see
+    /// [`CompiledFormula::to_infix`]). Returns an error if the notation is invalid.
+    pub fn rpn_to_infix(&self, rpn_string: &str) -> Result<String, EquationError> {
+        Ok(self.compile_rpn(rpn_string)?.to_infix())
+    }
+
+    /// Calculates the formula given in reverse polish notation (e.g. `5 2 3 * +`). Returns None
+    /// if the notation is invalid.
+    pub fn calculate_rpn(&self, rpn_string: &str) -> Option<f64> {
+        self.try_calculate_rpn(rpn_string).ok()
+    }
+
+    /// Calculates the formula given in reverse polish notation. Returns an error if the notation
+    /// is invalid or if it exceeds the evaluation limits (see
+    /// [`EvaluationLimits`](crate::equation_handler::EvaluationLimits)).
+    pub fn try_calculate_rpn(&self, rpn_string: &str) -> Result<f64, EquationError> {
+        self.compile_rpn(rpn_string)?.evaluate(self)
+    }
+
+    /// Compiles the formula given in reverse polish notation. The tokens are separated with
+    /// whitespace. The tokens are numbers (e.g. `-1,5e3`), variables, the operators (`+ - * /
+    /// ^` and the comparison operators) and the math operators. The number of the arguments is
+    /// given after a colon for the variadic math operators (e.g. `piecewise:3` or `lookup:2`).
+    /// The other math operators take the number of arguments in their signature.
+    pub fn compile_rpn(&self, rpn_string: &str) -> Result<CompiledFormula, EquationError> {
+        let length = rpn_string.chars().count();
+        if length > self.limits.max_formula_length {
+            let max = self.limits.max_formula_length;
+            return Err(EquationError::FormulaTooLong { length, max });
+        }
+        let tokens: Vec<&str> = rpn_string.split_whitespace().collect();
+        if tokens.len() > self.limits.max_token_count {
+            let max = self.limits.max_token_count;
+            return Err(EquationError::TooManyTokens {
+                count: tokens.len(),
+                max,
+            });
+        }
+        let invalid = || EquationError::InvalidFormula {
+            formula: rpn_string.to_string(),
+        };
+        let max_steps = self.limits.max_evaluation_steps;
+
+        let mut stack: Vec<Segment> = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            let index = i as isize;
+            let length = token.len() as isize;
+            let mut chars = token.chars().skip_while(|c| *c == '-' || *c == '+');
+            let is_number = token.len() > 1 || !RPN_OPERATORS.contains(token);
+            if is_number
+                && chars
+                    .next()
+                    .is_some_and(Self::is_number_or_decimal_separator)
+            {
+                let value: f64 = token.replace(',', ".").parse().map_err(|_| invalid())?;
+                let factors = vec![Factor::new_number(index, length, value)];
+                stack.push(Segment {
+                    factors,
+                    start: i,
+                    function_depth: 0,
+                });
+            } else if RPN_OPERATORS.contains(token) || COMPARISON_OPERATORS.contains(token) {
+                let right = stack.pop().ok_or_else(invalid)?;
+                let mut left = stack.pop().ok_or_else(invalid)?;
+                left.factors.extend(right.factors);
+                let operator = Factor::new(index, length, token.to_string(), FactorType::Operator);
+                left.factors.push(operator);
+                left.function_depth = left.function_depth.max(right.function_depth);
+                stack.push(left);
+            } else {
+                let (name, count) = match token.split_once(':') {
+                    Some((name, count)) => (name, Some(count.parse().map_err(|_| invalid())?)),
+                    None => (*token, None),
+                };
+                if let Some(operator) = Self::get_math_operator(name) {
+                    let signature = FUNCTION_SIGNATURES.iter().find(|f| f.name == operator);
+                    let signature = signature.ok_or_else(invalid)?;
+                    let fixed = (!signature.is_variadic()).then(|| signature.min_arity());
+                    let count = count.or(fixed).ok_or_else(invalid)?;
+                    if !signature.accepts_arguments(count) || stack.len() < count {
+                        return Err(invalid());
+                    }
+                    let segments = stack.split_off(stack.len() - count);
+                    let start = segments.first().map_or(i, |s| s.start);
+                    let function_depth =
+                        segments.iter().map(|s| s.function_depth).max().unwrap_or(0) + 1;
+                    if function_depth > self.limits.max_function_depth {
+                        return Err(EquationError::FunctionRecursionTooDeep {
+                            depth: function_depth,
+                            max: self.limits.max_function_depth,
+                        });
+                    }
+                    // The formula strings of the arguments are their parts of the notation
+                    let ends: Vec<usize> = segments
+                        .iter()
+                        .skip(1)
+                        .map(|s| s.start)
+                        .chain([i])
+                        .collect();
+                    let args = segments
+                        .into_iter()
+                        .zip(ends)
+                        .map(|(s, end)| {
+                            let formula = tokens[s.start..end].join(" ");
+                            CompiledFormula::new(&formula, s.factors, max_steps)
+                        })
+                        .collect();
+                    let function =
+                        self.new_function_factor(operator, args, index, length, &invalid)?;
+                    stack.push(Segment {
+                        factors: vec![function],
+                        start,
+                        function_depth,
+                    });
+                } else if count.is_none() && Self::is_variable_name(token) {
+                    let key = self.key_policy.normalize(token);
+                    let value = self.find_variable(&key).unwrap_or(0.0);
+                    let factors = vec![Factor::new_variable(index, length, key, value)];
+                    stack.push(Segment {
+                        factors,
+                        start: i,
+                        function_depth: 0,
+                    });
+                } else {
+                    return Err(invalid());
+                }
+            }
+        }
+        match stack.pop() {
+            Some(segment) if stack.is_empty() => {
+                Ok(CompiledFormula::new(rpn_string, segment.factors, max_steps))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equation_handler::Table;
+
+    #[test]
+    fn rpn_export() {
+        let equation_handler = EquationHandler::from([("x", 2.0)]);
+        let rpn = |formula: &str| equation_handler.to_rpn(formula).unwrap();
+        assert_eq!(rpn("5+2*3"), "5 2 3 * +");
+        assert_eq!(rpn("(5+2)*3"), "5 2 + 3 *");
+        assert_eq!(rpn("2^3^2"), "2 3 2 ^ ^");
+        assert_eq!(rpn("10-4-3"), "10 4 - 3 -");
+        assert_eq!(rpn("-x+1,5"), "0 x - 1.5 +");
+        assert_eq!(rpn("2E-5"), "2 10 0 5 - ^ *");
+        assert_eq!(rpn("x*1,5e3+1"), "x 1.5 10 3 ^ * * 1 +");
+        assert_eq!(rpn("sqrt(X)*2"), "x sqrt 2 *");
+        assert_eq!(rpn("sum(i^2; i; 1; 10)"), "i 2 ^ i 1 10 sum");
+        assert_eq!(rpn("piecewise(x<1; 5; 6)"), "x 1 < 5 6 piecewise:3");
+        assert!(equation_handler.to_rpn("sqrt(1;2)").is_err());
+    }
+
+    #[test]
+    fn rpn_import() {
+        let mut equation_handler = EquationHandler::from([("x", 2.0)]);
+        let calculate = |eq: &EquationHandler, rpn: &str| eq.try_calculate_rpn(rpn).unwrap();
+        assert_eq!(calculate(&equation_handler, "5 2 3 * +"), 11.0);
+        assert_eq!(calculate(&equation_handler, "  5 2\t+ 3 *  "), 21.0);
+        assert_eq!(calculate(&equation_handler, "-1,5 X *"), -3.0);
+        assert_eq!(calculate(&equation_handler, "2 3 2 ^ ^"), 512.0);
+        assert_eq!(calculate(&equation_handler, "x 16 sqrt +"), 6.0);
+        assert_eq!(calculate(&equation_handler, "i 2 ^ i 1 3 sum"), 14.0);
+        assert_eq!(calculate(&equation_handler, "x 1 < 5 6 piecewise:3"), 6.0);
+        assert_eq!(
+            calculate(&equation_handler, "pi 2 /"),
+            std::f64::consts::FRAC_PI_2
+        );
+        let table = Table::new_1d(vec![0.0, 4.0], vec![0.0, 8.0]).unwrap();
+        equation_handler.add_table("t", table);
+        assert_eq!(calculate(&equation_handler, "t x interp:2"), 4.0);
+
+        let invalid_notations = [
+            "",
+            "5 +",
+            "5 2",
+            "x sqrt:2",
+            "1 2 piecewise:2",
+            "5 ( +",
+            "1 2 lookup:2",
+            // The number of the arguments is required for the variadic operators
+            "1 5 piecewise",
+            "2 5 $",
+        ];
+        for invalid in invalid_notations {
+            assert!(
+                equation_handler.try_calculate_rpn(invalid).is_err(),
+                "{invalid}"
+            );
+        }
+        assert_eq!(equation_handler.calculate_rpn("1 +"), None);
+        assert_eq!(
+            equation_handler.try_calculate_rpn("y 1 +"),
+            Err(EquationError::UnknownVariable {
+                name: "y".to_string()
+            })
+        );
+        let compiled = equation_handler.compile_rpn("x y + i i 0 1 sum +").unwrap();
+        assert_eq!(compiled.variables().len(), 2);
+    }
+
+    #[test]
+    fn rpn_round_trip() {
+        let equation_handler =
+            EquationHandler::from([("a", 3.0), ("b", 0.5), ("c", 2.0), ("Ø", 16.0)]);
+        let formulas = [
+            "Ø*60-a",
+            "a-(b-c)",
+            "a-b-c",
+            "a/(b*c)",
+            "(a^b)^c",
+            "a^b^c",
+            "-a^2+(a+b)*c",
+            "2.5E-3*a",
+            "(a<b)==(b<c)",
+            "sqrt(abs(a-b))/c",
+            "sum(i*a; i; 1; c+1)",
+            "piecewise(a<b; 1; b<c; 2; 3)",
+        ];
+        for formula in formulas {
+            let expected = equation_handler.try_calculate_formula(formula).unwrap();
+            let rpn = equation_handler.to_rpn(formula).unwrap();
+            assert_eq!(
+                equation_handler.try_calculate_rpn(&rpn),
+                Ok(expected),
+                "{formula}"
+            );
+            let infix = equation_handler.rpn_to_infix(&rpn).unwrap();
+            let value = equation_handler.try_calculate_formula(&infix);
+            assert_eq!(value, Ok(expected), "{formula} -> {rpn} -> {infix}");
+            assert_eq!(equation_handler.to_rpn(&infix).unwrap(), rpn, "{infix}");
+        }
+        assert_eq!(
+            equation_handler.rpn_to_infix("a b c - -").unwrap(),
+            "a-(b-c)"
+        );
+        assert_eq!(equation_handler.rpn_to_infix("a b - c -").unwrap(), "a-b-c");
+        assert_eq!(
+            equation_handler.rpn_to_infix("a b ^ c ^").unwrap(),
+            "(a^b)^c"
+        );
+        assert_eq!(equation_handler.rpn_to_infix("a b c ^ ^").unwrap(), "a^b^c");
+        assert_eq!(
+            equation_handler.rpn_to_infix("-2 a 1 + sqrt *").unwrap(),
+            "(-2)*sqrt(a+1)"
+        );
+        let compiled = equation_handler.compile("a^2E-5").unwrap();
+        assert_eq!(compiled.to_infix(), "a^2*10^(0-5)");
+        assert!(!equation_handler
+            .rpn_to_infix(&compiled.to_rpn())
+            .unwrap()
+            .contains('$'));
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::equation_handler::{EquationHandler, Scalar};
+
+/// A 1D or 2D table for the table operators `lookup(table; x)` and `interp(table; x)` (or
+/// `interp(table; x; y)` with 2D tables). See [`EquationHandler::add_table`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "TableData")]
+pub struct Table {
+    x: Vec<f64>,
+    /// Empty for 1D tables
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    y: Vec<f64>,
+    /// The values in row-major order (row per x value)
+    values: Vec<f64>,
+}
+
+impl Table {
+    /// Creates a 1D table. The x values must be finite and in ascending order and there must be
+    /// a value for each x value. Returns None if the table is invalid.
+    pub fn new_1d(x: Vec<f64>, values: Vec<f64>) -> Option<Self> {
+        Self::new_checked(x, Vec::new(), values)
+    }
+
+    /// Creates a 2D table where `values[i][j]` is the value at `x[i]` and `y[j]`. The x and y
+    /// values must be finite and in ascending order. Returns None if the table is invalid.
+    pub fn new_2d(x: Vec<f64>, y: Vec<f64>, values: Vec<Vec<f64>>) -> Option<Self> {
+        if y.is_empty() || values.iter().any(|row| row.len() != y.len()) {
+            return None;
+        }
+        Self::new_checked(x, y, values.concat())
+    }
+
+    fn new_checked(x: Vec<f64>, y: Vec<f64>, values: Vec<f64>) -> Option<Self> {
+        let ascending = |axis: &[f64]| {
+            axis.iter().all(|v| v.is_finite()) && axis.windows(2).all(|w| w[0] < w[1])
+        };
+        let count = x.len() * y.len().max(1);
+        if x.is_empty() || !ascending(&x) || !ascending(&y) || values.len() != count {
+            return None;
+        }
+        Some(Table { x, y, values })
+    }
+
+    pub fn get_x(&self) -> &[f64] {
+        &self.x
+    }
+
+    /// Returns the y values of a 2D table. Empty for 1D tables.
+    pub fn get_y(&self) -> &[f64] {
+        &self.y
+    }
+
+    pub fn is_2d(&self) -> bool {
+        !self.y.is_empty()
+    }
+
+    /// Returns the value at `x[i]` (and `y[j]` with 2D tables, `j` is ignored with 1D tables)
+    pub fn get_value(&self, i: usize, j: usize) -> Option<f64> {
+        if !self.is_2d() {
+            self.values.get(i).copied()
+        } else if i < self.x.len() && j < self.y.len() {
+            Some(self.values[i * self.y.len() + j])
+        } else {
+            None
+        }
+    }
+
+    fn value<T: Scalar>(&self, i: usize, j: usize) -> T {
+        T::from_f64(self.values[i * self.y.len().max(1) + j])
+    }
+
+    /// Returns the value at the last x (and y) that is not greater than the given value. Values
+    /// outside the table are clamped to the first or the last row and NaN values give NaN.
+    /// Returns None if `y` is given with a 1D table or it's missing with a 2D table.
+    pub fn lookup<T: Scalar>(&self, x: T, y: Option<T>) -> Option<T> {
+        let (xs, ys) = self.segments(&x, y.as_ref())?;
+        if Self::is_nan(&x, y.as_ref()) {
+            return Some(T::from_f64(f64::NAN));
+        }
+        let mut result: Option<T> = None;
+        for kx in &xs {
+            for ky in &ys {
+                let value = self.value(kx.saturating_sub(1), ky.saturating_sub(1));
+                result = Some(match result {
+                    Some(r) => r.hull(value),
+                    None => value,
+                });
+            }
+        }
+        result
+    }
+
+    /// Returns the linearly (or bilinearly with 2D tables) interpolated value. Values outside
+    /// the table are clamped to the first or the last row and NaN values give NaN. Returns None
+    /// if `y` is given with a 1D table or it's missing with a 2D table.
+    pub fn interp<T: Scalar>(&self, x: T, y: Option<T>) -> Option<T> {
+        let (xs, ys) = self.segments(&x, y.as_ref())?;
+        if Self::is_nan(&x, y.as_ref()) {
+            return Some(T::from_f64(f64::NAN));
+        }
+        let mut result: Option<T> = None;
+        for kx in &xs {
+            let (i0, i1, tx) = Self::weight(&self.x, *kx, &x);
+            for ky in &ys {
+                let (j0, j1, ty) = match &y {
+                    Some(y) => Self::weight(&self.y, *ky, y),
+                    None => (0, 0, T::from_f64(0.0)),
+                };
+                let a: T = self.value(i0, j0);
+                let a = a.clone() + (self.value::<T>(i1, j0) - a) * tx.clone();
+                let b: T = self.value(i0, j1);
+                let b = b.clone() + (self.value::<T>(i1, j1) - b) * tx.clone();
+                let value = a.clone() + (b - a) * ty;
+                result = Some(match result {
+                    Some(r) => r.hull(value),
+                    None => value,
+                });
+            }
+        }
+        result
+    }
+
+    fn is_nan<T: Scalar>(x: &T, y: Option<&T>) -> bool {
+        x.is_nan() || y.is_some_and(T::is_nan)
+    }
+
+    /// Returns the segments of the x and y axes that can contain the given values. Segment `k`
+    /// is between `axis[k - 1]` and `axis[k]`, and the first and the last segments are outside
+    /// the axis. There are more than one segment only if the values are intervals.
+    fn segments<T: Scalar>(&self, x: &T, y: Option<&T>) -> Option<(Vec<usize>, Vec<usize>)> {
+        let segments = |axis: &[f64], value: &T| -> Vec<usize> {
+            (0..=axis.len())
+                .filter(|&k| {
+                    let less_than = |i: usize| value.less_than(&T::from_f64(axis[i])).truth();
+                    let below = k > 0 && less_than(k - 1) == Some(true);
+                    let above = k < axis.len() && less_than(k) == Some(false);
+                    !below && !above
+                })
+                .collect()
+        };
+        match (self.is_2d(), y) {
+            (true, Some(y)) => Some((segments(&self.x, x), segments(&self.y, y))),
+            // 1D tables have a single column. Segment 1 is the index 0.
+            (false, None) => Some((segments(&self.x, x), vec![1])),
+            _ => None,
+        }
+    }
+
+    /// Returns the indexes of the axis values around the segment and the relative position of
+    /// the value between them
+    fn weight<T: Scalar>(axis: &[f64], segment: usize, value: &T) -> (usize, usize, T) {
+        if segment == 0 {
+            (0, 0, T::from_f64(0.0))
+        } else if segment == axis.len() {
+            (segment - 1, segment - 1, T::from_f64(0.0))
+        } else {
+            let (x0, x1) = (axis[segment - 1], axis[segment]);
+            let t = (value.clone() - T::from_f64(x0)) / T::from_f64(x1 - x0);
+            (segment - 1, segment, t)
+        }
+    }
+}
+
+/// The serialized form of [`Table`]. The table is validated when deserializing.
+#[derive(Deserialize)]
+struct TableData {
+    x: Vec<f64>,
+    #[serde(default)]
+    y: Vec<f64>,
+    values: Vec<f64>,
+}
+
+impl TryFrom<TableData> for Table {
+    type Error = String;
+
+    fn try_from(data: TableData) -> Result<Self, Self::Error> {
+        Table::new_checked(data.x, data.y, data.values).ok_or_else(|| "Invalid table".to_string())
+    }
+}
+
+impl EquationHandler {
+    /// Adds the table for the table operators (see
+    /// [`TABLE_OPERATORS`](crate::equation_handler::TABLE_OPERATORS)). The name is handled
+    /// like the variable keys. The tables are copied into the compiled formulas, so changing
+    /// a table doesn't affect the formulas that are already compiled.
+    pub fn add_table(&mut self, name: &str, table: Table) {
+        self.tables.insert(self.key_policy.normalize(name), table);
+    }
+
+    pub fn remove_table(&mut self, name: &str) -> Option<Table> {
+        self.tables.remove(&self.key_policy.normalize(name))
+    }
+
+    /// Returns the table with the given name. The table is searched from the parent too.
+    pub fn get_table(&self, name: &str) -> Option<&Table> {
+        self.find_table(&self.key_policy.normalize(name))
+    }
+
+    /// Returns all the tables, including the tables of the parent
+    pub fn get_tables(&self) -> HashMap<String, Table> {
+        let mut tables = match &self.parent {
+            Some(parent) => parent.get_tables(),
+            None => HashMap::new(),
+        };
+        tables.extend(self.tables.iter().map(|(k, v)| (k.clone(), v.clone())));
+        tables
+    }
+
+    /// Finds the table with the already normalized name
+    pub(crate) fn find_table(&self, name: &str) -> Option<&Table> {
+        self.tables
+            .get(name)
+            .or_else(|| self.parent.as_ref().and_then(|p| p.find_table(name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::equation_handler::{EquationError, Interval};
+    use std::sync::Arc;
+
+    #[test]
+    fn tables() {
+        let table = Table::new_1d(vec![0.0, 0.2, 1.0], vec![1.0, 0.8, 0.4]).unwrap();
+        assert_eq!(table.lookup(0.5, None), Some(0.8));
+        assert_eq!(table.lookup(0.2, None), Some(0.8));
+        assert_eq!(table.lookup(-1.0, None), Some(1.0));
+        assert_eq!(table.interp(0.1, None), Some(0.9));
+        assert_eq!(table.interp(0.2, None), Some(0.8));
+        assert_eq!(table.interp(5.0, None), Some(0.4));
+        assert_eq!(table.interp(0.1, Some(0.0)), None);
+        assert!(table.lookup(f64::NAN, None).unwrap().is_nan());
+        assert!(table.interp(f64::NAN, None).unwrap().is_nan());
+        assert_eq!(table.get_value(1, 0), Some(0.8));
+
+        let table_2d = Table::new_2d(
+            vec![0.0, 10.0],
+            vec![0.0, 1.0, 2.0],
+            vec![vec![0.0, 1.0, 2.0], vec![10.0, 11.0, 12.0]],
+        )
+        .unwrap();
+        assert_eq!(table_2d.interp(5.0, Some(1.5)), Some(6.5));
+        assert_eq!(table_2d.lookup(5.0, Some(1.5)), Some(1.0));
+        assert_eq!(table_2d.interp(20.0, Some(-1.0)), Some(10.0));
+        assert_eq!(table_2d.get_value(1, 2), Some(12.0));
+        assert_eq!(table_2d.interp(5.0, None), None);
+        assert!(table_2d.interp(5.0, Some(f64::NAN)).unwrap().is_nan());
+
+        // Intervals over multiple rows
+        let interval = table.interp(Interval::new(0.1, 0.6), None).unwrap();
+        assert!(interval.contains(0.9) && interval.contains(0.6) && interval.contains(0.8));
+        // Each row is extrapolated over the whole interval, so the result is not the tightest
+        assert!(interval.lo > 0.39 && interval.hi < 0.91);
+        let interval = table.lookup(Interval::new(0.1, 0.6), None).unwrap();
+        assert_eq!(interval, Interval::new(0.8, 1.0));
+        assert!(table.interp(Interval::empty(), None).unwrap().is_empty());
+
+        assert!(Table::new_1d(vec![1.0, 0.0], vec![1.0, 2.0]).is_none());
+        assert!(Table::new_1d(vec![0.0, 1.0], vec![1.0]).is_none());
+        assert!(Table::new_2d(vec![0.0], vec![0.0, 1.0], vec![vec![1.0]]).is_none());
+    }
+
+    #[test]
+    fn table_operators() {
+        let mut equation_handler = EquationHandler::from([("lambda", 0.6)]);
+        let kc = Table::new_1d(vec![0.2, 1.0, 2.0], vec![1.0, 0.6, 0.2]).unwrap();
+        equation_handler.add_table("Kc", kc);
+        let calculate = |eq: &EquationHandler, f: &str| eq.try_calculate_formula(f).unwrap();
+        assert!((calculate(&equation_handler, "interp(kc; lambda)") - 0.8).abs() < 1e-12);
+        assert_eq!(calculate(&equation_handler, "lookup(KC; lambda)*2"), 2.0);
+        assert_eq!(
+            equation_handler.try_calculate_formula("interp(missing; lambda)"),
+            Err(EquationError::UnknownTable {
+                name: "missing".to_string()
+            })
+        );
+        assert!(equation_handler
+            .try_calculate_formula("interp(kc; lambda; 1)")
+            .is_err());
+
+        let info = equation_handler.analyze("interp(kc; lambda)");
+        assert!(info.tables.contains("kc") && info.unknown_variables.is_empty());
+        assert!(equation_handler.analyze("lookup()").tables.is_empty());
+        assert!(calculate(&equation_handler, "interp(kc; 0/0)").is_nan());
+        let compiled = equation_handler.compile("interp(kc; lambda)").unwrap();
+        assert_eq!(compiled.variables().len(), 1);
+
+        // Tables are serialized with the handler and found from the parent
+        let serialized = serde_json::to_string(&equation_handler).unwrap();
+        let deserialized: EquationHandler = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(
+            deserialized.get_table("kc"),
+            equation_handler.get_table("KC")
+        );
+        let child = EquationHandler::new_child(Arc::new(deserialized));
+        assert!((calculate(&child, "interp(kc; lambda)") - 0.8).abs() < 1e-12);
+        assert_eq!(child.snapshot().get_tables().len(), 1);
+        let invalid = r#"{"variables":{},"tables":{"t":{"x":[1.0,0.0],"values":[1.0,2.0]}}}"#;
+        assert!(serde_json::from_str::<EquationHandler>(invalid).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::equation_handler::{EquationHandler, FormulaInfo};
+
+/// The kind of a [`Token`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenKind {
+    /// A number, including the E-notation (e.g. `5`, `3,49` or `2E-5`)
+    Number,
+    /// A variable that is set in the equation handler or a built-in constant (see
+    /// [`CONSTANTS`](crate::equation_handler::CONSTANTS))
+    VariableKnown,
+    /// A variable that is not set in the equation handler
+    VariableUnknown,
+    /// A variable that is bound by a math operator (e.g. `i` in `sum(i^2; i; 1; 10)`). Like in
+    /// [`EquationHandler::analyze`], the names of the bound variables are bound everywhere in
+    /// the formula.
+    VariableBound,
+    /// A math operator call (see [`MATH_OPERATORS`](crate::equation_handler::MATH_OPERATORS)).
+    /// The span covers only the name.
+    Function,
+    /// An operator (+, -, *, /, ^) or a comparison operator (e.g. `<=`)
+    Operator,
+    /// An opening or closing parenthesis
+    Paren,
+    /// A semicolon that separates the arguments of a math operator
+    Separator,
+    /// A comment in brackets (e.g. unit `[kN]`). The span includes the brackets.
+    UnitComment,
+    /// One or more whitespace characters
+    Whitespace,
+    /// A character that can't be used in a formula (e.g. closing bracket without opening one)
+    Invalid,
+}
+
+/// A token in the formula string. The span is the byte range of the token in the original
+/// formula string, so it can be used to highlight the formula in an editor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+impl Token {
+    pub fn new(kind: TokenKind, span: Range<usize>) -> Self {
+        Token { kind, span }
+    }
+
+    /// Returns the text of the token from the formula string it was tokenized from
+    pub fn text<'a>(&self, formula_string: &'a str) -> &'a str {
+        &formula_string[self.span.clone()]
+    }
+}
+
+impl EquationHandler {
+    /// Splits the formula string into tokens for syntax highlighting. The formula is scanned
+    /// the same way as in the calculation, but the token spans refer to the given string. The
+    /// whitespace between the tokens is kept, so all the characters in the string are covered
+    /// by the tokens. The whitespace inside a token is part of the token like in the
+    /// calculation (e.g. `1 5` is the number 15).
+    pub fn tokenize(&self, formula_string: &str) -> Vec<Token> {
+        let (formatted, offsets) = Self::handle_string_formatting_with_offsets(formula_string);
+        // The keys are normalized character by character so that the offsets stay in sync
+        let mut normalized = String::with_capacity(formatted.len());
+        let mut normalized_offsets = Vec::with_capacity(offsets.len());
+        for (c, offset) in formatted.chars().zip(offsets) {
+            for n in self
+                .key_policy
+                .normalize(c.encode_utf8(&mut [0; 4]))
+                .chars()
+            {
+                normalized.push(n);
+                normalized_offsets.push(offset);
+            }
+        }
+        let mut scanned = Vec::new();
+        // Math operators are not compiled when the info is collected, so this can't fail
+        let mut info = FormulaInfo::default();
+        let _ = self.populate_lists(&normalized, 0, &mut 0, Some(&mut info), Some(&mut scanned));
+
+        let normalized: Vec<char> = normalized.chars().collect();
+        let mut tokens: Vec<Token> = Vec::with_capacity(scanned.len());
+        // Parenthesis depth inside the exponent of the E-notation
+        let mut exponent_depth = None;
+        for mut token in scanned {
+            let first = normalized[token.span.start];
+            if token.kind == TokenKind::VariableUnknown {
+                let name: String = normalized[token.span.clone()].iter().collect();
+                if info.bound_variables.contains(&name) {
+                    token.kind = TokenKind::VariableBound;
+                }
+            }
+            let span = Self::get_original_span(formula_string, &normalized_offsets[token.span]);
+            if let Some(depth) = exponent_depth.as_mut() {
+                // The exponent (e.g. 2E-5 => 2$(0-5)) is part of the number before it
+                match (token.kind, first) {
+                    (TokenKind::Paren, '(') => *depth += 1,
+                    (TokenKind::Paren, _) => *depth -= 1,
+                    _ => {}
+                }
+                if let (Some(span), Some(number)) = (span, tokens.last_mut()) {
+                    number.span.end = number.span.end.max(span.end);
+                }
+                if *depth == 0 {
+                    exponent_depth = None;
+                }
+                continue;
+            }
+            match span {
+                // '$' added by the formatting starts the exponent of the E-notation
+                None if first == '$' => exponent_depth = Some(0),
+                // Other characters added by the formatting are skipped
+                None => {}
+                // '$' is only used internally
+                Some(span) if first == '$' => tokens.push(Token::new(TokenKind::Invalid, span)),
+                Some(span) => tokens.push(Token::new(token.kind, span)),
+            }
+        }
+
+        // The numbers and variables are scanned after the comments inside them
+        tokens.sort_by_key(|t| t.span.start);
+        let mut result = Vec::with_capacity(tokens.len() * 2);
+        let mut position = 0;
+        for token in tokens {
+            if token.span.start < position {
+                continue;
+            }
+            Self::add_gap_tokens(formula_string, position..token.span.start, &mut result);
+            position = token.span.end;
+            result.push(token);
+        }
+        Self::add_gap_tokens(formula_string, position..formula_string.len(), &mut result);
+        result
+    }
+
+    /// Gets the byte range in the original formula string of the scanned characters. Returns
+    /// None if all the characters were added by the formatting.
+    fn get_original_span(formula_string: &str, offsets: &[Option<usize>]) -> Option<Range<usize>> {
+        let mut original = offsets.iter().flatten();
+        let start = *original.next()?;
+        let last = original.last().copied().unwrap_or(start);
+        let length = formula_string[last..]
+            .chars()
+            .next()
+            .map_or(0, char::len_utf8);
+        Some(start..last + length)
+    }
+
+    /// Adds the tokens for the characters between the scanned tokens. The whitespace is removed
+    /// before the scanning and the other characters can't be used in a formula.
+    fn add_gap_tokens(formula_string: &str, gap: Range<usize>, result: &mut Vec<Token>) {
+        for (i, c) in formula_string[gap.clone()].char_indices() {
+            let index = gap.start + i;
+            let kind = match c.is_whitespace() {
+                true => TokenKind::Whitespace,
+                false => TokenKind::Invalid,
+            };
+            match result.last_mut() {
+                Some(last) if last.kind == kind && last.span.end == index => {
+                    last.span.end = index + c.len_utf8();
+                }
+                _ => result.push(Token::new(kind, index..index + c.len_utf8())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(equation_handler: &EquationHandler, formula_string: &str) -> Vec<(TokenKind, String)> {
+        equation_handler
+            .tokenize(formula_string)
+            .iter()
+            .map(|t| (t.kind, t.text(formula_string).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn tokenize() {
+        let equation_handler = EquationHandler::from([("TESTI", 10.0)]);
+        let tokens = kinds(&equation_handler, "-5 + sqrt (testi)*2E-5 [kN] ^ foo");
+        let expected = vec![
+            (TokenKind::Operator, "-"),
+            (TokenKind::Number, "5"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::Operator, "+"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::Function, "sqrt"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::Paren, "("),
+            (TokenKind::VariableKnown, "testi"),
+            (TokenKind::Paren, ")"),
+            (TokenKind::Operator, "*"),
+            (TokenKind::Number, "2E-5"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::UnitComment, "[kN]"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::Operator, "^"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::VariableUnknown, "foo"),
+        ];
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(k, t)| (k, t.to_string()))
+            .collect();
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn tokenize_spans() {
+        let equation_handler = EquationHandler::from([("Ø", 16.0)]);
+        let formula_string = "Ø*60 ]";
+        let tokens = equation_handler.tokenize(formula_string);
+        assert_eq!(tokens[0], Token::new(TokenKind::VariableKnown, 0..2));
+        assert_eq!(tokens[1], Token::new(TokenKind::Operator, 2..3));
+        assert_eq!(tokens[2], Token::new(TokenKind::Number, 3..5));
+        assert_eq!(tokens[4], Token::new(TokenKind::Invalid, 6..7));
+        // All the characters are covered
+        let covered: usize = tokens.iter().map(|t| t.span.len()).sum();
+        assert_eq!(covered, formula_string.len());
+
+        let tokens = kinds(&equation_handler, "sum(i;i;1;2)");
+        assert_eq!(tokens[0], (TokenKind::Function, "sum".to_string()));
+        assert_eq!(tokens[3], (TokenKind::Separator, ";".to_string()));
+        assert_eq!(tokens[2], (TokenKind::VariableBound, "i".to_string()));
+        assert_eq!(tokens[4], (TokenKind::VariableBound, "i".to_string()));
+        let tokens = kinds(&equation_handler, "integrate(x^2; x; 0; k)");
+        assert_eq!(tokens[2], (TokenKind::VariableBound, "x".to_string()));
+        assert_eq!(tokens[13], (TokenKind::VariableUnknown, "k".to_string()));
+        let tokens = kinds(&equation_handler, "Ø<=5");
+        assert_eq!(tokens[1], (TokenKind::Operator, "<=".to_string()));
+
+        // Number followed by e that is not an exponent (e is a built-in constant)
+        let tokens = kinds(&equation_handler, "2e");
+        assert_eq!(tokens[0], (TokenKind::Number, "2".to_string()));
+        assert_eq!(tokens[1], (TokenKind::VariableKnown, "e".to_string()));
+    }
+
+    #[test]
+    fn tokenize_formatted_formula() {
+        let equation_handler = EquationHandler::from([("x", 1.0)]);
+        // The spans refer to the original string although the whitespace is removed and zeros
+        // are added before the negative signs in the calculation
+        let formula_string = "-x ^ -2 * ( -1 5 )";
+        let tokens: Vec<_> = equation_handler
+            .tokenize(formula_string)
+            .into_iter()
+            .filter(|t| t.kind != TokenKind::Whitespace)
+            .collect();
+        let expected = vec![
+            Token::new(TokenKind::Operator, 0..1),
+            Token::new(TokenKind::VariableKnown, 1..2),
+            Token::new(TokenKind::Operator, 3..4),
+            Token::new(TokenKind::Operator, 5..6),
+            Token::new(TokenKind::Number, 6..7),
+            Token::new(TokenKind::Operator, 8..9),
+            Token::new(TokenKind::Paren, 10..11),
+            Token::new(TokenKind::Operator, 12..13),
+            // The whitespace is removed before the calculation, so this is the number 15
+            Token::new(TokenKind::Number, 13..16),
+            Token::new(TokenKind::Paren, 17..18),
+        ];
+        assert_eq!(tokens, expected);
+
+        let tokens = kinds(&equation_handler, "2 E -5[k N] *x");
+        assert_eq!(tokens[0], (TokenKind::Number, "2 E -5".to_string()));
+        assert_eq!(tokens[1], (TokenKind::UnitComment, "[k N]".to_string()));
+        assert_eq!(tokens[4], (TokenKind::VariableKnown, "x".to_string()));
+
+        // '$' is only used internally for the E-notation
+        let tokens = kinds(&equation_handler, "2$3");
+        assert_eq!(tokens[1], (TokenKind::Invalid, "$".to_string()));
+        let tokens = kinds(&equation_handler, "1 [kN");
+        assert_eq!(tokens[2], (TokenKind::UnitComment, "[kN".to_string()));
+    }
+}
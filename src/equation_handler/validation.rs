@@ -0,0 +1,323 @@
+use crate::equation_handler::{
+    EquationError, EquationHandler, TokenKind, COMPARISON_OPERATORS, FUNCTION_SIGNATURES,
+};
+
+/// An open parenthesis found in the validation
+struct OpenParenthesis {
+    /// Byte index in the formula string
+    index: usize,
+    /// Name of the called function if the parenthesis starts a function call
+    function: Option<String>,
+    /// Number of the arguments found so far (separators + 1)
+    arguments: usize,
+    /// True if nothing has been found in the current argument
+    argument_empty: bool,
+}
+
+impl EquationHandler {
+    /// Checks the syntax of the formula string without calculating it, so the variables don't
+    /// need to have values. Returns all the problems found (e.g. unbalanced parentheses,
+    /// consecutive operators, numbers with multiple decimal separators, empty function arguments
+    /// and unknown functions). The indexes in the errors are byte indexes in the formula string.
+    pub fn validate(&self, formula_string: &str) -> Result<(), Vec<EquationError>> {
+        let tokens: Vec<_> = self
+            .tokenize(formula_string)
+            .into_iter()
+            .filter(|t| !matches!(t.kind, TokenKind::UnitComment | TokenKind::Whitespace))
+            .collect();
+        let mut errors = Vec::new();
+        let mut stack: Vec<OpenParenthesis> = Vec::new();
+        let mut expect_operand = true;
+        let mut previous: Option<(TokenKind, &str)> = None;
+        for (i, token) in tokens.iter().enumerate() {
+            let text = token.text(formula_string);
+            let index = token.span.start;
+            let next = tokens.get(i + 1).map(|t| (t.kind, t.text(formula_string)));
+            if let Some(open) = stack.last_mut() {
+                if token.kind != TokenKind::Separator
+                    && !(token.kind == TokenKind::Paren && text == ")")
+                {
+                    open.argument_empty = false;
+                }
+            }
+            match token.kind {
+                TokenKind::Number => {
+                    if !expect_operand {
+                        errors.push(EquationError::MissingOperator { index });
+                    }
+                    // The mantissa and the exponent can both have one decimal separator
+                    let separators = |s: &str| s.chars().filter(|c| *c == '.' || *c == ',').count();
+                    let mut parts = text.splitn(2, ['e', 'E']);
+                    if parts.any(|part| separators(part) > 1) {
+                        errors.push(EquationError::InvalidNumber {
+                            text: text.to_string(),
+                            index,
+                        });
+                    }
+                    expect_operand = false;
+                }
+                TokenKind::VariableKnown
+                | TokenKind::VariableUnknown
+                | TokenKind::VariableBound => {
+                    if next == Some((TokenKind::Paren, "(")) {
+                        // A name followed by a parenthesis is a call of an unknown function
+                        errors.push(EquationError::UnknownFunction {
+                            name: text.to_string(),
+                            index,
+                        });
+                    } else if !expect_operand {
+                        errors.push(EquationError::MissingOperator { index });
+                    }
+                    expect_operand = next == Some((TokenKind::Paren, "("));
+                }
+                TokenKind::Function => {
+                    if !expect_operand {
+                        errors.push(EquationError::MissingOperator { index });
+                    }
+                    expect_operand = true;
+                }
+                TokenKind::Paren if text == "(" => {
+                    let function = match previous {
+                        Some((TokenKind::Function, name))
+                        | Some((TokenKind::VariableKnown, name))
+                        | Some((TokenKind::VariableUnknown, name))
+                        | Some((TokenKind::VariableBound, name)) => Some(name.to_lowercase()),
+                        _ => None,
+                    };
+                    if !expect_operand {
+                        errors.push(EquationError::MissingOperator { index });
+                    }
+                    stack.push(OpenParenthesis {
+                        index,
+                        function,
+                        arguments: 1,
+                        argument_empty: true,
+                    });
+                    expect_operand = true;
+                }
+                TokenKind::Paren => match stack.pop() {
+                    Some(open) => {
+                        match &open.function {
+                            Some(function) if open.argument_empty => {
+                                let function = function.clone();
+                                errors.push(EquationError::EmptyArgument { function, index });
+                            }
+                            None if open.argument_empty => {
+                                errors.push(EquationError::MissingOperand { index });
+                            }
+                            _ if expect_operand => {
+                                errors.push(EquationError::MissingOperand { index });
+                            }
+                            _ => {}
+                        }
+                        if let Some(function) = &open.function {
+                            let signature = FUNCTION_SIGNATURES.iter().find(|f| f.name == function);
+                            if signature.is_some_and(|f| !f.accepts_arguments(open.arguments)) {
+                                errors.push(EquationError::WrongArgumentCount {
+                                    function: function.clone(),
+                                    count: open.arguments,
+                                    index: open.index,
+                                });
+                            }
+                        }
+                        expect_operand = false;
+                    }
+                    None => errors.push(EquationError::UnbalancedParenthesis { index }),
+                },
+                TokenKind::Separator => match stack.last_mut() {
+                    Some(open) if open.function.is_some() => {
+                        if open.argument_empty {
+                            let function = open.function.clone().unwrap_or_default();
+                            errors.push(EquationError::EmptyArgument { function, index });
+                        } else if expect_operand {
+                            errors.push(EquationError::MissingOperand { index });
+                        }
+                        open.arguments += 1;
+                        open.argument_empty = true;
+                        expect_operand = true;
+                    }
+                    _ => {
+                        // Handled like an operator to avoid reporting the next operand too
+                        errors.push(EquationError::UnexpectedCharacter {
+                            character: ';',
+                            index,
+                        });
+                        expect_operand = true;
+                    }
+                },
+                TokenKind::Operator => {
+                    if expect_operand && !Self::unary_minus_allowed(text, previous, next) {
+                        match previous {
+                            Some((TokenKind::Operator, _)) => {
+                                errors.push(EquationError::ConsecutiveOperators { index });
+                            }
+                            _ => errors.push(EquationError::MissingOperand { index }),
+                        }
+                    }
+                    expect_operand = true;
+                }
+                TokenKind::Invalid => {
+                    let character = text.chars().next().unwrap_or_default();
+                    errors.push(EquationError::UnexpectedCharacter { character, index });
+                }
+                TokenKind::Whitespace | TokenKind::UnitComment => {}
+            }
+            previous = Some((token.kind, text));
+        }
+        if expect_operand && !matches!(previous, Some((TokenKind::Paren, "("))) {
+            errors.push(EquationError::MissingOperand {
+                index: formula_string.len(),
+            });
+        }
+        for open in stack {
+            errors.push(EquationError::UnbalancedParenthesis { index: open.index });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Checks if the operator is a negative sign that the calculation can handle. The negative
+    /// sign is allowed at the start, after an opening parenthesis, an argument separator or a
+    /// comparison operator, and after `^` if a number follows it (e.g. `10^-5`).
+    fn unary_minus_allowed(
+        operator: &str,
+        previous: Option<(TokenKind, &str)>,
+        next: Option<(TokenKind, &str)>,
+    ) -> bool {
+        if operator != "-" {
+            return false;
+        }
+        match previous {
+            None | Some((TokenKind::Paren, "(")) | Some((TokenKind::Separator, _)) => true,
+            Some((TokenKind::Operator, "^")) => {
+                next.is_some_and(|(kind, _)| kind == TokenKind::Number)
+            }
+            Some((TokenKind::Operator, op)) => COMPARISON_OPERATORS.contains(&op),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        let equation_handler = EquationHandler::from([("TESTI", 10.0)]);
+        let valid = [
+            "5 +5*15",
+            "( 5+5)  *1 5",
+            "-5+ 5*15 *(-15 )*1 0^ -5",
+            "-5*10^2^2+5*15*(-15)*10^-5^-5",
+            "sqrt(testi) * unknown [kN]",
+            "2E-5 + 1,5e3",
+            "sum(i^2; i; -1; 10) + piecewise(x<-1; 1; 2)",
+            "interp(table; x)",
+        ];
+        for formula in valid {
+            assert_eq!(equation_handler.validate(formula), Ok(()), "{formula}");
+        }
+
+        let errors = |formula: &str| equation_handler.validate(formula).expect_err(formula);
+        assert_eq!(
+            errors("(5+3*(2"),
+            vec![
+                EquationError::UnbalancedParenthesis { index: 0 },
+                EquationError::UnbalancedParenthesis { index: 5 },
+            ]
+        );
+        // The missing parentheses are not added by the string formatting
+        assert_eq!(
+            errors("(-5+(-3"),
+            vec![
+                EquationError::UnbalancedParenthesis { index: 0 },
+                EquationError::UnbalancedParenthesis { index: 4 },
+            ]
+        );
+        assert_eq!(
+            errors("5+3)"),
+            vec![EquationError::UnbalancedParenthesis { index: 3 }]
+        );
+        assert_eq!(
+            errors("5 * / 3"),
+            vec![EquationError::ConsecutiveOperators { index: 4 }]
+        );
+        assert_eq!(
+            errors("2*-3"),
+            vec![EquationError::ConsecutiveOperators { index: 2 }]
+        );
+        assert_eq!(
+            errors("1.2.3 + 4,5e1.2.3"),
+            vec![
+                EquationError::InvalidNumber {
+                    text: "1.2.3".to_string(),
+                    index: 0
+                },
+                EquationError::InvalidNumber {
+                    text: "4,5e1.2.3".to_string(),
+                    index: 8
+                },
+            ]
+        );
+        assert_eq!(
+            errors("sqrt() + sum(i;;1;2)"),
+            vec![
+                EquationError::EmptyArgument {
+                    function: "sqrt".to_string(),
+                    index: 5
+                },
+                EquationError::EmptyArgument {
+                    function: "sum".to_string(),
+                    index: 15
+                },
+            ]
+        );
+        assert_eq!(
+            errors("foo(2) + sqrt(1; 2)"),
+            vec![
+                EquationError::UnknownFunction {
+                    name: "foo".to_string(),
+                    index: 0
+                },
+                EquationError::WrongArgumentCount {
+                    function: "sqrt".to_string(),
+                    count: 2,
+                    index: 13
+                },
+            ]
+        );
+        assert_eq!(
+            errors("2+"),
+            vec![EquationError::MissingOperand { index: 2 }]
+        );
+        assert_eq!(
+            errors("(*2)"),
+            vec![EquationError::MissingOperand { index: 1 }]
+        );
+        assert_eq!(
+            errors("2 (3)"),
+            vec![EquationError::MissingOperator { index: 2 }]
+        );
+        assert_eq!(errors(""), vec![EquationError::MissingOperand { index: 0 }]);
+        assert_eq!(
+            errors("1;2 ]"),
+            vec![
+                EquationError::UnexpectedCharacter {
+                    character: ';',
+                    index: 1
+                },
+                EquationError::UnexpectedCharacter {
+                    character: ']',
+                    index: 4
+                },
+            ]
+        );
+        // All the problems are reported at once
+        assert_eq!(errors("(1..2 + * foo(3)").len(), 4);
+    }
+}
@@ -1,14 +1,37 @@
 ﻿#![allow(dead_code)]
 #![allow(non_snake_case)]
 
+pub mod boolean;
+pub mod circle;
+pub mod hull;
+pub mod line_segment;
+pub mod multi_polygon;
+pub mod offset;
+pub mod oriented_rectangle;
 pub mod polygon;
+pub mod polygon_with_holes;
 pub mod rectangle;
+pub mod section_properties;
+pub mod triangulation;
+pub mod validation;
 pub mod vppoint;
+#[cfg(test)]
+mod test_utils;
 
 use polygon::Direction;
 pub use vppoint::VpPoint;
 pub use rectangle::Rectangle;
-pub use polygon::Polygon;
+pub use polygon::{Containment, Polygon};
+pub use boolean::BooleanOperation;
+pub use line_segment::{LineSegment, SegmentIntersection};
+pub use polygon_with_holes::PolygonWithHoles;
+pub use multi_polygon::MultiPolygon;
+pub use offset::JoinType;
+pub use circle::Circle;
+pub use oriented_rectangle::OrientedRectangle;
+pub use section_properties::SectionProperties;
+pub use triangulation::{MeshOptions, TriangleMesh};
+pub use validation::PolygonIssue;
 
 /// Calculates the length between two points.
 pub fn calc_length_between_points(p1: &VpPoint, p2: &VpPoint) -> f64 {
@@ -34,8 +57,8 @@ pub fn get_polygon_direction(polygon: &Polygon) -> Direction {
     if area < 0.0 { Direction::Clockwise } else { Direction::CounterClockwise }
 }
 
-/// Calculates the area of given polygon. Always returns a positive value.
-/// Note, polygon can't self intersect or the calculated area is not correct.
+/// Calculates the area of given polygon. Always returns a positive value. The polygon can be
+/// closed or open. Note, polygon can't self intersect or the calculated area is not correct.
 pub fn calculate_area(polygon: &Polygon) -> f64 {
     let area = calculate_area_internal(polygon);
     area.abs()
@@ -45,14 +68,7 @@ pub fn calculate_area(polygon: &Polygon) -> f64 {
 /// a positive value is returned. Otherwise the returned value is negative. See more at
 /// https://en.wikipedia.org/wiki/Shoelace_formula Triangle formula
 fn calculate_area_internal(polygon: &Polygon) -> f64 {
-    let mut area = 0.0;
-    for i in 0..(polygon.points.len()-1) {
-        area += area_sum_function(&polygon.points[i], &polygon.points[i+1]);
-    }
-    area += area_sum_function(&polygon.points[polygon.points.len()-1], &polygon.points[0]);
-    area /= 2.0;
-
-    area
+    signed_area(&polygon.points)
 }
 
 /// See https://en.wikipedia.org/wiki/Shoelace_formula Triangle formula
@@ -60,14 +76,57 @@ fn area_sum_function(p1: &VpPoint, p2: &VpPoint) -> f64 {
     p1.x * p2.y - p2.x * p1.y
 }
 
-/// Calculates the centroid from given polygon from current origo (positive axes: x →, y ↑)
+/// Calculates the signed area of the ring (positive if counterclockwise). The ring can be closed
+/// or open, the last point is always connected to the first one.
+fn signed_area(points: &[VpPoint]) -> f64 {
+    let count = points.len();
+    let mut area = 0.0;
+    for i in 0..count {
+        area += area_sum_function(&points[i], &points[(i + 1) % count]);
+    }
+    area / 2.0
+}
+
+/// Positive if the points turn counterclockwise (twice the signed area of the triangle)
+fn cross(p1: &VpPoint, p2: &VpPoint, p3: &VpPoint) -> f64 {
+    (p2.x - p1.x) * (p3.y - p1.y) - (p2.y - p1.y) * (p3.x - p1.x)
+}
+
+/// Gets the center of the circle through the three points. The points must not be collinear.
+fn circumcenter(a: &VpPoint, b: &VpPoint, c: &VpPoint) -> VpPoint {
+    let (bx, by) = (b.x - a.x, b.y - a.y);
+    let (cx, cy) = (c.x - a.x, c.y - a.y);
+    let d = 2.0 * (bx * cy - by * cx);
+    let (b2, c2) = (bx * bx + by * by, cx * cx + cy * cy);
+    VpPoint::new(a.x + (cy * b2 - by * c2) / d, a.y + (bx * c2 - cx * b2) / d)
+}
+
+/// Calculates how many times the points of a polygon wind around the given point. The result is
+/// positive for counterclockwise and negative for clockwise windings and zero if the point is
+/// outside. The polygon can be closed or open. Points on the boundary can be counted either way.
+pub fn winding_number(points: &[VpPoint], point: &VpPoint) -> i32 {
+    let mut winding = 0;
+    for i in 0..points.len() {
+        let (p1, p2) = (&points[i], &points[(i + 1) % points.len()]);
+        let cross = (p2.x - p1.x) * (point.y - p1.y) - (point.x - p1.x) * (p2.y - p1.y);
+        if p1.y <= point.y && p2.y > point.y && cross > 0.0 {
+            winding += 1;
+        } else if p1.y > point.y && p2.y <= point.y && cross < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+/// Calculates the centroid from given polygon from current origo (positive axes: x →, y ↑).
+/// The polygon can be closed or open.
 pub fn centroid_from_polygon(polygon: &Polygon) -> VpPoint {
     let (mut x, mut y) = (0.0, 0.0);
-    for i in 0..polygon.points.len()-1 {
-        let cur_x = polygon.points[i].x;
-        let next_x = polygon.points[i+1].x;
-        let cur_y = polygon.points[i].y;
-        let next_y = polygon.points[i+1].y;
+    for (current, next) in polygon.edges() {
+        let cur_x = current.x;
+        let next_x = next.x;
+        let cur_y = current.y;
+        let next_y = next.y;
         x += (cur_x + next_x)*(cur_x*next_y - next_x*cur_y);
         y += (cur_y+next_y)*(cur_x*next_y-next_x*cur_y);
     }
@@ -82,7 +141,7 @@ pub fn centroid_from_polygon(polygon: &Polygon) -> VpPoint {
 
 /// Gets the minimum x value from given list of points
 pub fn get_min_x(points: &Vec<VpPoint>) -> Option<f64> {
-    if points.len() == 0 {
+    if points.is_empty() {
         return None;
     }
     let mut min = points[0].x;
@@ -96,7 +155,7 @@ pub fn get_min_x(points: &Vec<VpPoint>) -> Option<f64> {
 
 /// Gets the minimum x value from given list of points
 pub fn get_max_x(points: &Vec<VpPoint>) -> Option<f64> {
-    if points.len() == 0 {
+    if points.is_empty() {
         return None;
     }
     let mut max = points[0].x;
@@ -110,7 +169,7 @@ pub fn get_max_x(points: &Vec<VpPoint>) -> Option<f64> {
 
 /// Gets the minimum x value from given list of points
 pub fn get_min_y(points: &Vec<VpPoint>) -> Option<f64> {
-    if points.len() == 0 {
+    if points.is_empty() {
         return None;
     }
     let mut min = points[0].y;
@@ -124,7 +183,7 @@ pub fn get_min_y(points: &Vec<VpPoint>) -> Option<f64> {
 
 /// Gets the minimum x value from given list of points
 pub fn get_max_y(points: &Vec<VpPoint>) -> Option<f64> {
-    if points.len() == 0 {
+    if points.is_empty() {
         return None;
     }
     let mut max = points[0].y;
@@ -246,6 +305,10 @@ mod tests {
 
         assert_eq!(area, 0.0);
         assert_eq!(direction, Direction::CounterClockwise);
+        // The bow-tie has to be split to get the correct area
+        assert!(!polygon.is_valid(1e-9));
+        let parts = polygon.make_valid(1e-9);
+        assert_eq!(parts.iter().map(calculate_area).sum::<f64>(), 312.5);
     }
 
     #[test]
@@ -309,6 +372,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::excessive_precision)]
     fn calculate_length_between() {
         let p1 = VpPoint::new(123.0, 123.0);
         let p2 = VpPoint::new(40075000.0, 321321.0);
@@ -385,7 +449,7 @@ mod tests {
         let p1 = VpPoint::new(123.0, 0.0);
         let p2 = VpPoint::new(123.0+123.0+123.0, 0.0);
         let p3 = VpPoint::new(123.0+123.0+123.0+123.0, 0.0);
-        assert!(point_in_line(&p1, &p2, &p3, 0.1) == false);
+        assert!(!point_in_line(&p1, &p2, &p3, 0.1));
 
         let p1 = VpPoint::new(0.0, 123.0);
         let p2 = VpPoint::new(0.0, 123.0+123.0+123.0);
@@ -395,7 +459,7 @@ mod tests {
         let p1 = VpPoint::new(123.0, 0.0);
         let p2 = VpPoint::new(123.0+123.0+123.0, 0.0);
         let p3 = VpPoint::new(-123.0, 0.0);
-        assert!(point_in_line(&p1, &p2, &p3, 0.1) == false);
+        assert!(!point_in_line(&p1, &p2, &p3, 0.1));
 
         let p1 = VpPoint::new(123.0, 123.0);
         let p2 = VpPoint::new(123.0+123.0+123.0, 123.0+123.0+123.0);
@@ -405,12 +469,12 @@ mod tests {
         let p1 = VpPoint::new(123.0, 123.0);
         let p2 = VpPoint::new(123.0+123.0+123.0, 123.0+123.0+123.0);
         let p3 = VpPoint::new(123.0+123.0+1.0, 123.0+123.0);
-        assert!(point_in_line(&p1, &p2, &p3, 0.1) == false);
+        assert!(!point_in_line(&p1, &p2, &p3, 0.1));
 
         let p1 = VpPoint::new(123.0, 123.0);
         let p2 = VpPoint::new(123.0+123.0+123.0, 123.0+123.0+123.0);
         let p3 = VpPoint::new(123.0+123.0, 123.0+123.0+1.0);
-        assert!(point_in_line(&p1, &p2, &p3, 0.1) == false);
+        assert!(!point_in_line(&p1, &p2, &p3, 0.1));
 
         let p1 = VpPoint::new(-123.0, -123.0);
         let p2 = VpPoint::new(-123.0-123.0-123.0, -123.0-123.0-123.0);
@@ -420,7 +484,7 @@ mod tests {
         let p1 = VpPoint::new(-123.0, -123.0);
         let p2 = VpPoint::new(-123.0-123.0-123.0+1.0, -123.0-123.0-123.0);
         let p3 = VpPoint::new(-123.0-123.0, -123.0-123.0);
-        assert!(point_in_line(&p1, &p2, &p3, 0.1) == false);
+        assert!(!point_in_line(&p1, &p2, &p3, 0.1));
     }
 
 }
\ No newline at end of file
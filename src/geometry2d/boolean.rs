@@ -0,0 +1,464 @@
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::geometry2d::{signed_area, Polygon, VpPoint};
+
+/// Boolean operation between two polygons. See [`boolean_operation`]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum BooleanOperation {
+    Union,
+    Intersection,
+    /// The first polygon minus the second polygon
+    Difference,
+    /// The area that is inside exactly one of the polygons
+    Xor,
+}
+
+impl BooleanOperation {
+    fn is_inside(&self, inside_a: bool, inside_b: bool) -> bool {
+        match self {
+            BooleanOperation::Union => inside_a || inside_b,
+            BooleanOperation::Intersection => inside_a && inside_b,
+            BooleanOperation::Difference => inside_a && !inside_b,
+            BooleanOperation::Xor => inside_a != inside_b,
+        }
+    }
+}
+
+/// Calculates the union of the polygons. See [`boolean_operation`]
+pub fn union(a: &Polygon, b: &Polygon, tolerance: f64) -> Vec<Polygon> {
+    boolean_operation(a, b, BooleanOperation::Union, tolerance)
+}
+
+/// Calculates the intersection of the polygons. See [`boolean_operation`]
+pub fn intersection(a: &Polygon, b: &Polygon, tolerance: f64) -> Vec<Polygon> {
+    boolean_operation(a, b, BooleanOperation::Intersection, tolerance)
+}
+
+/// Calculates the polygon `a` minus the polygon `b`. See [`boolean_operation`]
+pub fn difference(a: &Polygon, b: &Polygon, tolerance: f64) -> Vec<Polygon> {
+    boolean_operation(a, b, BooleanOperation::Difference, tolerance)
+}
+
+/// Calculates the area that is inside exactly one of the polygons. See [`boolean_operation`]
+pub fn xor(a: &Polygon, b: &Polygon, tolerance: f64) -> Vec<Polygon> {
+    boolean_operation(a, b, BooleanOperation::Xor, tolerance)
+}
+
+/// Calculates the boolean operation between the polygons. The polygons can be clockwise or
+/// counterclockwise and the last point can be the same as the first point or not. Self
+/// intersecting polygons are handled with the nonzero winding rule.
+///
+/// Points closer than `tolerance` to each other are merged and points closer than `tolerance`
+/// to an edge are moved onto the edge, so collinear edges and touching vertices are handled
+/// even if the coordinates are not exactly the same.
+///
+/// Returns the boundaries of the result as closed polygons (the last point is the same as the
+/// first point). Outer boundaries are counterclockwise and the holes are clockwise. Polygons
+/// that only touch at a vertex are returned as separate polygons.
+pub fn boolean_operation(
+    a: &Polygon,
+    b: &Polygon,
+    operation: BooleanOperation,
+    tolerance: f64,
+) -> Vec<Polygon> {
+    let inside =
+        |winding_a: i32, winding_b: i32| operation.is_inside(winding_a != 0, winding_b != 0);
+    resolve_boundaries(a, b, inside, tolerance)
+}
+
+/// Resolves the self intersections of the polygon with the positive winding rule, so only the
+/// areas that are wound around counterclockwise are inside. Returns the boundaries the same way
+/// as [`boolean_operation`].
+pub(super) fn positive_fill(polygon: &Polygon, tolerance: f64) -> Vec<Polygon> {
+    let inside = |winding: i32, _: i32| winding > 0;
+    resolve_boundaries(polygon, &Polygon::new_empty(), inside, tolerance)
+}
+
+/// Gets the boundaries of the area where `inside` is true for the winding numbers of the
+/// polygons `a` and `b`
+fn resolve_boundaries(
+    a: &Polygon,
+    b: &Polygon,
+    inside: impl Fn(i32, i32) -> bool,
+    tolerance: f64,
+) -> Vec<Polygon> {
+    let mut vertices: Vec<VpPoint> = Vec::new();
+    let edges_a = get_edges(a);
+    let edges_b = get_edges(b);
+    let all_edges: Vec<(VpPoint, VpPoint)> = edges_a.iter().chain(&edges_b).copied().collect();
+    let segments_a = split_edges(&edges_a, &all_edges, &mut vertices, tolerance);
+    let segments_b = split_edges(&edges_b, &all_edges, &mut vertices, tolerance);
+
+    // The unique undirected segments of both polygons
+    let mut unique: BTreeMap<(usize, usize), ()> = BTreeMap::new();
+    for &(i, j) in segments_a.iter().chain(&segments_b) {
+        unique.insert((i.min(j), i.max(j)), ());
+    }
+
+    // Keep the segments that separate the inside of the result from the outside. The inside is
+    // on the left side of the kept segments.
+    let mut kept: Vec<(usize, usize)> = Vec::new();
+    for &(i, j) in unique.keys() {
+        let (left_a, right_a) = winding_on_sides(&vertices, &segments_a, i, j);
+        let (left_b, right_b) = winding_on_sides(&vertices, &segments_b, i, j);
+        let left = inside(left_a, left_b);
+        let right = inside(right_a, right_b);
+        if left && !right {
+            kept.push((i, j));
+        } else if right && !left {
+            kept.push((j, i));
+        }
+    }
+
+    link_segments(&vertices, &kept)
+        .into_iter()
+        .filter_map(|ring| {
+            let mut points: Vec<VpPoint> = ring.iter().map(|&i| vertices[i]).collect();
+            remove_collinear_points(&mut points, tolerance);
+            if points.len() < 3 || signed_area(&points).abs() <= tolerance * tolerance {
+                return None;
+            }
+            points.push(points[0]);
+            Some(Polygon::new(points))
+        })
+        .collect()
+}
+
+/// Gets the edges of the polygon. Zero length edges (repeated points) are skipped.
+fn get_edges(polygon: &Polygon) -> Vec<(VpPoint, VpPoint)> {
+    polygon
+        .edges()
+        .filter(|(p1, p2)| p1 != p2)
+        .map(|(p1, p2)| (*p1, *p2))
+        .collect()
+}
+
+/// Splits the edges at the points where they cross or touch any of the other edges. Returns the
+/// directed segments as indexes to the vertices.
+fn split_edges(
+    edges: &[(VpPoint, VpPoint)],
+    all_edges: &[(VpPoint, VpPoint)],
+    vertices: &mut Vec<VpPoint>,
+    tolerance: f64,
+) -> Vec<(usize, usize)> {
+    let mut segments = Vec::new();
+    for &(p1, p2) in edges {
+        // The split points with their positions along the edge
+        let mut splits = vec![(0.0, p1), (1.0, p2)];
+        for &(q1, q2) in all_edges {
+            for q in [q1, q2] {
+                if let Some(t) = point_on_segment(&p1, &p2, &q, tolerance) {
+                    splits.push((t, q));
+                }
+            }
+            if let Some((t, point)) = segment_crossing(&p1, &p2, &q1, &q2) {
+                splits.push((t, point));
+            }
+        }
+        splits.sort_by(|s1, s2| s1.0.total_cmp(&s2.0));
+        let mut previous = get_vertex(vertices, p1, tolerance);
+        for (_, point) in splits.into_iter().skip(1) {
+            let current = get_vertex(vertices, point, tolerance);
+            if current != previous {
+                segments.push((previous, current));
+                previous = current;
+            }
+        }
+    }
+    segments
+}
+
+/// Gets the index of the vertex that is within the tolerance from the point. The point is added
+/// to the vertices if there's no such vertex.
+fn get_vertex(vertices: &mut Vec<VpPoint>, point: VpPoint, tolerance: f64) -> usize {
+    let existing = vertices
+        .iter()
+        .position(|v| (v.x - point.x).hypot(v.y - point.y) <= tolerance);
+    existing.unwrap_or_else(|| {
+        vertices.push(point);
+        vertices.len() - 1
+    })
+}
+
+/// Returns the position of the point along the segment (0..1) if the point is within the
+/// tolerance from the inside of the segment
+fn point_on_segment(p1: &VpPoint, p2: &VpPoint, point: &VpPoint, tolerance: f64) -> Option<f64> {
+    let (dx, dy) = (p2.x - p1.x, p2.y - p1.y);
+    let length_squared = dx * dx + dy * dy;
+    let t = ((point.x - p1.x) * dx + (point.y - p1.y) * dy) / length_squared;
+    if t <= 0.0 || t >= 1.0 {
+        return None;
+    }
+    let distance = (point.x - (p1.x + t * dx)).hypot(point.y - (p1.y + t * dy));
+    if distance <= tolerance {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// Returns the position along the first segment (0..1) and the point where the segments cross.
+/// Segments that only touch at the end points or are parallel don't cross.
+fn segment_crossing(
+    p1: &VpPoint,
+    p2: &VpPoint,
+    q1: &VpPoint,
+    q2: &VpPoint,
+) -> Option<(f64, VpPoint)> {
+    let (rx, ry) = (p2.x - p1.x, p2.y - p1.y);
+    let (sx, sy) = (q2.x - q1.x, q2.y - q1.y);
+    let denominator = rx * sy - ry * sx;
+    if denominator == 0.0 {
+        return None;
+    }
+    let (qpx, qpy) = (q1.x - p1.x, q1.y - p1.y);
+    let t = (qpx * sy - qpy * sx) / denominator;
+    let u = (qpx * ry - qpy * rx) / denominator;
+    if t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0 {
+        Some((t, VpPoint::new(p1.x + t * rx, p1.y + t * ry)))
+    } else {
+        None
+    }
+}
+
+/// Calculates the winding numbers of the polygon (given as segments) on the left and the right
+/// side of the segment from vertex `i` to vertex `j`. The winding number at the midpoint of the
+/// segment is the sum of the angles the other segments subtend, and the segments on top of the
+/// segment add half a turn to one side.
+fn winding_on_sides(
+    vertices: &[VpPoint],
+    segments: &[(usize, usize)],
+    i: usize,
+    j: usize,
+) -> (i32, i32) {
+    let midpoint = VpPoint::new(
+        (vertices[i].x + vertices[j].x) / 2.0,
+        (vertices[i].y + vertices[j].y) / 2.0,
+    );
+    let mut angle_sum = 0.0;
+    let mut direction = 0.0;
+    for &(s1, s2) in segments {
+        if (s1, s2) == (i, j) {
+            direction += 1.0;
+        } else if (s1, s2) == (j, i) {
+            direction -= 1.0;
+        } else {
+            let (p1, p2) = (&vertices[s1], &vertices[s2]);
+            let (ax, ay) = (p1.x - midpoint.x, p1.y - midpoint.y);
+            let (bx, by) = (p2.x - midpoint.x, p2.y - midpoint.y);
+            angle_sum += (ax * by - ay * bx).atan2(ax * bx + ay * by);
+        }
+    }
+    let left = ((angle_sum + direction * PI) / (2.0 * PI)).round() as i32;
+    let right = ((angle_sum - direction * PI) / (2.0 * PI)).round() as i32;
+    (left, right)
+}
+
+/// Links the directed segments into rings. At the vertices with multiple outgoing segments the
+/// sharpest left turn is taken, so the rings that touch at a vertex stay separate.
+fn link_segments(vertices: &[VpPoint], segments: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut outgoing: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (index, &(i, _)) in segments.iter().enumerate() {
+        outgoing.entry(i).or_default().push(index);
+    }
+    let angle = |from: usize, to: usize| {
+        (vertices[to].y - vertices[from].y).atan2(vertices[to].x - vertices[from].x)
+    };
+    let mut used = vec![false; segments.len()];
+    let mut rings = Vec::new();
+    for first in 0..segments.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let start = segments[first].0;
+        let mut ring = vec![start];
+        let mut current = first;
+        loop {
+            let (from, to) = segments[current];
+            if to == start {
+                rings.push(ring);
+                break;
+            }
+            ring.push(to);
+            // The clockwise angle from the reversed incoming segment to the outgoing segment
+            let reverse = angle(to, from);
+            let next = outgoing.get(&to).and_then(|candidates| {
+                candidates
+                    .iter()
+                    .copied()
+                    .filter(|&s| !used[s])
+                    .min_by(|&s1, &s2| {
+                        let turn =
+                            |s: usize| (reverse - angle(to, segments[s].1)).rem_euclid(2.0 * PI);
+                        turn(s1).total_cmp(&turn(s2))
+                    })
+            });
+            match next {
+                Some(next) => {
+                    used[next] = true;
+                    current = next;
+                }
+                // The segments don't form a closed ring
+                None => break,
+            }
+        }
+    }
+    rings
+}
+
+/// Removes the points that are within the tolerance from the line between their neighbors
+fn remove_collinear_points(points: &mut Vec<VpPoint>, tolerance: f64) {
+    let mut i = 0;
+    while points.len() >= 3 && i < points.len() {
+        let count = points.len();
+        let previous = &points[(i + count - 1) % count];
+        let next = &points[(i + 1) % count];
+        let current = &points[i];
+        let length = (next.x - previous.x).hypot(next.y - previous.y);
+        let cross = (next.x - previous.x) * (current.y - previous.y)
+            - (next.y - previous.y) * (current.x - previous.x);
+        if length == 0.0 || (cross / length).abs() <= tolerance {
+            points.remove(i);
+            // The previous point has to be checked again with its new neighbor
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry2d::test_utils::rectangle;
+    use crate::geometry2d::{calculate_area, polygon::Direction};
+
+    /// Sum of the areas where the holes are negative
+    fn total_area(polygons: &[Polygon]) -> f64 {
+        polygons
+            .iter()
+            .map(|p| calculate_area(p) * p.get_direction() as i32 as f64)
+            .sum()
+    }
+
+    #[test]
+    fn overlapping_rectangles() {
+        let a = rectangle(0.0, 0.0, 2.0, 2.0);
+        let b = rectangle(1.0, 1.0, 2.0, 2.0);
+        let result = union(&a, &b, 1e-9);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].points.len(), 9);
+        assert_eq!(result[0].points.first(), result[0].points.last());
+        assert_eq!(result[0].get_direction(), Direction::CounterClockwise);
+        assert!((total_area(&result) - 7.0).abs() < 1e-12);
+
+        let result = intersection(&a, &b, 1e-9);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].points.len(), 5);
+        assert!((total_area(&result) - 1.0).abs() < 1e-12);
+
+        let result = difference(&a, &b, 1e-9);
+        assert_eq!(result.len(), 1);
+        assert!((total_area(&result) - 3.0).abs() < 1e-12);
+
+        let result = xor(&a, &b, 1e-9);
+        assert_eq!(result.len(), 2);
+        assert!((total_area(&result) - 6.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn collinear_edges_and_touching_vertices() {
+        // Rectangles sharing a part of an edge merge into a single polygon
+        let a = rectangle(0.0, 0.0, 2.0, 1.0);
+        let b = rectangle(1.0, 0.0, 2.0, 1.0);
+        let result = union(&a, &b, 1e-9);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].points.len(), 5);
+        assert!((total_area(&result) - 3.0).abs() < 1e-12);
+
+        // Web and flange of a T-section sharing an edge
+        let flange = rectangle(0.0, 10.0, 10.0, 1.0);
+        let web = rectangle(4.5, 0.0, 1.0, 10.0);
+        let result = union(&flange, &web, 1e-9);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].points.len(), 9);
+        assert!((total_area(&result) - 20.0).abs() < 1e-12);
+        assert!(intersection(&flange, &web, 1e-9).is_empty());
+        assert_eq!(difference(&flange, &web, 1e-9).len(), 1);
+
+        // Rectangles touching at a vertex stay separate
+        let a = rectangle(0.0, 0.0, 1.0, 1.0);
+        let b = rectangle(1.0, 1.0, 1.0, 1.0);
+        let result = union(&a, &b, 1e-9);
+        assert_eq!(result.len(), 2);
+        assert!((total_area(&result) - 2.0).abs() < 1e-12);
+        assert!(intersection(&a, &b, 1e-9).is_empty());
+
+        // Gaps and overlaps within the tolerance are closed
+        let b = rectangle(1.0 + 1e-7, 0.0, 1.0, 1.0 - 1e-7);
+        let result = union(&a, &b, 1e-6);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].points.len(), 5);
+        assert!((total_area(&result) - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn holes_and_directions() {
+        // Clockwise and open polygons are accepted
+        let plate = rectangle(0.0, 0.0, 10.0, 10.0);
+        let mut hole = rectangle(4.0, 4.0, 2.0, 2.0);
+        hole.points.pop();
+        hole.points.reverse();
+        let result = difference(&plate, &hole, 1e-9);
+        assert_eq!(result.len(), 2);
+        let holes: Vec<_> = result
+            .iter()
+            .filter(|p| p.get_direction() == Direction::Clockwise)
+            .collect();
+        assert_eq!(holes.len(), 1);
+        assert!((calculate_area(holes[0]) - 4.0).abs() < 1e-12);
+        assert!((total_area(&result) - 96.0).abs() < 1e-12);
+
+        let result = xor(&plate, &hole, 1e-9);
+        assert!((total_area(&result) - 96.0).abs() < 1e-12);
+        let result = union(&plate, &hole, 1e-9);
+        assert_eq!(result.len(), 1);
+        assert!((total_area(&result) - 100.0).abs() < 1e-12);
+        assert!(difference(&hole, &plate, 1e-9).is_empty());
+
+        // A hole touching the outer boundary makes a notch
+        let notch = rectangle(4.0, 0.0, 2.0, 2.0);
+        let result = difference(&plate, &notch, 1e-9);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].points.len(), 9);
+        assert!((total_area(&result) - 96.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn crossing_polygons() {
+        // Triangles forming a star of David
+        let a = Polygon::new(vec![
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(6.0, 0.0),
+            VpPoint::new(3.0, 6.0),
+        ]);
+        let b = Polygon::new(vec![
+            VpPoint::new(0.0, 4.0),
+            VpPoint::new(3.0, -2.0),
+            VpPoint::new(6.0, 4.0),
+        ]);
+        let union_area = total_area(&union(&a, &b, 1e-9));
+        let intersection_area = total_area(&intersection(&a, &b, 1e-9));
+        let xor_area = total_area(&xor(&a, &b, 1e-9));
+        assert_eq!(intersection(&a, &b, 1e-9)[0].points.len(), 7);
+        assert!((union_area - (18.0 + 18.0 - intersection_area)).abs() < 1e-9);
+        assert!((xor_area - (union_area - intersection_area)).abs() < 1e-9);
+        let difference_area = total_area(&difference(&a, &b, 1e-9));
+        assert!((difference_area - (18.0 - intersection_area)).abs() < 1e-9);
+        assert_eq!(difference(&a, &b, 1e-9).len(), 3);
+    }
+}
@@ -0,0 +1,57 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::geometry2d::{calc_length_between_points, Containment, VpPoint};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Circle {
+    pub center: VpPoint,
+    pub radius: f64,
+}
+
+impl Circle {
+    pub fn new(center: VpPoint, radius: f64) -> Circle {
+        Circle { center, radius }
+    }
+
+    pub fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+
+    /// Checks if the point is inside the circle. The point is on the boundary if its distance
+    /// from the circle is at most `epsilon`.
+    pub fn contains(&self, point: &VpPoint, epsilon: f64) -> Containment {
+        let distance = calc_length_between_points(&self.center, point);
+        if (distance - self.radius).abs() <= epsilon {
+            Containment::OnBoundary
+        } else if distance < self.radius {
+            Containment::Inside
+        } else {
+            Containment::Outside
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn circle() {
+        let circle = Circle::new(VpPoint::new(1.0, 1.0), 2.0);
+        assert_eq!(circle.area(), 4.0 * PI);
+        assert_eq!(
+            circle.contains(&VpPoint::new(2.0, 2.0), 1e-9),
+            Containment::Inside
+        );
+        assert_eq!(
+            circle.contains(&VpPoint::new(3.0, 1.0), 1e-9),
+            Containment::OnBoundary
+        );
+        assert_eq!(
+            circle.contains(&VpPoint::new(3.0, 3.0), 1e-9),
+            Containment::Outside
+        );
+    }
+}
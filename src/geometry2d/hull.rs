@@ -0,0 +1,310 @@
+use crate::geometry2d::{
+    calc_length_between_points, circumcenter, cross, get_angle_from_points, Circle,
+    OrientedRectangle, Polygon, VpPoint,
+};
+use crate::rng::SplitMix64;
+
+/// Calculates the convex hull of the points with Andrew's monotone chain algorithm. Returns a
+/// closed counterclockwise polygon without collinear points. If all the points are on the same
+/// line, the polygon only has the two end points (and the closing point). Returns an empty
+/// polygon if there are no points.
+pub fn convex_hull(points: &[VpPoint]) -> Polygon {
+    let mut sorted: Vec<VpPoint> = points.to_vec();
+    sorted.sort_by(|p1, p2| p1.x.total_cmp(&p2.x).then(p1.y.total_cmp(&p2.y)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        if let Some(first) = sorted.first().copied() {
+            if sorted.len() == 2 {
+                sorted.push(first);
+            }
+        }
+        return Polygon::new(sorted);
+    }
+
+    // The lower hull from left to right and the upper hull from right to left
+    let mut hull: Vec<VpPoint> = Vec::with_capacity(sorted.len() + 1);
+    for point in &sorted {
+        push_hull_point(&mut hull, 0, point);
+    }
+    let lower_end = hull.len() - 1;
+    for point in sorted.iter().rev().skip(1) {
+        push_hull_point(&mut hull, lower_end, point);
+    }
+    // The last point is the same as the first point
+    Polygon::new(hull)
+}
+
+/// Calculates the minimum area rectangle that contains the points with the rotating calipers
+/// over the convex hull. One side of the rectangle is always on an edge of the hull. The angle
+/// of the returned rectangle is in the range 0..90 degrees. Returns None if there are no
+/// points.
+pub fn minimum_bounding_rectangle(points: &[VpPoint]) -> Option<OrientedRectangle> {
+    let hull = open_hull(points);
+    let count = hull.len();
+    match count {
+        0 => return None,
+        1 => return Some(OrientedRectangle::new(hull[0], 0.0, 0.0, 0.0)),
+        _ => {}
+    }
+
+    // The length of the vector from p1 to p2 in the direction
+    let dot =
+        |p1: &VpPoint, p2: &VpPoint, (x, y): (f64, f64)| (p2.x - p1.x) * x + (p2.y - p1.y) * y;
+    let mut best: Option<(f64, OrientedRectangle)> = None;
+    // The points furthest along the edge, furthest from the edge and furthest against the edge
+    let (mut right, mut top, mut left) = (1 % count, 0, 0);
+    for i in 0..count {
+        let (p1, p2) = (&hull[i], &hull[(i + 1) % count]);
+        let length = calc_length_between_points(p1, p2);
+        let u = ((p2.x - p1.x) / length, (p2.y - p1.y) / length);
+        let n = (-u.1, u.0);
+        let next = |index: usize| (index + 1) % count;
+        while dot(&hull[right], &hull[next(right)], u) > 0.0 {
+            right = next(right);
+        }
+        if i == 0 {
+            top = right;
+        }
+        while dot(&hull[top], &hull[next(top)], n) > 0.0 {
+            top = next(top);
+        }
+        if i == 0 {
+            left = top;
+        }
+        while dot(&hull[left], &hull[next(left)], u) < 0.0 {
+            left = next(left);
+        }
+
+        let max_u = dot(p1, &hull[right], u);
+        let min_u = dot(p1, &hull[left], u);
+        let height = dot(p1, &hull[top], n);
+        let width = max_u - min_u;
+        if best
+            .as_ref()
+            .is_some_and(|(area, _)| width * height >= *area)
+        {
+            continue;
+        }
+        let along = (max_u + min_u) / 2.0;
+        let center = VpPoint::new(
+            p1.x + u.0 * along + n.0 * height / 2.0,
+            p1.y + u.1 * along + n.1 * height / 2.0,
+        );
+        let angle = get_angle_from_points(p1, p2);
+        best = Some((
+            width * height,
+            OrientedRectangle::new(center, width, height, angle),
+        ));
+    }
+
+    // Rotating by 90 degrees swaps the width and the height
+    best.map(|(_, mut rectangle)| {
+        let quarters = (rectangle.angle / 90.0).floor();
+        rectangle.angle -= quarters * 90.0;
+        if quarters as i64 % 2 != 0 {
+            std::mem::swap(&mut rectangle.width, &mut rectangle.height);
+        }
+        rectangle
+    })
+}
+
+/// Calculates the smallest circle that contains the points with Welzl's algorithm. Only the
+/// points of the convex hull are used. The hull points are shuffled (with a fixed seed, so the
+/// result is deterministic), which makes the expected running time linear in the number of hull
+/// points instead of cubic for the ordered hull. Returns None if there are no points.
+pub fn minimum_enclosing_circle(points: &[VpPoint]) -> Option<Circle> {
+    let mut hull = open_hull(points);
+    SplitMix64::new(hull.len() as u64).shuffle(&mut hull);
+    let first = *hull.first()?;
+    let contains = |circle: &Circle, point: &VpPoint| {
+        calc_length_between_points(&circle.center, point) <= circle.radius * (1.0 + 1e-12)
+    };
+    let mut circle = Circle::new(first, 0.0);
+    for i in 1..hull.len() {
+        if contains(&circle, &hull[i]) {
+            continue;
+        }
+        // The point i is on the boundary of the circle of the points 0..=i
+        circle = circle_from_diameter(&hull[i], &hull[0]);
+        for j in 1..i {
+            if contains(&circle, &hull[j]) {
+                continue;
+            }
+            circle = circle_from_diameter(&hull[i], &hull[j]);
+            for k in 0..j {
+                if !contains(&circle, &hull[k]) {
+                    circle = circumcircle(&hull[i], &hull[j], &hull[k]);
+                }
+            }
+        }
+    }
+    Some(circle)
+}
+
+/// Gets the convex hull without the closing point
+fn open_hull(points: &[VpPoint]) -> Vec<VpPoint> {
+    let mut hull = convex_hull(points).points;
+    if hull.len() > 1 {
+        hull.pop();
+    }
+    hull
+}
+
+/// Adds the point to the hull after removing the points after `start` that don't turn
+/// counterclockwise
+fn push_hull_point(hull: &mut Vec<VpPoint>, start: usize, point: &VpPoint) {
+    while hull.len() >= start + 2 {
+        let count = hull.len();
+        if cross(&hull[count - 2], &hull[count - 1], point) > 0.0 {
+            break;
+        }
+        hull.pop();
+    }
+    hull.push(*point);
+}
+
+fn circle_from_diameter(p1: &VpPoint, p2: &VpPoint) -> Circle {
+    let center = VpPoint::new((p1.x + p2.x) / 2.0, (p1.y + p2.y) / 2.0);
+    Circle::new(center, calc_length_between_points(p1, p2) / 2.0)
+}
+
+/// The circle through the three points. The points of the convex hull are never collinear.
+fn circumcircle(p1: &VpPoint, p2: &VpPoint, p3: &VpPoint) -> Circle {
+    let center = circumcenter(p1, p2, p3);
+    Circle::new(center, calc_length_between_points(&center, p1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry2d::test_utils::assert_close;
+    use crate::geometry2d::{calculate_area, rotate_point, Containment};
+
+    #[test]
+    fn hull() {
+        let points = vec![
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(2.0, 2.0),
+            VpPoint::new(4.0, 0.0),
+            VpPoint::new(4.0, 4.0),
+            VpPoint::new(0.0, 4.0),
+            VpPoint::new(2.0, 0.0),
+            VpPoint::new(1.0, 3.0),
+            VpPoint::new(4.0, 4.0),
+        ];
+        let hull = convex_hull(&points);
+        assert_eq!(
+            hull.points,
+            vec![
+                VpPoint::new(0.0, 0.0),
+                VpPoint::new(4.0, 0.0),
+                VpPoint::new(4.0, 4.0),
+                VpPoint::new(0.0, 4.0),
+                VpPoint::new(0.0, 0.0),
+            ]
+        );
+        assert_eq!(calculate_area(&hull), 16.0);
+
+        assert!(convex_hull(&[]).points.is_empty());
+        assert_eq!(convex_hull(&[VpPoint::new(1.0, 1.0)]).points.len(), 1);
+        let line = [
+            VpPoint::new(2.0, 2.0),
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(1.0, 1.0),
+        ];
+        assert_eq!(
+            convex_hull(&line).points,
+            vec![
+                VpPoint::new(0.0, 0.0),
+                VpPoint::new(2.0, 2.0),
+                VpPoint::new(0.0, 0.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn bounding_rectangle() {
+        // A rotated 10x4 plate with some points inside
+        let origin = VpPoint::new(0.0, 0.0);
+        let points: Vec<VpPoint> = [(0.0, 0.0), (10.0, 0.0), (10.0, 4.0), (0.0, 4.0), (5.0, 2.0)]
+            .iter()
+            .map(|&(x, y)| rotate_point(&origin, &VpPoint::new(x + 3.0, y + 1.0), 120.0))
+            .collect();
+        let rectangle = minimum_bounding_rectangle(&points).unwrap();
+        assert_close(rectangle.area(), 40.0);
+        // The long side is at 120 degrees, which is 30 degrees with the width and height swapped
+        assert_close(rectangle.angle, 30.0);
+        assert_close(rectangle.width, 4.0);
+        assert_close(rectangle.height, 10.0);
+        let center = rotate_point(&origin, &VpPoint::new(8.0, 3.0), 120.0);
+        assert_close(rectangle.center.x, center.x);
+        assert_close(rectangle.center.y, center.y);
+
+        // A triangle touches the rectangle with one side
+        let triangle = [
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(4.0, 0.0),
+            VpPoint::new(1.0, 3.0),
+        ];
+        let rectangle = minimum_bounding_rectangle(&triangle).unwrap();
+        assert_close(rectangle.area(), 12.0);
+        let corners = rectangle.to_polygon();
+        for point in &triangle {
+            assert_ne!(corners.contains(point, 1e-9), Containment::Outside);
+        }
+
+        let segment = [VpPoint::new(0.0, 0.0), VpPoint::new(-3.0, 4.0)];
+        let rectangle = minimum_bounding_rectangle(&segment).unwrap();
+        assert_close(rectangle.width + rectangle.height, 5.0);
+        assert_close(rectangle.area(), 0.0);
+        assert!(minimum_bounding_rectangle(&[]).is_none());
+    }
+
+    #[test]
+    fn enclosing_circle() {
+        // The diameter is the longest distance
+        let points = [
+            VpPoint::new(-5.0, 0.0),
+            VpPoint::new(5.0, 0.0),
+            VpPoint::new(0.0, 1.0),
+            VpPoint::new(1.0, -2.0),
+        ];
+        let circle = minimum_enclosing_circle(&points).unwrap();
+        assert_close(circle.center.x, 0.0);
+        assert_close(circle.center.y, 0.0);
+        assert_close(circle.radius, 5.0);
+
+        // Circumcircle of an equilateral triangle
+        let height = 3.0_f64.sqrt() / 2.0;
+        let triangle = [
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(1.0, 0.0),
+            VpPoint::new(0.5, height),
+        ];
+        let circle = minimum_enclosing_circle(&triangle).unwrap();
+        assert_close(circle.radius, 1.0 / 3.0_f64.sqrt());
+        assert_close(circle.center.y, height / 3.0);
+
+        let points: Vec<VpPoint> = (0..50)
+            .map(|i| VpPoint::new((i as f64 * 1.7).sin() * 10.0, (i as f64 * 0.9).cos() * 3.0))
+            .collect();
+        let circle = minimum_enclosing_circle(&points).unwrap();
+        for point in &points {
+            assert_ne!(circle.contains(point, 1e-9), Containment::Outside);
+        }
+        // All the points are on the hull in order
+        let angles = (0..20000).map(|i| i as f64 * std::f64::consts::TAU / 20000.0);
+        let points: Vec<VpPoint> = angles
+            .map(|a| VpPoint::new(7.0 * a.cos(), 7.0 * a.sin()))
+            .collect();
+        let circle = minimum_enclosing_circle(&points).unwrap();
+        assert!((circle.radius - 7.0).abs() < 1e-9 && circle.center.x.abs() < 1e-9);
+        assert_eq!(
+            minimum_enclosing_circle(&[VpPoint::new(1.0, 2.0)])
+                .unwrap()
+                .radius,
+            0.0
+        );
+        assert!(minimum_enclosing_circle(&[]).is_none());
+    }
+}
@@ -0,0 +1,307 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry2d::{calc_length_between_points, Polygon, VpPoint};
+
+/// A line segment between two points. The queries that take an `epsilon` use it as a distance
+/// tolerance like [`point_in_line`](crate::geometry2d::point_in_line). The `*_line` variants
+/// treat the segment as an infinite line through the points.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineSegment {
+    pub start: VpPoint,
+    pub end: VpPoint,
+}
+
+/// Result of the segment–segment intersection. See [`LineSegment::intersection`]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SegmentIntersection {
+    None,
+    Point(VpPoint),
+    /// The segments are collinear and share the given part
+    Overlap(LineSegment),
+}
+
+impl LineSegment {
+    pub fn new(start: VpPoint, end: VpPoint) -> LineSegment {
+        LineSegment { start, end }
+    }
+
+    pub fn new_xy(x1: f64, y1: f64, x2: f64, y2: f64) -> LineSegment {
+        LineSegment::new(VpPoint::new(x1, y1), VpPoint::new(x2, y2))
+    }
+
+    pub fn length(&self) -> f64 {
+        calc_length_between_points(&self.start, &self.end)
+    }
+
+    /// Gets the point at the parameter `t`. 0.0 is the start point and 1.0 is the end point.
+    /// Values outside 0..1 are on the line outside the segment.
+    pub fn point_at(&self, t: f64) -> VpPoint {
+        VpPoint::new(
+            self.start.x + t * (self.end.x - self.start.x),
+            self.start.y + t * (self.end.y - self.start.y),
+        )
+    }
+
+    /// Gets the parameter of the point projected to the line (see [`LineSegment::point_at`]).
+    /// Returns 0.0 if the segment has zero length.
+    pub fn project(&self, point: &VpPoint) -> f64 {
+        let (dx, dy) = (self.end.x - self.start.x, self.end.y - self.start.y);
+        let length_squared = dx * dx + dy * dy;
+        if length_squared == 0.0 {
+            return 0.0;
+        }
+        ((point.x - self.start.x) * dx + (point.y - self.start.y) * dy) / length_squared
+    }
+
+    /// Gets the point on the segment that is closest to the given point
+    pub fn closest_point(&self, point: &VpPoint) -> VpPoint {
+        self.point_at(self.project(point).clamp(0.0, 1.0))
+    }
+
+    /// Gets the point on the infinite line that is closest to the given point
+    pub fn closest_point_on_line(&self, point: &VpPoint) -> VpPoint {
+        self.point_at(self.project(point))
+    }
+
+    /// Calculates the distance from the point to the segment
+    pub fn distance_to_point(&self, point: &VpPoint) -> f64 {
+        calc_length_between_points(&self.closest_point(point), point)
+    }
+
+    /// Calculates the distance from the point to the infinite line
+    pub fn distance_to_line(&self, point: &VpPoint) -> f64 {
+        calc_length_between_points(&self.closest_point_on_line(point), point)
+    }
+
+    /// Checks if the point is on the segment. Like
+    /// [`point_in_line`](crate::geometry2d::point_in_line), the point must be within `epsilon`
+    /// from the line and between the end points along the line.
+    pub fn contains(&self, point: &VpPoint, epsilon: f64) -> bool {
+        (0.0..=1.0).contains(&self.project(point)) && self.distance_to_line(point) <= epsilon
+    }
+
+    /// Checks if the point is within `epsilon` from the segment. Unlike
+    /// [`LineSegment::contains`], the points within `epsilon` from the ends are accepted too.
+    pub fn is_near(&self, point: &VpPoint, epsilon: f64) -> bool {
+        self.distance_to_point(point) <= epsilon
+    }
+
+    /// Calculates the intersection of the segments. The segments are collinear if the end
+    /// points of the other segment are within `epsilon` from the line of this segment, and
+    /// the segments touch if an end point is within `epsilon` from the other segment. A shared
+    /// part shorter than `epsilon` is a single point.
+    pub fn intersection(&self, other: &LineSegment, epsilon: f64) -> SegmentIntersection {
+        if self.length() <= epsilon {
+            return match other.is_near(&self.start, epsilon) {
+                true => SegmentIntersection::Point(self.start),
+                false => SegmentIntersection::None,
+            };
+        }
+        let collinear = self.distance_to_line(&other.start) <= epsilon
+            && self.distance_to_line(&other.end) <= epsilon;
+        if collinear {
+            // The shared part as distances along this segment
+            let length = self.length();
+            let t1 = self.project(&other.start) * length;
+            let t2 = self.project(&other.end) * length;
+            let low = t1.min(t2).max(0.0);
+            let high = t1.max(t2).min(length);
+            return if high - low > epsilon {
+                let overlap =
+                    LineSegment::new(self.point_at(low / length), self.point_at(high / length));
+                SegmentIntersection::Overlap(overlap)
+            } else if high - low >= -epsilon {
+                SegmentIntersection::Point(self.point_at((low + high) / 2.0 / length))
+            } else {
+                SegmentIntersection::None
+            };
+        }
+        if let Some(point) = self.line_intersection(other, 0.0) {
+            if self.is_near(&point, epsilon) && other.is_near(&point, epsilon) {
+                return SegmentIntersection::Point(point);
+            }
+        }
+        // Nearly parallel segments can touch far from the intersection of the lines
+        for (segment, point) in [
+            (other, &self.start),
+            (other, &self.end),
+            (self, &other.start),
+            (self, &other.end),
+        ] {
+            if segment.is_near(point, epsilon) {
+                return SegmentIntersection::Point(*point);
+            }
+        }
+        SegmentIntersection::None
+    }
+
+    /// Calculates the intersection of the infinite lines. Returns None if the lines are
+    /// parallel, which means that the distances from the end points of the other segment to
+    /// this line differ at most by `epsilon`.
+    pub fn line_intersection(&self, other: &LineSegment, epsilon: f64) -> Option<VpPoint> {
+        let (rx, ry) = (self.end.x - self.start.x, self.end.y - self.start.y);
+        let (sx, sy) = (other.end.x - other.start.x, other.end.y - other.start.y);
+        let denominator = rx * sy - ry * sx;
+        let length = self.length();
+        // The change of the signed distance to this line along the other segment
+        if length == 0.0 || (denominator / length).abs() <= epsilon {
+            return None;
+        }
+        let (qpx, qpy) = (other.start.x - self.start.x, other.start.y - self.start.y);
+        let t = (qpx * sy - qpy * sx) / denominator;
+        Some(self.point_at(t))
+    }
+}
+
+impl Polygon {
+    /// Gets the edges of the polygon as segments (see [`Polygon::edges`]). The zero length edges
+    /// are skipped.
+    pub fn get_segments(&self) -> Vec<LineSegment> {
+        self.edges()
+            .map(|(p1, p2)| LineSegment::new(*p1, *p2))
+            .filter(|s| s.start != s.end)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry2d::point_in_line;
+
+    #[test]
+    fn point_queries() {
+        let segment = LineSegment::new_xy(0.0, 0.0, 10.0, 0.0);
+        assert_eq!(segment.length(), 10.0);
+        assert_eq!(segment.point_at(0.25), VpPoint::new(2.5, 0.0));
+        assert_eq!(segment.point_at(1.5), VpPoint::new(15.0, 0.0));
+        assert_eq!(segment.project(&VpPoint::new(5.0, 3.0)), 0.5);
+        assert_eq!(segment.project(&VpPoint::new(-5.0, 3.0)), -0.5);
+        assert_eq!(
+            segment.closest_point(&VpPoint::new(5.0, 3.0)),
+            VpPoint::new(5.0, 0.0)
+        );
+        assert_eq!(
+            segment.closest_point(&VpPoint::new(-5.0, 3.0)),
+            VpPoint::new(0.0, 0.0)
+        );
+        let point = VpPoint::new(-5.0, 3.0);
+        assert_eq!(
+            segment.closest_point_on_line(&point),
+            VpPoint::new(-5.0, 0.0)
+        );
+        assert_eq!(segment.distance_to_point(&VpPoint::new(13.0, 4.0)), 5.0);
+        assert_eq!(segment.distance_to_line(&VpPoint::new(13.0, 4.0)), 4.0);
+        assert!(segment.contains(&VpPoint::new(5.0, 0.05), 0.1));
+        assert!(!segment.contains(&VpPoint::new(10.05, 0.0), 0.1));
+        assert!(!segment.contains(&VpPoint::new(5.0, 0.2), 0.1));
+        assert!(segment.is_near(&VpPoint::new(10.05, 0.0), 0.1));
+        assert!(!segment.is_near(&VpPoint::new(5.0, 0.2), 0.1));
+        // Same as point_in_line
+        for point in [
+            (5.0, 0.05),
+            (10.05, 0.0),
+            (-0.05, 0.0),
+            (0.0, -0.1),
+            (10.0, 0.2),
+        ] {
+            let point = VpPoint::new(point.0, point.1);
+            assert_eq!(
+                segment.contains(&point, 0.1),
+                point_in_line(&segment.start, &segment.end, &point, 0.1),
+                "{point:?}"
+            );
+        }
+
+        let diagonal = LineSegment::new_xy(1.0, 1.0, 4.0, 5.0);
+        assert_eq!(diagonal.length(), 5.0);
+        assert!((diagonal.distance_to_point(&VpPoint::new(5.0, 2.0)) - 2.6).abs() < 1e-12);
+        let point = LineSegment::new_xy(1.0, 1.0, 1.0, 1.0);
+        assert_eq!(
+            point.closest_point(&VpPoint::new(5.0, 5.0)),
+            VpPoint::new(1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn intersections() {
+        let segment = LineSegment::new_xy(0.0, 0.0, 10.0, 0.0);
+        let intersection =
+            |x1, y1, x2, y2| segment.intersection(&LineSegment::new_xy(x1, y1, x2, y2), 1e-6);
+        assert_eq!(
+            intersection(5.0, -5.0, 5.0, 5.0),
+            SegmentIntersection::Point(VpPoint::new(5.0, 0.0))
+        );
+        assert_eq!(intersection(5.0, 1.0, 5.0, 5.0), SegmentIntersection::None);
+        assert_eq!(
+            intersection(15.0, -5.0, 15.0, 5.0),
+            SegmentIntersection::None
+        );
+        // Touching at the end points and within the epsilon
+        assert_eq!(
+            intersection(10.0, 0.0, 20.0, 5.0),
+            SegmentIntersection::Point(VpPoint::new(10.0, 0.0))
+        );
+        assert_eq!(
+            intersection(5.0, 1e-7, 5.0, 5.0),
+            SegmentIntersection::Point(VpPoint::new(5.0, 0.0))
+        );
+        // Collinear overlap in both directions
+        assert_eq!(
+            intersection(12.0, 0.0, 5.0, 0.0),
+            SegmentIntersection::Overlap(LineSegment::new_xy(5.0, 0.0, 10.0, 0.0))
+        );
+        assert_eq!(
+            intersection(-5.0, 1e-7, 15.0, -1e-7),
+            SegmentIntersection::Overlap(segment)
+        );
+        assert_eq!(
+            intersection(10.0, 0.0, 15.0, 0.0),
+            SegmentIntersection::Point(VpPoint::new(10.0, 0.0))
+        );
+        assert_eq!(
+            intersection(11.0, 0.0, 15.0, 0.0),
+            SegmentIntersection::None
+        );
+        assert_eq!(intersection(0.0, 1.0, 10.0, 1.0), SegmentIntersection::None);
+        // Nearly parallel segment touching at its end
+        assert_eq!(
+            intersection(5.0, 0.0, 10000.0, 1e-3),
+            SegmentIntersection::Point(VpPoint::new(5.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn line_intersections() {
+        let segment = LineSegment::new_xy(0.0, 0.0, 1.0, 1.0);
+        let other = LineSegment::new_xy(5.0, 0.0, 6.0, -1.0);
+        assert_eq!(
+            segment.line_intersection(&other, 1e-9),
+            Some(VpPoint::new(2.5, 2.5))
+        );
+        // The segments don't intersect but the lines do
+        assert_eq!(
+            segment.intersection(&other, 1e-9),
+            SegmentIntersection::None
+        );
+        let parallel = LineSegment::new_xy(0.0, 1.0, 1.0, 2.0 + 1e-12);
+        assert_eq!(segment.line_intersection(&parallel, 1e-9), None);
+        assert!(segment.line_intersection(&parallel, 0.0).is_some());
+    }
+
+    #[test]
+    fn polygon_segments() {
+        let open = Polygon::new(vec![
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(1.0, 0.0),
+            VpPoint::new(1.0, 1.0),
+        ]);
+        let mut closed = open.clone();
+        closed.points.push(VpPoint::new(0.0, 0.0));
+        assert_eq!(open.get_segments(), closed.get_segments());
+        assert_eq!(
+            open.get_segments()[2],
+            LineSegment::new_xy(1.0, 1.0, 0.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry2d::polygon::{Containment, Direction};
+use crate::geometry2d::{
+    calculate_area, winding_number, Polygon, PolygonWithHoles, Rectangle, VpPoint,
+};
+
+/// Multiple separate polygons with holes (e.g. a cross-section made of separate parts)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MultiPolygon {
+    pub polygons: Vec<PolygonWithHoles>,
+}
+
+impl MultiPolygon {
+    pub fn new(polygons: Vec<PolygonWithHoles>) -> MultiPolygon {
+        MultiPolygon { polygons }
+    }
+
+    /// Creates the multi-polygon from the boundaries returned by the boolean operations (see
+    /// [`boolean_operation`](crate::geometry2d::boolean::boolean_operation)). Counterclockwise
+    /// polygons are the outer polygons and the clockwise polygons are the holes. Each hole is
+    /// added to the smallest outer polygon that contains it. Holes that are not inside any
+    /// outer polygon are dropped.
+    pub fn from_rings(rings: Vec<Polygon>) -> MultiPolygon {
+        let (outers, holes): (Vec<Polygon>, Vec<Polygon>) = rings
+            .into_iter()
+            .partition(|p| p.get_direction() == Direction::CounterClockwise);
+        let mut polygons: Vec<PolygonWithHoles> = outers.into_iter().map(|p| p.into()).collect();
+        for hole in holes {
+            let Some(point) = edge_midpoint(&hole) else {
+                continue;
+            };
+            let outer = polygons
+                .iter_mut()
+                .filter(|p| winding_number(&p.outer.points, &point) != 0)
+                .min_by(|p1, p2| calculate_area(&p1.outer).total_cmp(&calculate_area(&p2.outer)));
+            if let Some(outer) = outer {
+                outer.holes.push(hole);
+            }
+        }
+        MultiPolygon { polygons }
+    }
+
+    /// Calculates the total area of the polygons with the holes subtracted
+    pub fn calculate_area(&self) -> f64 {
+        self.polygons.iter().map(|p| p.calculate_area()).sum()
+    }
+
+    /// Calculates the centroid of all the polygons from current origo
+    pub fn centroid(&self) -> VpPoint {
+        let (mut area, mut x, mut y) = (0.0, 0.0, 0.0);
+        for polygon in &self.polygons {
+            let polygon_area = polygon.calculate_area();
+            let centroid = polygon.centroid();
+            area += polygon_area;
+            x += centroid.x * polygon_area;
+            y += centroid.y * polygon_area;
+        }
+        VpPoint::new(x / area, y / area)
+    }
+
+    /// Gets the bounding box containing all the polygons. Returns None if there are no points.
+    pub fn bounding_box(&self) -> Option<Rectangle> {
+        let boxes: Vec<Rectangle> = self
+            .polygons
+            .iter()
+            .filter_map(|p| p.bounding_box())
+            .collect();
+        let min_x = boxes.iter().map(|b| b.origin.x).reduce(f64::min)?;
+        let min_y = boxes.iter().map(|b| b.origin.y).reduce(f64::min)?;
+        let max_x = boxes
+            .iter()
+            .map(|b| b.origin.x + b.width)
+            .reduce(f64::max)?;
+        let max_y = boxes
+            .iter()
+            .map(|b| b.origin.y + b.height)
+            .reduce(f64::max)?;
+        Some(Rectangle::new(min_x, min_y, max_x - min_x, max_y - min_y))
+    }
+
+    /// Sets the outer polygons counterclockwise and the holes clockwise
+    pub fn normalize_directions(&mut self) {
+        for polygon in &mut self.polygons {
+            polygon.normalize_directions();
+        }
+    }
+
+    /// Checks if the point is inside any of the polygons. See [`Polygon::contains`]
+    pub fn contains(&self, point: &VpPoint, epsilon: f64) -> Containment {
+        let mut result = Containment::Outside;
+        for polygon in &self.polygons {
+            match polygon.contains(point, epsilon) {
+                Containment::Inside => return Containment::Inside,
+                Containment::OnBoundary => result = Containment::OnBoundary,
+                Containment::Outside => {}
+            }
+        }
+        result
+    }
+}
+
+/// Gets the midpoint of the first edge of the polygon
+fn edge_midpoint(polygon: &Polygon) -> Option<VpPoint> {
+    let p1 = polygon.points.first()?;
+    let p2 = polygon.points.iter().find(|p| *p != p1)?;
+    Some(VpPoint::new((p1.x + p2.x) / 2.0, (p1.y + p2.y) / 2.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry2d::boolean;
+    use crate::geometry2d::test_utils::rectangle;
+
+    #[test]
+    fn multi_polygon() {
+        // Two separate plates, one with a hole
+        let multi = MultiPolygon::new(vec![
+            PolygonWithHoles::new(
+                rectangle(0.0, 0.0, 10.0, 10.0),
+                vec![rectangle(4.0, 4.0, 2.0, 2.0)],
+            ),
+            rectangle(20.0, 0.0, 10.0, 4.0).into(),
+        ]);
+        assert_eq!(multi.calculate_area(), 96.0 + 40.0);
+        let centroid = multi.centroid();
+        assert!((centroid.x - (96.0 * 5.0 + 40.0 * 25.0) / 136.0).abs() < 1e-12);
+        assert!((centroid.y - (96.0 * 5.0 + 40.0 * 2.0) / 136.0).abs() < 1e-12);
+        let bb = multi.bounding_box().unwrap();
+        assert_eq!(
+            (bb.origin.x, bb.origin.y, bb.width, bb.height),
+            (0.0, 0.0, 30.0, 10.0)
+        );
+        assert!(MultiPolygon::default().bounding_box().is_none());
+        assert_eq!(
+            multi.contains(&VpPoint::new(25.0, 2.0), 1e-9),
+            Containment::Inside
+        );
+        assert_eq!(
+            multi.contains(&VpPoint::new(5.0, 5.0), 1e-9),
+            Containment::Outside
+        );
+        assert_eq!(
+            multi.contains(&VpPoint::new(4.0, 5.0), 1e-9),
+            Containment::OnBoundary
+        );
+        assert_eq!(
+            multi.contains(&VpPoint::new(15.0, 2.0), 1e-9),
+            Containment::Outside
+        );
+
+        let serialized = serde_json::to_string(&multi).unwrap();
+        let deserialized: MultiPolygon = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.calculate_area(), multi.calculate_area());
+    }
+
+    #[test]
+    fn from_rings() {
+        // Plates with holes cut out with the boolean operations
+        let plate = rectangle(0.0, 0.0, 100.0, 50.0);
+        let mut rings = boolean::difference(&plate, &rectangle(10.0, 10.0, 10.0, 10.0), 1e-9);
+        rings.extend(boolean::difference(
+            &rectangle(200.0, 0.0, 100.0, 100.0),
+            &rectangle(220.0, 20.0, 60.0, 60.0),
+            1e-9,
+        ));
+        // An island inside the hole is a separate polygon
+        rings.push(rectangle(240.0, 40.0, 20.0, 20.0));
+        rings.extend(rectangle(400.0, 0.0, 1.0, 1.0).xor(&rectangle(400.2, 0.2, 0.5, 0.5), 1e-9));
+        let multi = MultiPolygon::from_rings(rings);
+        assert_eq!(multi.polygons.len(), 4);
+        assert_eq!(
+            multi.polygons.iter().map(|p| p.holes.len()).sum::<usize>(),
+            3
+        );
+        let expected = 4900.0 + 6400.0 + 400.0 + 0.75;
+        assert!((multi.calculate_area() - expected).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,262 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::geometry2d::boolean::positive_fill;
+use crate::geometry2d::polygon::Direction;
+use crate::geometry2d::{rotate_point, Polygon, VpPoint};
+
+/// How the offset edges are joined at the corners where they move apart. See [`offset`]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum JoinType {
+    /// The offset edges are extended until they meet. Sharp corners where the miter would be
+    /// longer than the miter limit are beveled.
+    Miter,
+    /// The corner is rounded with an arc around the original vertex. The arc is approximated
+    /// with line segments that are at most `tolerance` from the true arc.
+    Round { tolerance: f64 },
+    /// The ends of the offset edges are connected with a straight line
+    Bevel,
+}
+
+/// Offsets the polygon by the distance. Positive distance grows the polygon outwards and
+/// negative distance shrinks it inwards, regardless of the direction of the polygon. The
+/// polygon can be closed or open.
+///
+/// `miter_limit` is the maximum distance of a miter corner from the original vertex as a
+/// multiple of the offset distance (e.g. 2.0). It's only used with [`JoinType::Miter`].
+///
+/// The parts that collapse when shrinking (e.g. narrow parts of the polygon) are removed, so
+/// the result can be empty or split into several polygons. Returns the boundaries the same way
+/// as the boolean operations: closed polygons with the outer boundaries counterclockwise and
+/// the holes clockwise (see [`MultiPolygon::from_rings`]).
+///
+/// [`MultiPolygon::from_rings`]: crate::geometry2d::MultiPolygon::from_rings
+pub fn offset(
+    polygon: &Polygon,
+    distance: f64,
+    join_type: JoinType,
+    miter_limit: f64,
+) -> Vec<Polygon> {
+    let mut points: Vec<VpPoint> = Vec::new();
+    for point in &polygon.points {
+        if points.last() != Some(point) {
+            points.push(*point);
+        }
+    }
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    if points.len() < 3 {
+        return Vec::new();
+    }
+    // The offset is calculated for a counterclockwise polygon, so the outward normals are on the
+    // right side of the edges
+    if polygon.get_direction() == Direction::Clockwise {
+        points.reverse();
+    }
+
+    let count = points.len();
+    let normals: Vec<(f64, f64)> = (0..count)
+        .map(|i| {
+            let (p1, p2) = (&points[i], &points[(i + 1) % count]);
+            let length = (p2.x - p1.x).hypot(p2.y - p1.y);
+            ((p2.y - p1.y) / length, -(p2.x - p1.x) / length)
+        })
+        .collect();
+    let size = points
+        .iter()
+        .map(|p| p.x.abs().max(p.y.abs()))
+        .fold(distance.abs(), f64::max);
+    let tolerance = size * 1e-9;
+
+    let mut raw: Vec<VpPoint> = Vec::new();
+    for i in 0..count {
+        let vertex = &points[i];
+        let n1 = normals[(i + count - 1) % count];
+        let n2 = normals[i];
+        let start = VpPoint::new(vertex.x + distance * n1.0, vertex.y + distance * n1.1);
+        let end = VpPoint::new(vertex.x + distance * n2.0, vertex.y + distance * n2.1);
+        let cross = n1.0 * n2.1 - n1.1 * n2.0;
+        let dot = n1.0 * n2.0 + n1.1 * n2.1;
+        if cross.abs() <= 1e-12 && dot > 0.0 {
+            // Collinear edges
+            raw.push(start);
+        } else if cross * distance > 0.0 || dot < 0.0 && cross.abs() <= 1e-12 {
+            // The offset edges move apart (or the polygon turns back at a spike)
+            raw.push(start);
+            add_join(&mut raw, vertex, n1, n2, distance, join_type, miter_limit);
+            raw.push(end);
+        } else {
+            // The offset edges cross each other. The loop through the vertex is wound clockwise
+            // and is removed with the other inverted parts.
+            raw.push(start);
+            raw.push(*vertex);
+            raw.push(end);
+        }
+    }
+    positive_fill(&Polygon::new(raw), tolerance)
+}
+
+/// Adds the points between the ends of the offset edges around the vertex
+fn add_join(
+    raw: &mut Vec<VpPoint>,
+    vertex: &VpPoint,
+    n1: (f64, f64),
+    n2: (f64, f64),
+    distance: f64,
+    join_type: JoinType,
+    miter_limit: f64,
+) {
+    let cos = n1.0 * n2.0 + n1.1 * n2.1;
+    match join_type {
+        JoinType::Bevel => {}
+        JoinType::Miter => {
+            // The miter is 1 / cos(angle / 2) times the distance from the vertex
+            if 1.0 + cos >= 2.0 / (miter_limit * miter_limit) {
+                let scale = distance / (1.0 + cos);
+                raw.push(VpPoint::new(
+                    vertex.x + scale * (n1.0 + n2.0),
+                    vertex.y + scale * (n1.1 + n2.1),
+                ));
+            }
+        }
+        JoinType::Round { tolerance } => {
+            let sin = n1.0 * n2.1 - n1.1 * n2.0;
+            let mut angle = sin.atan2(cos);
+            if sin.abs() <= 1e-12 {
+                // Around the tip of the spike
+                angle = PI * distance.signum();
+            }
+            let radius = distance.abs();
+            let step = if tolerance > 0.0 && tolerance < radius {
+                2.0 * (1.0 - tolerance / radius).acos()
+            } else {
+                PI / 2.0
+            };
+            let steps = (angle.abs() / step).ceil().max(1.0) as usize;
+            let start = VpPoint::new(vertex.x + distance * n1.0, vertex.y + distance * n1.1);
+            for k in 1..steps {
+                let rotation = (angle * k as f64 / steps as f64).to_degrees();
+                raw.push(rotate_point(vertex, &start, rotation));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry2d::test_utils::{assert_close, assert_within, rectangle};
+    use crate::geometry2d::{calculate_area, MultiPolygon};
+
+    #[test]
+    fn outward_joins() {
+        let square = rectangle(0.0, 0.0, 10.0, 10.0);
+        let miter = offset(&square, 1.0, JoinType::Miter, 2.0);
+        assert_eq!(miter.len(), 1);
+        assert_close(calculate_area(&miter[0]), 144.0);
+        assert!(miter[0].points.contains(&VpPoint::new(11.0, 11.0)));
+
+        // The miter of a square corner is sqrt(2) times the distance
+        let beveled = offset(&square, 1.0, JoinType::Miter, 1.4);
+        assert_close(calculate_area(&beveled[0]), 144.0 - 4.0 * 0.5);
+        let bevel = offset(&square, 1.0, JoinType::Bevel, 2.0);
+        assert_close(calculate_area(&bevel[0]), 142.0);
+
+        let round = offset(&square, 1.0, JoinType::Round { tolerance: 1e-4 }, 2.0);
+        let expected = 100.0 + 40.0 + PI;
+        assert_within(calculate_area(&round[0]), expected, 1e-3);
+        for point in &round[0].points {
+            let dx = (point.x - 5.0).abs() - 5.0;
+            let dy = (point.y - 5.0).abs() - 5.0;
+            assert_close(dx.max(0.0).hypot(dy.max(0.0)).max(dx.max(dy)), 1.0);
+        }
+        let coarse = offset(&square, 1.0, JoinType::Round { tolerance: 0.1 }, 2.0);
+        assert!(coarse[0].points.len() < round[0].points.len());
+    }
+
+    #[test]
+    fn directions() {
+        // Clockwise and open polygons are offset the same way
+        let mut clockwise = rectangle(0.0, 0.0, 10.0, 10.0);
+        clockwise.points.reverse();
+        let grown = offset(&clockwise, 1.0, JoinType::Miter, 2.0);
+        assert_close(calculate_area(&grown[0]), 144.0);
+        clockwise.points.pop();
+        let shrunk = offset(&clockwise, -1.0, JoinType::Miter, 2.0);
+        assert_eq!(shrunk.len(), 1);
+        assert_close(calculate_area(&shrunk[0]), 64.0);
+    }
+
+    #[test]
+    fn inward_offset_collapses() {
+        // Concrete cover of an L-shaped section
+        let l_shape = Polygon::new(vec![
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(100.0, 0.0),
+            VpPoint::new(100.0, 20.0),
+            VpPoint::new(20.0, 20.0),
+            VpPoint::new(20.0, 100.0),
+            VpPoint::new(0.0, 100.0),
+            VpPoint::new(0.0, 0.0),
+        ]);
+        let cover = offset(&l_shape, -5.0, JoinType::Miter, 2.0);
+        assert_eq!(cover.len(), 1);
+        assert_close(calculate_area(&cover[0]), 90.0 * 10.0 + 80.0 * 10.0);
+        assert!(offset(&l_shape, -12.0, JoinType::Miter, 2.0).is_empty());
+        assert!(offset(&rectangle(0.0, 0.0, 10.0, 4.0), -3.0, JoinType::Bevel, 2.0).is_empty());
+
+        // A dumbbell splits into two parts when the handle collapses
+        let dumbbell = Polygon::new(vec![
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(10.0, 0.0),
+            VpPoint::new(10.0, 4.0),
+            VpPoint::new(20.0, 4.0),
+            VpPoint::new(20.0, 0.0),
+            VpPoint::new(30.0, 0.0),
+            VpPoint::new(30.0, 10.0),
+            VpPoint::new(20.0, 10.0),
+            VpPoint::new(20.0, 6.0),
+            VpPoint::new(10.0, 6.0),
+            VpPoint::new(10.0, 10.0),
+            VpPoint::new(0.0, 10.0),
+        ]);
+        let parts = offset(&dumbbell, -2.0, JoinType::Miter, 2.0);
+        assert_eq!(parts.len(), 2);
+        for part in &parts {
+            assert_close(calculate_area(part), 36.0);
+        }
+        // The round joins at the inner corners of the handle leave a small bump on the parts
+        let parts = offset(&dumbbell, -2.0, JoinType::Round { tolerance: 0.01 }, 2.0);
+        assert_eq!(parts.len(), 2);
+        for part in &parts {
+            let area = calculate_area(part);
+            assert!(area > 36.0 && area < 37.0, "{area}");
+        }
+    }
+
+    #[test]
+    fn outward_offset_closes_gap() {
+        // The gap of the C-shape is closed and the inside becomes a hole
+        let c_shape = Polygon::new(vec![
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(30.0, 0.0),
+            VpPoint::new(30.0, 30.0),
+            VpPoint::new(0.0, 30.0),
+            VpPoint::new(0.0, 18.0),
+            VpPoint::new(10.0, 18.0),
+            VpPoint::new(10.0, 20.0),
+            VpPoint::new(20.0, 20.0),
+            VpPoint::new(20.0, 10.0),
+            VpPoint::new(10.0, 10.0),
+            VpPoint::new(10.0, 12.0),
+            VpPoint::new(0.0, 12.0),
+        ]);
+        let rings = offset(&c_shape, 4.0, JoinType::Miter, 2.0);
+        let multi = MultiPolygon::from_rings(rings);
+        assert_eq!(multi.polygons.len(), 1);
+        assert_eq!(multi.polygons[0].holes.len(), 1);
+        assert_close(calculate_area(&multi.polygons[0].holes[0]), 4.0);
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry2d::{rotate_point, Polygon, VpPoint};
+
+/// A rectangle rotated around its center. The width is measured along the rotated x-axis.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrientedRectangle {
+    pub center: VpPoint,
+    pub width: f64,
+    pub height: f64,
+    /// Rotation of the rectangle counterclockwise from the global x-axis in degrees
+    pub angle: f64,
+}
+
+impl OrientedRectangle {
+    pub fn new(center: VpPoint, width: f64, height: f64, angle: f64) -> OrientedRectangle {
+        OrientedRectangle {
+            center,
+            width,
+            height,
+            angle,
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        self.width * self.height
+    }
+
+    /// Gets the corners as a closed counterclockwise polygon starting from the corner that is the
+    /// bottom left corner before the rotation
+    pub fn to_polygon(&self) -> Polygon {
+        let (w, h) = (self.width / 2.0, self.height / 2.0);
+        let corners = [(-w, -h), (w, -h), (w, h), (-w, h), (-w, -h)];
+        let points = corners
+            .iter()
+            .map(|(x, y)| {
+                let point = VpPoint::new(self.center.x + x, self.center.y + y);
+                rotate_point(&self.center, &point, self.angle)
+            })
+            .collect();
+        Polygon::new(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_polygon() {
+        let rectangle = OrientedRectangle::new(VpPoint::new(0.0, 0.0), 4.0, 2.0, 90.0);
+        assert_eq!(rectangle.area(), 8.0);
+        let polygon = rectangle.to_polygon();
+        assert_eq!(polygon.points.len(), 5);
+        assert_eq!(polygon.points[0], polygon.points[4]);
+        let expected = [(1.0, -2.0), (1.0, 2.0), (-1.0, 2.0), (-1.0, -2.0)];
+        for (point, (x, y)) in polygon.points.iter().zip(expected) {
+            assert!(
+                (point.x - x).abs() < 1e-12 && (point.y - y).abs() < 1e-12,
+                "{point:?}"
+            );
+        }
+    }
+}
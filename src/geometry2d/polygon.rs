@@ -1,8 +1,11 @@
 ﻿use serde::{Deserialize, Serialize};
 
-use crate::geometry2d::{self, VpPoint};
+use crate::geometry2d::{
+    self, boolean, hull, offset, triangulation, validation, Circle, JoinType, MeshOptions,
+    OrientedRectangle, PolygonIssue, PolygonWithHoles, TriangleMesh, VpPoint,
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Polygon {
     pub points: Vec<VpPoint>,
 }
@@ -15,35 +18,146 @@ impl Polygon {
         Polygon { points: vec![] }
     }
 
-    pub fn get_line(&self, index: i32) -> Option<(&VpPoint, &VpPoint)> {
-        let p1;
-        let p2;
-        if index >= self.points.len() as i32 {
-            return None
-        } else {
-            p1 = &self.points[index as usize];
-            p2 = &self.points[index as usize + 1];
+    /// Checks if the last point is the same as the first point. The polygons can be stored
+    /// either way and all the calculations give the same results for both.
+    pub fn is_closed(&self) -> bool {
+        self.points.len() > 1 && self.points.first() == self.points.last()
+    }
+
+    /// Adds the first point to the end if the polygon isn't closed
+    pub fn close(&mut self) {
+        if !self.is_closed() {
+            if let Some(first) = self.points.first().copied() {
+                self.points.push(first);
+            }
         }
-        Some((p1, p2))
     }
 
+    /// Removes the last point if it's the same as the first point
+    pub fn open(&mut self) {
+        if self.is_closed() {
+            self.points.pop();
+        }
+    }
+
+    /// Iterates over the edges of the polygon. Every edge is returned once and the closing edge
+    /// from the last point to the first point is included whether the polygon is closed or not.
+    pub fn edges(&self) -> impl Iterator<Item = (&VpPoint, &VpPoint)> {
+        let count = if self.is_closed() { self.points.len() - 1 } else { self.points.len() };
+        // A single point has no edges
+        let count = if count < 2 { 0 } else { count };
+        (0..count).map(move |i| (&self.points[i], &self.points[(i + 1) % self.points.len()]))
+    }
+
+    /// Gets the edge from the point at the index to the next point. The closing edge of an open
+    /// polygon is the edge from the last point to the first point. Returns None if there's no
+    /// such edge.
+    pub fn get_line(&self, index: i32) -> Option<(&VpPoint, &VpPoint)> {
+        let index = usize::try_from(index).ok()?;
+        self.edges().nth(index)
+    }
+
+    /// Gets the edge like [`Polygon::get_line`] or the last edge if the index is out of bounds.
+    /// Panics if the polygon has less than two points.
     pub fn get_line_or_last(&self, index: i32) -> (&VpPoint, &VpPoint) {
-        let p1;
-        let p2;
-        if index >= self.points.len() as i32 {
+        match self.get_line(index) {
+            Some(line) => line,
             // Get the last line of the polygon if the index is out of bounds
-            p1 = &self.points[self.points.len() - 2];
-            p2 = &self.points[self.points.len() - 1];
-        } else {
-            p1 = &self.points[index as usize];
-            p2 = &self.points[index as usize + 1];
+            None => self.edges().last().expect("the polygon has no edges"),
         }
-        (p1, p2)
     }
 
     pub fn get_direction(&self) -> Direction {
         geometry2d::get_polygon_direction(self)
     }
+
+    /// Checks if the point is inside the polygon. Works with clockwise and counterclockwise
+    /// polygons and the polygon can be closed or open. Self intersecting polygons use the nonzero
+    /// winding rule, so the areas that are wound around multiple times are inside.
+    ///
+    /// The point is on the boundary if it's within `epsilon` from a vertex or lies on an edge
+    /// (see [`geometry2d::point_in_line`]).
+    pub fn contains(&self, point: &VpPoint, epsilon: f64) -> Containment {
+        let count = self.points.len();
+        for i in 0..count {
+            let (p1, p2) = (&self.points[i], &self.points[(i + 1) % count]);
+            if geometry2d::calc_length_between_points(p1, point) <= epsilon
+                || (p1 != p2 && geometry2d::point_in_line(p1, p2, point, epsilon))
+            {
+                return Containment::OnBoundary;
+            }
+        }
+        if geometry2d::winding_number(&self.points, point) != 0 {
+            Containment::Inside
+        } else {
+            Containment::Outside
+        }
+    }
+
+    /// See [`boolean::union`]
+    pub fn union(&self, other: &Polygon, tolerance: f64) -> Vec<Polygon> {
+        boolean::union(self, other, tolerance)
+    }
+
+    /// See [`boolean::intersection`]
+    pub fn intersection(&self, other: &Polygon, tolerance: f64) -> Vec<Polygon> {
+        boolean::intersection(self, other, tolerance)
+    }
+
+    /// See [`boolean::difference`]
+    pub fn difference(&self, other: &Polygon, tolerance: f64) -> Vec<Polygon> {
+        boolean::difference(self, other, tolerance)
+    }
+
+    /// See [`boolean::xor`]
+    pub fn xor(&self, other: &Polygon, tolerance: f64) -> Vec<Polygon> {
+        boolean::xor(self, other, tolerance)
+    }
+
+    /// See [`offset::offset`]
+    pub fn offset(&self, distance: f64, join_type: JoinType, miter_limit: f64) -> Vec<Polygon> {
+        offset::offset(self, distance, join_type, miter_limit)
+    }
+
+    /// See [`hull::convex_hull`]
+    pub fn convex_hull(&self) -> Polygon {
+        hull::convex_hull(&self.points)
+    }
+
+    /// See [`hull::minimum_bounding_rectangle`]
+    pub fn minimum_bounding_rectangle(&self) -> Option<OrientedRectangle> {
+        hull::minimum_bounding_rectangle(&self.points)
+    }
+
+    /// See [`hull::minimum_enclosing_circle`]
+    pub fn minimum_enclosing_circle(&self) -> Option<Circle> {
+        hull::minimum_enclosing_circle(&self.points)
+    }
+
+    /// See [`triangulation::triangulate`]
+    pub fn triangulate(&self) -> Vec<[usize; 3]> {
+        triangulation::triangulate(self)
+    }
+
+    /// See [`triangulation::constrained_delaunay`]
+    pub fn triangulate_delaunay(&self, options: &MeshOptions) -> TriangleMesh {
+        triangulation::constrained_delaunay(&PolygonWithHoles::from(self.clone()), options)
+    }
+
+    /// See [`validation::validate`]
+    pub fn validate(&self, epsilon: f64) -> Vec<PolygonIssue> {
+        validation::validate(self, epsilon)
+    }
+
+    /// Checks if the polygon is a simple polygon. See [`validation::validate`]
+    pub fn is_valid(&self, epsilon: f64) -> bool {
+        validation::validate(self, epsilon).is_empty()
+    }
+
+    /// See [`validation::make_valid`]
+    pub fn make_valid(&self, tolerance: f64) -> Vec<Polygon> {
+        validation::make_valid(self, tolerance)
+    }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -52,6 +166,14 @@ pub enum Direction {
     CounterClockwise = 1,
 }
 
+/// Result of the point-in-polygon test. See [`Polygon::contains`]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum Containment {
+    Inside,
+    Outside,
+    OnBoundary,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,4 +211,106 @@ mod tests {
             (&polygon.points[3], &polygon.points[4])
         );
     }
+
+    #[test]
+    fn closed_and_open() {
+        let mut polygon = Polygon::new(vec![
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(1.0, 0.0),
+            VpPoint::new(1.0, 1.0),
+        ]);
+        assert!(!polygon.is_closed());
+        let open_edges: Vec<_> = polygon.edges().map(|(p1, p2)| (*p1, *p2)).collect();
+        assert_eq!(open_edges.len(), 3);
+        assert_eq!(open_edges[2], (VpPoint::new(1.0, 1.0), VpPoint::new(0.0, 0.0)));
+        assert_eq!(polygon.get_line(2), Some((&polygon.points[2], &polygon.points[0])));
+        assert_eq!(polygon.get_line(3), None);
+        assert_eq!(polygon.get_line(-1), None);
+        let open_area = geometry2d::calculate_area(&polygon);
+        let open_centroid = geometry2d::centroid_from_polygon(&polygon);
+
+        polygon.close();
+        polygon.close();
+        assert!(polygon.is_closed());
+        assert_eq!(polygon.points.len(), 4);
+        let closed_edges: Vec<_> = polygon.edges().map(|(p1, p2)| (*p1, *p2)).collect();
+        assert_eq!(closed_edges, open_edges);
+        assert_eq!(polygon.get_line(3), None);
+        assert_eq!(polygon.get_line_or_last(3), (&polygon.points[2], &polygon.points[3]));
+        assert_eq!(geometry2d::calculate_area(&polygon), open_area);
+        assert_eq!(geometry2d::centroid_from_polygon(&polygon), open_centroid);
+        assert_eq!(open_area, 0.5);
+
+        polygon.open();
+        polygon.open();
+        assert_eq!(polygon.points.len(), 3);
+        assert_eq!(polygon.get_line_or_last(10), (&polygon.points[2], &polygon.points[0]));
+
+        let mut point = Polygon::new(vec![VpPoint::new(1.0, 1.0)]);
+        assert_eq!(point.edges().count(), 0);
+        point.close();
+        assert!(point.is_closed());
+        assert_eq!(point.edges().count(), 0);
+        assert_eq!(Polygon::new_empty().edges().count(), 0);
+        assert_eq!(geometry2d::calculate_area(&Polygon::new_empty()), 0.0);
+    }
+
+    #[test]
+    fn contains() {
+        let mut polygon = Polygon::new(vec![
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(10.0, 0.0),
+            VpPoint::new(10.0, 10.0),
+            VpPoint::new(5.0, 5.0),
+            VpPoint::new(0.0, 10.0),
+        ]);
+        for iteration in 0..2 {
+            if iteration == 1 {
+                assert_eq!(polygon.get_direction(), Direction::Clockwise);
+            }
+            assert_eq!(polygon.contains(&VpPoint::new(2.0, 2.0), 0.01), Containment::Inside);
+            assert_eq!(polygon.contains(&VpPoint::new(5.0, 8.0), 0.01), Containment::Outside);
+            assert_eq!(polygon.contains(&VpPoint::new(-1.0, 5.0), 0.01), Containment::Outside);
+            assert_eq!(polygon.contains(&VpPoint::new(5.0, 0.0), 0.01), Containment::OnBoundary);
+            assert_eq!(polygon.contains(&VpPoint::new(0.0, 5.0), 0.01), Containment::OnBoundary);
+            assert_eq!(polygon.contains(&VpPoint::new(7.5, 7.5), 0.01), Containment::OnBoundary);
+            assert_eq!(polygon.contains(&VpPoint::new(5.0, 0.005), 0.01), Containment::OnBoundary);
+            assert_eq!(polygon.contains(&VpPoint::new(5.0, 0.02), 0.01), Containment::Inside);
+            // Within the epsilon from a corner but outside both edges
+            assert_eq!(
+                polygon.contains(&VpPoint::new(10.005, -0.005), 0.01),
+                Containment::OnBoundary
+            );
+            assert_eq!(polygon.contains(&VpPoint::new(10.02, -0.02), 0.01), Containment::Outside);
+            // The same results with clockwise and closed polygon
+            polygon.points.reverse();
+            polygon.points.push(polygon.points[0]);
+        }
+
+        // Self intersecting bowtie and pentagram (the center is wound around twice)
+        let bowtie = Polygon::new(vec![
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(10.0, 10.0),
+            VpPoint::new(10.0, 0.0),
+            VpPoint::new(0.0, 10.0),
+        ]);
+        assert_eq!(bowtie.contains(&VpPoint::new(2.0, 5.0), 0.01), Containment::Inside);
+        assert_eq!(bowtie.contains(&VpPoint::new(8.0, 5.0), 0.01), Containment::Inside);
+        assert_eq!(bowtie.contains(&VpPoint::new(5.0, 2.0), 0.01), Containment::Outside);
+        assert_eq!(bowtie.contains(&VpPoint::new(5.0, 5.0), 0.01), Containment::OnBoundary);
+        let pentagram = Polygon::new(
+            [0, 2, 4, 1, 3]
+                .iter()
+                .map(|i| {
+                    let angle = (90.0 + 72.0 * *i as f64).to_radians();
+                    VpPoint::new(angle.cos(), angle.sin())
+                })
+                .collect(),
+        );
+        assert_eq!(pentagram.contains(&VpPoint::new(0.0, 0.0), 0.001), Containment::Inside);
+        assert_eq!(pentagram.contains(&VpPoint::new(0.0, 0.7), 0.001), Containment::Inside);
+        assert_eq!(pentagram.contains(&VpPoint::new(0.5, 0.6), 0.001), Containment::Outside);
+        let empty = Polygon::new_empty();
+        assert_eq!(empty.contains(&VpPoint::new(0.0, 0.0), 0.1), Containment::Outside);
+    }
 }
@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry2d::polygon::{Containment, Direction};
+use crate::geometry2d::rectangle::bounding_box;
+use crate::geometry2d::triangulation;
+use crate::geometry2d::{
+    calculate_area, centroid_from_polygon, MeshOptions, Polygon, Rectangle, TriangleMesh, VpPoint,
+};
+
+/// A polygon with holes (e.g. a hollow section or a plate with bolt holes). The holes must be
+/// inside the outer polygon and must not overlap each other. The directions of the outer
+/// polygon and the holes don't matter in the calculations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolygonWithHoles {
+    pub outer: Polygon,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub holes: Vec<Polygon>,
+}
+
+impl PolygonWithHoles {
+    pub fn new(outer: Polygon, holes: Vec<Polygon>) -> PolygonWithHoles {
+        PolygonWithHoles { outer, holes }
+    }
+
+    /// Calculates the area of the outer polygon minus the areas of the holes. Always returns a
+    /// positive value.
+    pub fn calculate_area(&self) -> f64 {
+        let holes: f64 = self.holes.iter().map(calculate_area).sum();
+        calculate_area(&self.outer) - holes
+    }
+
+    /// Calculates the centroid from current origo. The holes are subtracted from the outer
+    /// polygon by their areas.
+    pub fn centroid(&self) -> VpPoint {
+        let (mut area, mut x, mut y) = (0.0, 0.0, 0.0);
+        for (polygon, sign) in self.rings() {
+            let polygon_area = calculate_area(polygon) * sign;
+            let centroid = centroid_from_polygon(polygon);
+            area += polygon_area;
+            x += centroid.x * polygon_area;
+            y += centroid.y * polygon_area;
+        }
+        VpPoint::new(x / area, y / area)
+    }
+
+    /// Gets the bounding box of the outer polygon
+    pub fn bounding_box(&self) -> Option<Rectangle> {
+        bounding_box(&self.outer)
+    }
+
+    /// Gets the direction of the outer polygon
+    pub fn get_direction(&self) -> Direction {
+        self.outer.get_direction()
+    }
+
+    /// Sets the outer polygon counterclockwise and the holes clockwise (the same directions as
+    /// the boolean operations return)
+    pub fn normalize_directions(&mut self) {
+        if self.outer.get_direction() == Direction::Clockwise {
+            self.outer.points.reverse();
+        }
+        for hole in &mut self.holes {
+            if hole.get_direction() == Direction::CounterClockwise {
+                hole.points.reverse();
+            }
+        }
+    }
+
+    /// Checks if the point is inside the outer polygon and outside the holes. Points on the
+    /// boundary of a hole are on the boundary. See [`Polygon::contains`]
+    pub fn contains(&self, point: &VpPoint, epsilon: f64) -> Containment {
+        let outer = self.outer.contains(point, epsilon);
+        if outer != Containment::Inside {
+            return outer;
+        }
+        for hole in &self.holes {
+            match hole.contains(point, epsilon) {
+                Containment::Inside => return Containment::Outside,
+                Containment::OnBoundary => return Containment::OnBoundary,
+                Containment::Outside => {}
+            }
+        }
+        Containment::Inside
+    }
+
+    /// See [`triangulation::triangulate_with_holes`]
+    pub fn triangulate(&self) -> Vec<[usize; 3]> {
+        triangulation::triangulate_with_holes(self)
+    }
+
+    /// See [`triangulation::constrained_delaunay`]
+    pub fn triangulate_delaunay(&self, options: &MeshOptions) -> TriangleMesh {
+        triangulation::constrained_delaunay(self, options)
+    }
+
+    /// The outer polygon with 1.0 and the holes with -1.0
+    pub(super) fn rings(&self) -> impl Iterator<Item = (&Polygon, f64)> {
+        std::iter::once((&self.outer, 1.0)).chain(self.holes.iter().map(|h| (h, -1.0)))
+    }
+}
+
+impl From<Polygon> for PolygonWithHoles {
+    fn from(outer: Polygon) -> Self {
+        PolygonWithHoles::new(outer, Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry2d::test_utils::rectangle;
+
+    #[test]
+    fn hollow_section() {
+        // 100x200 rectangular hollow section with 10 thick walls
+        let mut inner = rectangle(10.0, 10.0, 80.0, 180.0);
+        inner.points.reverse();
+        let section = PolygonWithHoles::new(rectangle(0.0, 0.0, 100.0, 200.0), vec![inner]);
+        assert_eq!(section.calculate_area(), 20000.0 - 14400.0);
+        assert_eq!(section.centroid(), VpPoint::new(50.0, 100.0));
+        let bb = section.bounding_box().unwrap();
+        assert_eq!((bb.width, bb.height), (100.0, 200.0));
+        assert_eq!(section.get_direction(), Direction::CounterClockwise);
+    }
+
+    #[test]
+    fn plate_with_bolt_holes() {
+        // The directions of the holes don't affect the results
+        let plate = rectangle(0.0, 0.0, 100.0, 50.0);
+        let hole = rectangle(70.0, 20.0, 10.0, 10.0);
+        let mut reversed_hole = hole.clone();
+        reversed_hole.points.reverse();
+        let mut with_hole = PolygonWithHoles::new(plate.clone(), vec![hole]);
+        let with_reversed_hole = PolygonWithHoles::new(plate, vec![reversed_hole]);
+        assert_eq!(with_hole.calculate_area(), 4900.0);
+        assert_eq!(with_reversed_hole.calculate_area(), 4900.0);
+        let centroid = with_hole.centroid();
+        assert_eq!(centroid, with_reversed_hole.centroid());
+        assert!((centroid.x - (5000.0 * 50.0 - 100.0 * 75.0) / 4900.0).abs() < 1e-12);
+        assert!((centroid.y - 25.0).abs() < 1e-12);
+
+        with_hole.outer.points.reverse();
+        with_hole.normalize_directions();
+        assert_eq!(with_hole.get_direction(), Direction::CounterClockwise);
+        assert_eq!(with_hole.holes[0].get_direction(), Direction::Clockwise);
+    }
+
+    #[test]
+    fn contains() {
+        let section = PolygonWithHoles::new(
+            rectangle(0.0, 0.0, 10.0, 10.0),
+            vec![rectangle(2.0, 2.0, 2.0, 2.0)],
+        );
+        assert_eq!(
+            section.contains(&VpPoint::new(1.0, 1.0), 1e-6),
+            Containment::Inside
+        );
+        assert_eq!(
+            section.contains(&VpPoint::new(3.0, 3.0), 1e-6),
+            Containment::Outside
+        );
+        assert_eq!(
+            section.contains(&VpPoint::new(4.0, 3.0), 1e-6),
+            Containment::OnBoundary
+        );
+        assert_eq!(
+            section.contains(&VpPoint::new(10.0, 3.0), 1e-6),
+            Containment::OnBoundary
+        );
+        assert_eq!(
+            section.contains(&VpPoint::new(11.0, 3.0), 1e-6),
+            Containment::Outside
+        );
+    }
+
+    #[test]
+    fn serialization() {
+        let section = PolygonWithHoles::new(
+            rectangle(0.0, 0.0, 10.0, 10.0),
+            vec![rectangle(2.0, 2.0, 2.0, 2.0)],
+        );
+        let serialized = serde_json::to_string(&section).unwrap();
+        let deserialized: PolygonWithHoles = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.holes.len(), 1);
+        assert_eq!(deserialized.calculate_area(), 96.0);
+
+        let solid: PolygonWithHoles = rectangle(0.0, 0.0, 10.0, 10.0).into();
+        let serialized = serde_json::to_string(&solid).unwrap();
+        assert!(!serialized.contains("holes"));
+        let deserialized: PolygonWithHoles = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.calculate_area(), 100.0);
+    }
+}
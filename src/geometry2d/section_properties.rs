@@ -0,0 +1,421 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry2d::{rotate_point, MultiPolygon, Polygon, PolygonWithHoles, VpPoint};
+
+/// Cross-section properties of a polygon. The moments of inertia are about the axes through
+/// the centroid that are parallel to the global axes (positive axes: x →, y ↑). Angles are in
+/// degrees.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SectionProperties {
+    pub area: f64,
+    pub centroid: VpPoint,
+    /// Moment of inertia about the x-axis (∫y² dA)
+    pub ix: f64,
+    /// Moment of inertia about the y-axis (∫x² dA)
+    pub iy: f64,
+    /// Product of inertia (∫xy dA)
+    pub ixy: f64,
+    /// Major principal moment of inertia
+    pub i1: f64,
+    /// Minor principal moment of inertia
+    pub i2: f64,
+    /// Angle from the x-axis to the major principal axis (-90..90)
+    pub angle: f64,
+    /// Elastic section modulus about the x-axis for the top edge (max y)
+    pub wx_top: f64,
+    /// Elastic section modulus about the x-axis for the bottom edge (min y)
+    pub wx_bottom: f64,
+    /// Elastic section modulus about the y-axis for the right edge (max x)
+    pub wy_right: f64,
+    /// Elastic section modulus about the y-axis for the left edge (min x)
+    pub wy_left: f64,
+    /// Minimum elastic section modulus about the major principal axis
+    pub w1: f64,
+    /// Minimum elastic section modulus about the minor principal axis
+    pub w2: f64,
+    /// Radius of gyration about the x-axis
+    pub rx: f64,
+    /// Radius of gyration about the y-axis
+    pub ry: f64,
+    /// Radius of gyration about the major principal axis
+    pub r1: f64,
+    /// Radius of gyration about the minor principal axis
+    pub r2: f64,
+    /// Plastic section modulus about the x-axis
+    pub zx: f64,
+    /// Plastic section modulus about the y-axis
+    pub zy: f64,
+    /// The y coordinate of the plastic neutral axis parallel to the x-axis (splits the area in
+    /// half)
+    pub plastic_y: f64,
+    /// The x coordinate of the plastic neutral axis parallel to the y-axis
+    pub plastic_x: f64,
+}
+
+/// Axis parallel to the global x or y axis
+#[derive(Debug, Copy, Clone)]
+enum Axis {
+    X,
+    Y,
+}
+
+/// The area integrals of a ring: area, ∫y dA, ∫x dA, ∫y² dA, ∫x² dA and ∫xy dA
+#[derive(Debug, Default, Copy, Clone)]
+struct AreaIntegrals {
+    area: f64,
+    sx: f64,
+    sy: f64,
+    ixx: f64,
+    iyy: f64,
+    ixy: f64,
+}
+
+impl AreaIntegrals {
+    /// Calculates the integrals with Green's theorem. The values are positive for
+    /// counterclockwise rings. The ring can be closed or open.
+    fn from_ring(points: &[VpPoint]) -> AreaIntegrals {
+        let mut result = AreaIntegrals::default();
+        for i in 0..points.len() {
+            let (p1, p2) = (&points[i], &points[(i + 1) % points.len()]);
+            let cross = p1.x * p2.y - p2.x * p1.y;
+            result.area += cross / 2.0;
+            result.sx += (p1.y + p2.y) * cross / 6.0;
+            result.sy += (p1.x + p2.x) * cross / 6.0;
+            result.ixx += (p1.y * p1.y + p1.y * p2.y + p2.y * p2.y) * cross / 12.0;
+            result.iyy += (p1.x * p1.x + p1.x * p2.x + p2.x * p2.x) * cross / 12.0;
+            result.ixy +=
+                (p1.x * p2.y + 2.0 * p1.x * p1.y + 2.0 * p2.x * p2.y + p2.x * p1.y) * cross / 24.0;
+        }
+        result
+    }
+
+    fn add(&mut self, other: AreaIntegrals, factor: f64) {
+        self.area += other.area * factor;
+        self.sx += other.sx * factor;
+        self.sy += other.sy * factor;
+        self.ixx += other.ixx * factor;
+        self.iyy += other.iyy * factor;
+        self.ixy += other.ixy * factor;
+    }
+}
+
+impl SectionProperties {
+    /// Calculates the section properties of the polygon. Returns None if the area is zero.
+    pub fn from_polygon(polygon: &Polygon) -> Option<SectionProperties> {
+        Self::from_rings(&[(polygon, 1.0)])
+    }
+
+    /// Calculates the section properties of the polygon with the holes subtracted. Returns None
+    /// if the area is zero.
+    pub fn from_polygon_with_holes(polygon: &PolygonWithHoles) -> Option<SectionProperties> {
+        let rings: Vec<_> = polygon.rings().collect();
+        Self::from_rings(&rings)
+    }
+
+    /// Calculates the section properties of all the polygons together. Returns None if the
+    /// area is zero.
+    pub fn from_multi_polygon(multi_polygon: &MultiPolygon) -> Option<SectionProperties> {
+        let rings: Vec<_> = multi_polygon
+            .polygons
+            .iter()
+            .flat_map(PolygonWithHoles::rings)
+            .collect();
+        Self::from_rings(&rings)
+    }
+
+    /// Calculates the properties of the rings. The rings are added (1.0) or subtracted (-1.0)
+    /// regardless of their directions.
+    fn from_rings(rings: &[(&Polygon, f64)]) -> Option<SectionProperties> {
+        // The rings with the factors that make their contributions positive or negative
+        let rings: Vec<(&[VpPoint], f64)> = rings
+            .iter()
+            .map(|&(ring, sign)| {
+                let points = ring.points.as_slice();
+                let area = AreaIntegrals::from_ring(points).area;
+                (points, if area < 0.0 { -sign } else { sign })
+            })
+            .collect();
+        let mut total = AreaIntegrals::default();
+        for &(points, factor) in &rings {
+            total.add(AreaIntegrals::from_ring(points), factor);
+        }
+        let area = total.area;
+        if area == 0.0 || !area.is_finite() {
+            return None;
+        }
+        let (xc, yc) = (total.sy / area, total.sx / area);
+        let centroid = VpPoint::new(xc, yc);
+        let ix = total.ixx - area * yc * yc;
+        let iy = total.iyy - area * xc * xc;
+        let ixy = total.ixy - area * xc * yc;
+
+        let average = (ix + iy) / 2.0;
+        let radius = ((ix - iy) / 2.0).hypot(ixy);
+        let (i1, i2) = (average + radius, average - radius);
+        let angle = (0.5 * (-2.0 * ixy).atan2(ix - iy)).to_degrees();
+
+        let points = || rings.iter().flat_map(|(points, _)| points.iter());
+        let max = |values: &mut dyn Iterator<Item = f64>| values.fold(f64::MIN, f64::max);
+        let y_max = max(&mut points().map(|p| p.y));
+        let y_min = -max(&mut points().map(|p| -p.y));
+        let x_max = max(&mut points().map(|p| p.x));
+        let x_min = -max(&mut points().map(|p| -p.x));
+        // Distances from the principal axes in the rotated coordinates
+        let rotated: Vec<VpPoint> = points()
+            .map(|p| rotate_point(&centroid, p, -angle))
+            .collect();
+        let distance_1 = max(&mut rotated.iter().map(|p| (p.y - yc).abs()));
+        let distance_2 = max(&mut rotated.iter().map(|p| (p.x - xc).abs()));
+
+        let (plastic_y, zx) = Self::plastic_modulus(&rings, &total, Axis::X, y_min, y_max);
+        let (plastic_x, zy) = Self::plastic_modulus(&rings, &total, Axis::Y, x_min, x_max);
+
+        Some(SectionProperties {
+            area,
+            centroid,
+            ix,
+            iy,
+            ixy,
+            i1,
+            i2,
+            angle,
+            wx_top: ix / (y_max - yc),
+            wx_bottom: ix / (yc - y_min),
+            wy_right: iy / (x_max - xc),
+            wy_left: iy / (xc - x_min),
+            w1: i1 / distance_1,
+            w2: i2 / distance_2,
+            rx: (ix / area).sqrt(),
+            ry: (iy / area).sqrt(),
+            r1: (i1 / area).sqrt(),
+            r2: (i2 / area).sqrt(),
+            zx,
+            zy,
+            plastic_y,
+            plastic_x,
+        })
+    }
+
+    /// Finds the plastic neutral axis (the axis that splits the area in half) with bisection
+    /// and calculates the plastic section modulus about it. The axis is parallel to `axis`.
+    fn plastic_modulus(
+        rings: &[(&[VpPoint], f64)],
+        total: &AreaIntegrals,
+        axis: Axis,
+        min: f64,
+        max: f64,
+    ) -> (f64, f64) {
+        let first_moment = |integrals: &AreaIntegrals| match axis {
+            Axis::X => integrals.sx,
+            Axis::Y => integrals.sy,
+        };
+        // The integrals of the area below the axis
+        let below = |position: f64| {
+            let mut result = AreaIntegrals::default();
+            for &(points, factor) in rings {
+                let clipped = clip_ring(points, axis, position);
+                result.add(AreaIntegrals::from_ring(&clipped), factor);
+            }
+            result
+        };
+        let (mut low, mut high) = (min, max);
+        while low < high {
+            let middle = (low + high) / 2.0;
+            if middle <= low || middle >= high {
+                break;
+            }
+            if below(middle).area < total.area / 2.0 {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        let position = (low + high) / 2.0;
+        let below = below(position);
+        let below_moment = position * below.area - first_moment(&below);
+        let above_moment =
+            first_moment(total) - first_moment(&below) - position * (total.area - below.area);
+        (position, below_moment + above_moment)
+    }
+}
+
+/// Clips the ring to the half-plane below the axis (or left of it with [`Axis::Y`]) at the
+/// given position with the Sutherland–Hodgman algorithm. The clipped ring can have overlapping
+/// edges with non-convex rings, but they don't affect the area integrals.
+fn clip_ring(points: &[VpPoint], axis: Axis, position: f64) -> Vec<VpPoint> {
+    let coordinate = |p: &VpPoint| match axis {
+        Axis::X => p.y,
+        Axis::Y => p.x,
+    };
+    let count = points.len();
+    let mut result = Vec::with_capacity(count + 2);
+    for i in 0..count {
+        let (p1, p2) = (&points[i], &points[(i + 1) % count]);
+        let (c1, c2) = (coordinate(p1) - position, coordinate(p2) - position);
+        if c1 <= 0.0 {
+            result.push(*p1);
+        }
+        if (c1 < 0.0 && c2 > 0.0) || (c1 > 0.0 && c2 < 0.0) {
+            let t = c1 / (c1 - c2);
+            result.push(VpPoint::new(
+                p1.x + t * (p2.x - p1.x),
+                p1.y + t * (p2.y - p1.y),
+            ));
+        }
+    }
+    result
+}
+
+impl Polygon {
+    /// See [`SectionProperties::from_polygon`]
+    pub fn section_properties(&self) -> Option<SectionProperties> {
+        SectionProperties::from_polygon(self)
+    }
+}
+
+impl PolygonWithHoles {
+    /// See [`SectionProperties::from_polygon_with_holes`]
+    pub fn section_properties(&self) -> Option<SectionProperties> {
+        SectionProperties::from_polygon_with_holes(self)
+    }
+}
+
+impl MultiPolygon {
+    /// See [`SectionProperties::from_multi_polygon`]
+    pub fn section_properties(&self) -> Option<SectionProperties> {
+        SectionProperties::from_multi_polygon(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry2d::test_utils::{assert_close, rectangle};
+
+    #[test]
+    fn rectangle_section() {
+        let (b, h) = (100.0, 200.0);
+        let properties = rectangle(50.0, -20.0, b, h).section_properties().unwrap();
+        assert_close(properties.area, b * h);
+        assert_close(properties.centroid.x, 100.0);
+        assert_close(properties.centroid.y, 80.0);
+        assert_close(properties.ix, b * h.powi(3) / 12.0);
+        assert_close(properties.iy, h * b.powi(3) / 12.0);
+        assert_close(properties.ixy, 0.0);
+        assert_close(properties.i1, properties.ix);
+        assert_close(properties.i2, properties.iy);
+        assert_close(properties.angle, 0.0);
+        assert_close(properties.wx_top, b * h * h / 6.0);
+        assert_close(properties.wx_bottom, b * h * h / 6.0);
+        assert_close(properties.wy_left, h * b * b / 6.0);
+        assert_close(properties.w1, b * h * h / 6.0);
+        assert_close(properties.w2, h * b * b / 6.0);
+        assert_close(properties.rx, h / 12f64.sqrt());
+        assert_close(properties.ry, b / 12f64.sqrt());
+        assert_close(properties.zx, b * h * h / 4.0);
+        assert_close(properties.zy, h * b * b / 4.0);
+        assert_close(properties.plastic_y, 80.0);
+        assert_close(properties.plastic_x, 100.0);
+
+        // The direction and the closing point don't matter
+        let mut reversed = rectangle(50.0, -20.0, b, h);
+        reversed.points.pop();
+        reversed.points.reverse();
+        let reversed = reversed.section_properties().unwrap();
+        assert_close(reversed.area, properties.area);
+        assert_close(reversed.ix, properties.ix);
+        assert_close(reversed.zy, properties.zy);
+        assert!(Polygon::new_empty().section_properties().is_none());
+    }
+
+    #[test]
+    fn hollow_and_composite_sections() {
+        // 100x200x10 rectangular hollow section, the hole is clockwise or counterclockwise
+        let outer = rectangle(0.0, 0.0, 100.0, 200.0);
+        for reverse in [false, true] {
+            let mut hole = rectangle(10.0, 10.0, 80.0, 180.0);
+            if reverse {
+                hole.points.reverse();
+            }
+            let section = PolygonWithHoles::new(outer.clone(), vec![hole]);
+            let properties = section.section_properties().unwrap();
+            assert_close(properties.area, 20000.0 - 14400.0);
+            assert_close(
+                properties.ix,
+                (100.0 * 200f64.powi(3) - 80.0 * 180f64.powi(3)) / 12.0,
+            );
+            assert_close(properties.wx_top, properties.ix / 100.0);
+            assert_close(
+                properties.zx,
+                (100.0 * 200f64.powi(2) - 80.0 * 180f64.powi(2)) / 4.0,
+            );
+            assert_close(properties.plastic_y, 100.0);
+        }
+
+        // Symmetric I-section made of three plates
+        let (b, h, tf, tw) = (200.0, 400.0, 20.0, 10.0);
+        let section = MultiPolygon::new(vec![
+            rectangle(-b / 2.0, 0.0, b, tf).into(),
+            rectangle(-tw / 2.0, tf, tw, h - 2.0 * tf).into(),
+            rectangle(-b / 2.0, h - tf, b, tf).into(),
+        ]);
+        let properties = section.section_properties().unwrap();
+        let hw = h - 2.0 * tf;
+        assert_close(properties.area, 2.0 * b * tf + hw * tw);
+        assert_close(
+            properties.ix,
+            (b * h.powi(3) - (b - tw) * hw.powi(3)) / 12.0,
+        );
+        assert_close(properties.zx, b * tf * (h - tf) + tw * hw * hw / 4.0);
+        assert_close(properties.zy, 2.0 * tf * b * b / 4.0 + hw * tw * tw / 4.0);
+        assert_close(properties.plastic_y, h / 2.0);
+
+        // Unsymmetric T-section: the plastic neutral axis is not at the centroid
+        let flange = rectangle(0.0, 100.0, 100.0, 10.0);
+        let web = rectangle(45.0, 0.0, 10.0, 100.0);
+        let t_section = flange.union(&web, 1e-9).remove(0);
+        let properties = t_section.section_properties().unwrap();
+        assert_close(properties.area, 2000.0);
+        assert_close(
+            properties.centroid.y,
+            (1000.0 * 105.0 + 1000.0 * 50.0) / 2000.0,
+        );
+        assert_close(properties.plastic_y, 100.0);
+        assert_close(properties.zx, 1000.0 * 5.0 + 1000.0 * 50.0);
+    }
+
+    #[test]
+    fn principal_axes() {
+        // Rectangle rotated 30 degrees has the principal axes rotated by the same angle
+        let centroid = VpPoint::new(50.0, 100.0);
+        let points = rectangle(0.0, 0.0, 100.0, 200.0)
+            .points
+            .iter()
+            .map(|p| rotate_point(&centroid, p, 30.0))
+            .collect();
+        let properties = Polygon::new(points).section_properties().unwrap();
+        assert_close(properties.angle, 30.0);
+        assert_close(properties.i1, 200.0 * 100.0 * 200.0 * 200.0 / 12.0);
+        assert_close(properties.i2, 200.0 * 100.0 * 100.0 * 100.0 / 12.0);
+        assert_close(properties.w1, 100.0 * 200.0 * 200.0 / 6.0);
+        assert_close(properties.w2, 200.0 * 100.0 * 100.0 / 6.0);
+        assert_close(properties.r1, 200.0 / 12f64.sqrt());
+
+        // Equal leg angle 100x100x10 has the major principal axis at 45 degrees
+        let angle = Polygon::new(vec![
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(100.0, 0.0),
+            VpPoint::new(100.0, 10.0),
+            VpPoint::new(10.0, 10.0),
+            VpPoint::new(10.0, 100.0),
+            VpPoint::new(0.0, 100.0),
+        ]);
+        let properties = angle.section_properties().unwrap();
+        assert_close(properties.area, 1900.0);
+        assert!(properties.ixy < 0.0);
+        assert_close(properties.angle, 45.0);
+        assert_close(properties.ix, properties.iy);
+        assert_close(properties.i1 + properties.i2, properties.ix + properties.iy);
+        assert_close(properties.i1, properties.ix - properties.ixy);
+    }
+}
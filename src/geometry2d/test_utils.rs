@@ -0,0 +1,36 @@
+use crate::geometry2d::{Polygon, VpPoint};
+
+/// Creates a closed counterclockwise rectangle with the bottom left corner at (x, y)
+pub(crate) fn rectangle(x: f64, y: f64, width: f64, height: f64) -> Polygon {
+    Polygon::new(vec![
+        VpPoint::new(x, y),
+        VpPoint::new(x + width, y),
+        VpPoint::new(x + width, y + height),
+        VpPoint::new(x, y + height),
+        VpPoint::new(x, y),
+    ])
+}
+
+/// Creates a polygon from the coordinates as they are (the polygon is not closed automatically)
+pub(crate) fn polygon(coordinates: &[(f64, f64)]) -> Polygon {
+    Polygon::new(
+        coordinates
+            .iter()
+            .map(|&(x, y)| VpPoint::new(x, y))
+            .collect(),
+    )
+}
+
+/// Asserts that the values are equal within 1e-9 relative to the expected value (or absolute if
+/// the expected value is less than one)
+pub(crate) fn assert_close(actual: f64, expected: f64) {
+    assert_within(actual, expected, 1e-9 * expected.abs().max(1.0));
+}
+
+/// Asserts that the values are equal within the given tolerance
+pub(crate) fn assert_within(actual: f64, expected: f64, epsilon: f64) {
+    assert!(
+        (actual - expected).abs() <= epsilon,
+        "{actual} != {expected}"
+    );
+}
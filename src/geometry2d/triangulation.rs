@@ -0,0 +1,874 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::geometry2d::{
+    calc_length_between_points, circumcenter, cross, signed_area, Containment, LineSegment,
+    Polygon, PolygonWithHoles, VpPoint,
+};
+
+/// Maximum number of points the refinement adds to a mesh
+const MAX_REFINEMENT_POINTS: usize = 10_000;
+
+/// Quality requirements for [`constrained_delaunay`]. Without any requirements only the points
+/// of the polygon are used. The refinement adds at most 10 000 points, so the requirements may
+/// not be met (see [`TriangleMesh::limit_reached`]).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MeshOptions {
+    /// Maximum area of a triangle. Very small areas compared to the area of the polygon need
+    /// more points than the limit of 10 000 points.
+    pub max_area: Option<f64>,
+    /// Minimum angle of a triangle in degrees. Angles up to about 30 degrees work well, larger
+    /// angles may add points until the limit of 10 000 points is reached. Small angles between
+    /// the edges of the polygon are not refined.
+    pub min_angle: Option<f64>,
+}
+
+/// Triangles as indexes to the points. The triangles are counterclockwise.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TriangleMesh {
+    pub points: Vec<VpPoint>,
+    pub triangles: Vec<[usize; 3]>,
+    /// True if the refinement stopped at the limit of the added points before all the triangles
+    /// met the requirements of the [`MeshOptions`]
+    pub limit_reached: bool,
+}
+
+impl TriangleMesh {
+    /// Calculates the total area of the triangles
+    pub fn area(&self) -> f64 {
+        self.triangles
+            .iter()
+            .map(|t| triangle_area(&self.points, t))
+            .sum()
+    }
+
+    /// Gets the smallest angle of the triangles in degrees
+    pub fn min_angle(&self) -> Option<f64> {
+        self.triangles
+            .iter()
+            .map(|t| min_angle(&self.points, t).0.to_degrees())
+            .reduce(f64::min)
+    }
+}
+
+/// Triangulates the polygon with ear clipping. The polygon can be clockwise or counterclockwise
+/// and closed or open. Returns counterclockwise triangles as indexes to the points of the
+/// polygon. The closing point of a closed polygon isn't used.
+///
+/// The polygon must not intersect itself (see [`validate`](super::validation::validate)). The
+/// ears of a self-intersecting polygon run out before the whole polygon is covered, so only a
+/// part of it is triangulated.
+pub fn triangulate(polygon: &Polygon) -> Vec<[usize; 3]> {
+    triangulate_rings(
+        &polygon.points,
+        ring_indexes(&polygon.points, 0),
+        Vec::new(),
+    )
+}
+
+/// Triangulates the polygon with holes with ear clipping. The holes are connected to the outer
+/// polygon with bridge edges before clipping the ears. Returns counterclockwise triangles as
+/// indexes to the points of the outer polygon followed by the points of the holes in order.
+/// Like in [`triangulate`], only a part of a self-intersecting polygon is triangulated.
+pub fn triangulate_with_holes(polygon: &PolygonWithHoles) -> Vec<[usize; 3]> {
+    let mut points = polygon.outer.points.clone();
+    let mut holes = Vec::new();
+    for hole in &polygon.holes {
+        holes.push(ring_indexes(&hole.points, points.len()));
+        points.extend(&hole.points);
+    }
+    triangulate_rings(&points, ring_indexes(&polygon.outer.points, 0), holes)
+}
+
+/// Triangulates the polygon with holes with the constrained Delaunay triangulation. The edges of
+/// the polygon are always edges of the triangles and the triangles are as close to equilateral
+/// as possible. Points are added inside the polygon and on its edges until the triangles meet
+/// the requirements of the options.
+///
+/// The points of the mesh start with the unique points of the outer polygon and the holes in
+/// order, followed by the added points.
+pub fn constrained_delaunay(polygon: &PolygonWithHoles, options: &MeshOptions) -> TriangleMesh {
+    constrained_delaunay_limited(polygon, options, MAX_REFINEMENT_POINTS)
+}
+
+/// See [`constrained_delaunay`]. The refinement adds at most `max_points` points.
+fn constrained_delaunay_limited(
+    polygon: &PolygonWithHoles,
+    options: &MeshOptions,
+    max_points: usize,
+) -> TriangleMesh {
+    let mut unique: Vec<VpPoint> = Vec::new();
+    let mut lookup: HashMap<(u64, u64), usize> = HashMap::new();
+    let mut segments: Vec<(usize, usize)> = Vec::new();
+    for ring in std::iter::once(&polygon.outer).chain(&polygon.holes) {
+        let ids: Vec<usize> = ring_indexes(&ring.points, 0)
+            .into_iter()
+            .map(|i| {
+                let point = ring.points[i];
+                *lookup
+                    .entry((point.x.to_bits(), point.y.to_bits()))
+                    .or_insert_with(|| {
+                        unique.push(point);
+                        unique.len() - 1
+                    })
+            })
+            .collect();
+        if ids.len() < 3 {
+            continue;
+        }
+        for k in 0..ids.len() {
+            segments.push((ids[k], ids[(k + 1) % ids.len()]));
+        }
+    }
+    if unique.len() < 3 {
+        return TriangleMesh::default();
+    }
+
+    let mut cdt = Cdt::new(&unique);
+    for point in &unique {
+        if let Some(start) = cdt.locate(point) {
+            cdt.insert(*point, &[start]);
+        }
+    }
+    for (a, b) in split_segments(&unique, &segments) {
+        cdt.recover_constraint(a + SUPER_POINTS, b + SUPER_POINTS);
+    }
+    cdt.make_delaunay();
+    cdt.remove_outside(polygon);
+    let mut limit_reached = false;
+    if options.max_area.is_some() || options.min_angle.is_some() {
+        limit_reached = !cdt.refine(options, max_points);
+    }
+    TriangleMesh {
+        limit_reached,
+        ..cdt.into_mesh()
+    }
+}
+
+/// Gets the indexes of the ring without the repeated points and the closing point
+fn ring_indexes(points: &[VpPoint], offset: usize) -> Vec<usize> {
+    let mut indexes: Vec<usize> = Vec::new();
+    for (i, point) in points.iter().enumerate() {
+        if indexes.last().map(|&last| points[last - offset]) != Some(*point) {
+            indexes.push(offset + i);
+        }
+    }
+    let first = indexes.first().map(|&i| points[i - offset]);
+    while indexes.len() > 1 && indexes.last().map(|&i| points[i - offset]) == first {
+        indexes.pop();
+    }
+    indexes
+}
+
+fn triangle_area(points: &[VpPoint], triangle: &[usize; 3]) -> f64 {
+    cross(
+        &points[triangle[0]],
+        &points[triangle[1]],
+        &points[triangle[2]],
+    ) / 2.0
+}
+
+/// Gets the smallest angle of the triangle in radians and the corner it's in
+fn min_angle(points: &[VpPoint], triangle: &[usize; 3]) -> (f64, usize) {
+    (0..3)
+        .map(|k| {
+            let p = &points[triangle[k]];
+            let (q, r) = (
+                &points[triangle[(k + 1) % 3]],
+                &points[triangle[(k + 2) % 3]],
+            );
+            let (ux, uy, vx, vy) = (q.x - p.x, q.y - p.y, r.x - p.x, r.y - p.y);
+            ((ux * vy - uy * vx).abs().atan2(ux * vx + uy * vy), k)
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap()
+}
+
+/// Signed area of the ring given as indexes to the points
+fn ring_area(points: &[VpPoint], ring: &[usize]) -> f64 {
+    let ring_points: Vec<VpPoint> = ring.iter().map(|&i| points[i]).collect();
+    signed_area(&ring_points)
+}
+
+// Ear clipping
+
+fn triangulate_rings(
+    points: &[VpPoint],
+    mut outer: Vec<usize>,
+    holes: Vec<Vec<usize>>,
+) -> Vec<[usize; 3]> {
+    if outer.len() < 3 {
+        return Vec::new();
+    }
+    if ring_area(points, &outer) < 0.0 {
+        outer.reverse();
+    }
+    // The holes are clockwise and bridged starting from the rightmost hole
+    let mut holes: Vec<Vec<usize>> = holes.into_iter().filter(|h| h.len() >= 3).collect();
+    for hole in &mut holes {
+        if ring_area(points, hole) > 0.0 {
+            hole.reverse();
+        }
+    }
+    let max_x = |ring: &Vec<usize>| ring.iter().map(|&i| points[i].x).fold(f64::MIN, f64::max);
+    holes.sort_by(|h1, h2| max_x(h2).total_cmp(&max_x(h1)));
+    for hole in &holes {
+        bridge_hole(points, &mut outer, hole);
+    }
+    clip_ears(points, outer)
+}
+
+/// Connects the hole to the ring from the rightmost point of the hole to a vertex of the ring
+/// that is visible from it (David Eberly, Triangulation by Ear Clipping)
+fn bridge_hole(points: &[VpPoint], ring: &mut Vec<usize>, hole: &[usize]) {
+    let hole_start = (0..hole.len())
+        .max_by(|&i, &j| points[hole[i]].x.total_cmp(&points[hole[j]].x))
+        .unwrap();
+    let m = points[hole[hole_start]];
+    let count = ring.len();
+
+    // The closest edge that the ray from the point to the positive x-direction hits
+    let mut closest: Option<(f64, usize)> = None;
+    for i in 0..count {
+        let (a, b) = (&points[ring[i]], &points[ring[(i + 1) % count]]);
+        if (a.y - m.y) * (b.y - m.y) > 0.0 || a.y == b.y {
+            continue;
+        }
+        let x = a.x + (m.y - a.y) * (b.x - a.x) / (b.y - a.y);
+        if x < m.x || closest.is_some_and(|(closest_x, _)| x >= closest_x) {
+            continue;
+        }
+        let visible = if a.y == m.y {
+            i
+        } else if b.y == m.y || b.x > a.x {
+            (i + 1) % count
+        } else {
+            i
+        };
+        closest = Some((x, visible));
+    }
+    let Some((x, mut visible)) = closest else {
+        return;
+    };
+
+    // A reflex vertex inside the triangle can block the view to the end point of the edge. The
+    // vertex with the smallest angle to the ray is visible.
+    let hit = VpPoint::new(x, m.y);
+    let p = points[ring[visible]];
+    if p != hit {
+        let (t1, t2, t3) = if p.y < m.y {
+            (&m, &p, &hit)
+        } else {
+            (&m, &hit, &p)
+        };
+        let mut best = (f64::MAX, f64::MAX);
+        for i in 0..count {
+            let r = &points[ring[i]];
+            let previous = &points[ring[(i + count - 1) % count]];
+            let next = &points[ring[(i + 1) % count]];
+            if i == visible || *r == p || cross(previous, r, next) >= 0.0 {
+                continue;
+            }
+            if cross(t1, t2, r) >= 0.0 && cross(t2, t3, r) >= 0.0 && cross(t3, t1, r) >= 0.0 {
+                let angle = (r.y - m.y).abs().atan2(r.x - m.x);
+                let distance = calc_length_between_points(&m, r);
+                if (angle, distance) < best {
+                    best = (angle, distance);
+                    visible = i;
+                }
+            }
+        }
+    }
+
+    let mut bridged: Vec<usize> = ring[..=visible].to_vec();
+    bridged.extend(&hole[hole_start..]);
+    bridged.extend(&hole[..=hole_start]);
+    bridged.push(ring[visible]);
+    bridged.extend(&ring[visible + 1..]);
+    *ring = bridged;
+}
+
+/// Clips the ears of the counterclockwise ring until only one triangle is left. Stops early if
+/// there are no ears or collinear points left (the ring intersects itself).
+fn clip_ears(points: &[VpPoint], mut ring: Vec<usize>) -> Vec<[usize; 3]> {
+    let mut triangles = Vec::new();
+    let mut i = 0;
+    let mut failures = 0;
+    while ring.len() > 3 {
+        let count = ring.len();
+        if is_ear(points, &ring, i) {
+            triangles.push([
+                ring[(i + count - 1) % count],
+                ring[i],
+                ring[(i + 1) % count],
+            ]);
+        } else if failures < count {
+            failures += 1;
+            i = (i + 1) % count;
+            continue;
+        } else {
+            // There are no ears left, so remove a collinear point without a triangle. If there
+            // are no collinear points either, the polygon intersects itself.
+            let collinear = (0..count).find(|&j| {
+                let previous = &points[ring[(j + count - 1) % count]];
+                cross(previous, &points[ring[j]], &points[ring[(j + 1) % count]]) == 0.0
+            });
+            match collinear {
+                Some(j) => i = j,
+                None => break,
+            }
+        }
+        ring.remove(i);
+        failures = 0;
+        i = (i + ring.len() - 1) % ring.len();
+    }
+    if ring.len() == 3 && cross(&points[ring[0]], &points[ring[1]], &points[ring[2]]) > 0.0 {
+        triangles.push([ring[0], ring[1], ring[2]]);
+    }
+    triangles
+}
+
+/// Checks if the vertex is convex and there are no other points inside the triangle it forms with
+/// its neighbors. The points that are the same as the corners (at the bridges) are ignored.
+fn is_ear(points: &[VpPoint], ring: &[usize], i: usize) -> bool {
+    let count = ring.len();
+    let a = &points[ring[(i + count - 1) % count]];
+    let b = &points[ring[i]];
+    let c = &points[ring[(i + 1) % count]];
+    if cross(a, b, c) <= 0.0 {
+        return false;
+    }
+    ring.iter().map(|&j| &points[j]).all(|p| {
+        p == a
+            || p == b
+            || p == c
+            || cross(a, b, p) < 0.0
+            || cross(b, c, p) < 0.0
+            || cross(c, a, p) < 0.0
+    })
+}
+
+// Constrained Delaunay triangulation
+
+/// The number of points in the super triangle that contains all the points
+const SUPER_POINTS: usize = 3;
+
+/// Incremental constrained Delaunay triangulation. The triangles are counterclockwise and the
+/// directed edges point to the triangle on their left side. The first three points are the
+/// corners of a super triangle that contains all the points.
+struct Cdt {
+    points: Vec<VpPoint>,
+    triangles: Vec<Option<[usize; 3]>>,
+    edges: HashMap<(usize, usize), usize>,
+    /// The edges of the polygon with the smaller index first
+    constraints: BTreeSet<(usize, usize)>,
+}
+
+impl Cdt {
+    fn new(points: &[VpPoint]) -> Cdt {
+        let min_x = points.iter().map(|p| p.x).fold(f64::MAX, f64::min);
+        let max_x = points.iter().map(|p| p.x).fold(f64::MIN, f64::max);
+        let min_y = points.iter().map(|p| p.y).fold(f64::MAX, f64::min);
+        let max_y = points.iter().map(|p| p.y).fold(f64::MIN, f64::max);
+        let size = (max_x - min_x).max(max_y - min_y).max(1e-9);
+        let (cx, cy) = ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+        let mut cdt = Cdt {
+            points: vec![
+                VpPoint::new(cx - 20.0 * size, cy - 10.0 * size),
+                VpPoint::new(cx + 20.0 * size, cy - 10.0 * size),
+                VpPoint::new(cx, cy + 20.0 * size),
+            ],
+            triangles: Vec::new(),
+            edges: HashMap::new(),
+            constraints: BTreeSet::new(),
+        };
+        cdt.add_triangle([0, 1, 2]);
+        cdt
+    }
+
+    fn add_triangle(&mut self, triangle: [usize; 3]) -> usize {
+        let index = self.triangles.len();
+        for k in 0..3 {
+            self.edges
+                .insert((triangle[k], triangle[(k + 1) % 3]), index);
+        }
+        self.triangles.push(Some(triangle));
+        index
+    }
+
+    fn remove_triangle(&mut self, index: usize) {
+        if let Some(triangle) = self.triangles[index].take() {
+            for k in 0..3 {
+                self.edges.remove(&(triangle[k], triangle[(k + 1) % 3]));
+            }
+        }
+    }
+
+    fn is_constrained(&self, a: usize, b: usize) -> bool {
+        self.constraints.contains(&(a.min(b), a.max(b)))
+    }
+
+    /// Gets the triangle on the left side of the edge and its third point
+    fn opposite(&self, a: usize, b: usize) -> Option<(usize, usize)> {
+        let index = *self.edges.get(&(a, b))?;
+        let triangle = self.triangles[index]?;
+        let third = triangle.iter().copied().find(|&i| i != a && i != b)?;
+        Some((index, third))
+    }
+
+    /// Checks if the point is inside the circumcircle of the triangle. Points that are almost
+    /// on the circle are outside, so the co-circular points don't flip edges back and forth.
+    fn in_circumcircle(&self, triangle: &[usize; 3], point: &VpPoint) -> bool {
+        let [a, b, c] = triangle.map(|i| self.points[i]);
+        let (adx, ady) = (a.x - point.x, a.y - point.y);
+        let (bdx, bdy) = (b.x - point.x, b.y - point.y);
+        let (cdx, cdy) = (c.x - point.x, c.y - point.y);
+        let (ad, bd, cd) = (
+            adx * adx + ady * ady,
+            bdx * bdx + bdy * bdy,
+            cdx * cdx + cdy * cdy,
+        );
+        let det = ad * (bdx * cdy - cdx * bdy)
+            + bd * (cdx * ady - adx * cdy)
+            + cd * (adx * bdy - bdx * ady);
+        let magnitude = ad * (bdx * cdy).abs().max((cdx * bdy).abs())
+            + bd * (cdx * ady).abs().max((adx * cdy).abs())
+            + cd * (adx * bdy).abs().max((bdx * ady).abs());
+        det > magnitude * 1e-10
+    }
+
+    /// Finds a triangle that contains the point
+    fn locate(&self, point: &VpPoint) -> Option<usize> {
+        self.triangles.iter().position(|triangle| {
+            triangle.is_some_and(|t| {
+                let [a, b, c] = t.map(|i| self.points[i]);
+                cross(&a, &b, point) >= 0.0
+                    && cross(&b, &c, point) >= 0.0
+                    && cross(&c, &a, point) >= 0.0
+            })
+        })
+    }
+
+    /// Inserts the point with the Bowyer–Watson algorithm. The triangles whose circumcircle
+    /// contains the point are replaced with a fan of triangles around the point. The cavity
+    /// grows from the start triangles and doesn't cross the constrained edges.
+    fn insert(&mut self, point: VpPoint, start: &[usize]) -> usize {
+        let index = self.points.len();
+        self.points.push(point);
+        let mut cavity: BTreeSet<usize> = start.iter().copied().collect();
+        let mut stack: Vec<usize> = start.to_vec();
+        while let Some(t) = stack.pop() {
+            let Some(triangle) = self.triangles[t] else {
+                continue;
+            };
+            for k in 0..3 {
+                let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+                if self.is_constrained(a, b) {
+                    continue;
+                }
+                if let Some((neighbor, _)) = self.opposite(b, a) {
+                    let neighbor_triangle = self.triangles[neighbor].unwrap();
+                    if !cavity.contains(&neighbor)
+                        && self.in_circumcircle(&neighbor_triangle, &point)
+                    {
+                        cavity.insert(neighbor);
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+
+        let mut boundary = Vec::new();
+        for &t in &cavity {
+            let triangle = self.triangles[t].unwrap();
+            for k in 0..3 {
+                let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+                if !self
+                    .opposite(b, a)
+                    .is_some_and(|(n, _)| cavity.contains(&n))
+                {
+                    boundary.push((a, b));
+                }
+            }
+        }
+        for &t in &cavity {
+            self.remove_triangle(t);
+        }
+        for (a, b) in boundary {
+            // The split segment is on the boundary when there's a triangle only on one side
+            let (pa, pb) = (&self.points[a], &self.points[b]);
+            let length_squared = (pb.x - pa.x).powi(2) + (pb.y - pa.y).powi(2);
+            if cross(pa, pb, &point) > length_squared * 1e-12 {
+                self.add_triangle([a, b, index]);
+            }
+        }
+        index
+    }
+
+    /// Flips the edge between the two triangles. Returns the new edge.
+    fn flip(&mut self, a: usize, b: usize) -> (usize, usize) {
+        let (t1, c) = self.opposite(a, b).unwrap();
+        let (t2, d) = self.opposite(b, a).unwrap();
+        self.remove_triangle(t1);
+        self.remove_triangle(t2);
+        self.add_triangle([c, a, d]);
+        self.add_triangle([d, b, c]);
+        (c, d)
+    }
+
+    /// Makes the segment an edge by flipping the edges that cross it (S. W. Sloan, A fast
+    /// algorithm for generating constrained Delaunay triangulations)
+    fn recover_constraint(&mut self, a: usize, b: usize) {
+        let crosses = |cdt: &Cdt, u: usize, v: usize, s: usize, t: usize| {
+            let p = &cdt.points;
+            cross(&p[u], &p[v], &p[s]) * cross(&p[u], &p[v], &p[t]) < 0.0
+                && cross(&p[s], &p[t], &p[u]) * cross(&p[s], &p[t], &p[v]) < 0.0
+        };
+        if !self.edges.contains_key(&(a, b)) && !self.edges.contains_key(&(b, a)) {
+            let mut crossing: VecDeque<(usize, usize)> = self
+                .triangles
+                .iter()
+                .flatten()
+                .flat_map(|t| (0..3).map(move |k| (t[k], t[(k + 1) % 3])))
+                .filter(|&(u, v)| u < v && crosses(self, a, b, u, v))
+                .collect();
+            let mut attempts = 0;
+            while let Some((u, v)) = crossing.pop_front() {
+                attempts += 1;
+                if attempts > 100 * (self.points.len() + crossing.len()) {
+                    break;
+                }
+                let (Some((_, c)), Some((_, d))) = (self.opposite(u, v), self.opposite(v, u))
+                else {
+                    continue;
+                };
+                // Only the diagonal of a convex quadrilateral can be flipped
+                if !crosses(self, u, v, c, d) {
+                    crossing.push_back((u, v));
+                    continue;
+                }
+                let (c, d) = self.flip(u, v);
+                if crosses(self, a, b, c, d) {
+                    crossing.push_back((c, d));
+                }
+            }
+        }
+        self.constraints.insert((a.min(b), a.max(b)));
+    }
+
+    /// Flips the unconstrained edges until all the triangles are constrained Delaunay
+    fn make_delaunay(&mut self) {
+        for _ in 0..100 * self.triangles.len() {
+            let mut flipped = false;
+            for t in 0..self.triangles.len() {
+                let Some(triangle) = self.triangles[t] else {
+                    continue;
+                };
+                for k in 0..3 {
+                    let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+                    if self.is_constrained(a, b) {
+                        continue;
+                    }
+                    if let Some((_, d)) = self.opposite(b, a) {
+                        if self.in_circumcircle(&triangle, &self.points[d]) {
+                            self.flip(a, b);
+                            flipped = true;
+                            break;
+                        }
+                    }
+                }
+            }
+            if !flipped {
+                break;
+            }
+        }
+    }
+
+    /// Removes the triangles of the super triangle and the triangles outside the polygon
+    fn remove_outside(&mut self, polygon: &PolygonWithHoles) {
+        for t in 0..self.triangles.len() {
+            let Some(triangle) = self.triangles[t] else {
+                continue;
+            };
+            let [a, b, c] = triangle.map(|i| self.points[i]);
+            let centroid = VpPoint::new((a.x + b.x + c.x) / 3.0, (a.y + b.y + c.y) / 3.0);
+            if triangle.iter().any(|&i| i < SUPER_POINTS)
+                || polygon.contains(&centroid, 0.0) != Containment::Inside
+            {
+                self.remove_triangle(t);
+            }
+        }
+    }
+
+    /// Adds points until the triangles meet the requirements (Ruppert's algorithm). The
+    /// circumcenter of a bad triangle is added unless it encroaches a segment, in which case
+    /// the segment is split at its midpoint instead. Returns false if `max_points` points were
+    /// added before all the triangles met the requirements.
+    fn refine(&mut self, options: &MeshOptions, max_points: usize) -> bool {
+        let limit = self.points.len() + max_points;
+        while let Some(triangle) = self.find_bad_triangle(options) {
+            if self.points.len() >= limit {
+                return false;
+            }
+            let [a, b, c] = triangle.map(|i| self.points[i]);
+            let center = circumcenter(&a, &b, &c);
+            let encroached = self.constraints.iter().copied().find(|&(s, t)| {
+                let (ps, pt) = (&self.points[s], &self.points[t]);
+                let midpoint = VpPoint::new((ps.x + pt.x) / 2.0, (ps.y + pt.y) / 2.0);
+                calc_length_between_points(&midpoint, &center)
+                    < calc_length_between_points(ps, pt) / 2.0
+            });
+            if let Some((s, t)) = encroached {
+                self.split_segment(s, t);
+            } else if let Some(start) = self.locate(&center) {
+                self.insert(center, &[start]);
+            } else {
+                // The circumcenter is outside the polygon. Split the longest edge instead.
+                let (s, t) = (0..3)
+                    .map(|k| (triangle[k], triangle[(k + 1) % 3]))
+                    .max_by(|&(s1, t1), &(s2, t2)| {
+                        let length = |s: usize, t: usize| {
+                            calc_length_between_points(&self.points[s], &self.points[t])
+                        };
+                        length(s1, t1).total_cmp(&length(s2, t2))
+                    })
+                    .unwrap();
+                self.split_segment(s, t);
+            }
+        }
+        true
+    }
+
+    fn find_bad_triangle(&self, options: &MeshOptions) -> Option<[usize; 3]> {
+        let min_allowed = options.min_angle.map(f64::to_radians);
+        self.triangles.iter().flatten().copied().find(|triangle| {
+            if options
+                .max_area
+                .is_some_and(|max| triangle_area(&self.points, triangle) > max)
+            {
+                return true;
+            }
+            let Some(min_allowed) = min_allowed else {
+                return false;
+            };
+            let (angle, k) = min_angle(&self.points, triangle);
+            // The small angles between two edges of the polygon can't be refined
+            let vertex = triangle[k];
+            angle < min_allowed
+                && !(self.is_constrained(vertex, triangle[(k + 1) % 3])
+                    && self.is_constrained(vertex, triangle[(k + 2) % 3]))
+        })
+    }
+
+    /// Inserts the midpoint of the edge. If the edge is constrained, the halves are constrained.
+    fn split_segment(&mut self, a: usize, b: usize) {
+        let (pa, pb) = (self.points[a], self.points[b]);
+        let midpoint = VpPoint::new((pa.x + pb.x) / 2.0, (pa.y + pb.y) / 2.0);
+        let start: Vec<usize> = [(a, b), (b, a)]
+            .iter()
+            .filter_map(|edge| self.edges.get(edge).copied())
+            .collect();
+        let constrained = self.constraints.remove(&(a.min(b), a.max(b)));
+        let index = self.insert(midpoint, &start);
+        if constrained {
+            self.constraints.insert((a.min(index), a.max(index)));
+            self.constraints.insert((b.min(index), b.max(index)));
+        }
+    }
+
+    fn into_mesh(self) -> TriangleMesh {
+        TriangleMesh {
+            points: self.points[SUPER_POINTS..].to_vec(),
+            triangles: self
+                .triangles
+                .into_iter()
+                .flatten()
+                .map(|t| t.map(|i| i - SUPER_POINTS))
+                .collect(),
+            limit_reached: false,
+        }
+    }
+}
+
+/// Splits the segments at the points that lie on them
+fn split_segments(points: &[VpPoint], segments: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut result = Vec::new();
+    for &(a, b) in segments {
+        let segment = LineSegment::new(points[a], points[b]);
+        let epsilon = segment.length() * 1e-12;
+        let mut on_segment: Vec<(f64, usize)> = (0..points.len())
+            .filter(|&i| i != a && i != b && segment.is_near(&points[i], epsilon))
+            .map(|i| (segment.project(&points[i]), i))
+            .collect();
+        on_segment.sort_by(|p1, p2| p1.0.total_cmp(&p2.0));
+        let mut previous = a;
+        for (_, i) in on_segment {
+            result.push((previous, i));
+            previous = i;
+        }
+        result.push((previous, b));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry2d::calculate_area;
+    use crate::geometry2d::test_utils::{assert_close, polygon, rectangle};
+
+    fn total_area(points: &[VpPoint], triangles: &[[usize; 3]]) -> f64 {
+        for triangle in triangles {
+            assert!(triangle_area(points, triangle) > 0.0, "{triangle:?}");
+        }
+        triangles.iter().map(|t| triangle_area(points, t)).sum()
+    }
+
+    #[test]
+    fn ear_clipping() {
+        let mut square = rectangle(0.0, 0.0, 10.0, 10.0);
+        let triangles = triangulate(&square);
+        assert_eq!(triangles.len(), 2);
+        assert!(triangles.iter().flatten().all(|&i| i < 4));
+        assert_close(total_area(&square.points, &triangles), 100.0);
+        square.points.reverse();
+        square.points.pop();
+        assert_close(total_area(&square.points, &triangulate(&square)), 100.0);
+
+        // A comb with collinear points on the base
+        let mut points = vec![VpPoint::new(0.0, 0.0)];
+        for i in 0..5 {
+            let x = i as f64 * 2.0;
+            points.push(VpPoint::new(x + 1.0, 0.0));
+            points.push(VpPoint::new(x + 2.0, 0.0));
+        }
+        for i in (0..5).rev() {
+            let x = i as f64 * 2.0;
+            points.push(VpPoint::new(x + 2.0, 5.0));
+            points.push(VpPoint::new(x + 1.5, 1.0));
+        }
+        points.push(VpPoint::new(0.0, 5.0));
+        let comb = Polygon::new(points);
+        let triangles = comb.triangulate();
+        assert_eq!(triangles.len(), comb.points.len() - 2);
+        let mut closed = comb.clone();
+        closed.points.push(comb.points[0]);
+        assert_close(
+            total_area(&comb.points, &triangles),
+            calculate_area(&closed),
+        );
+
+        // Only a part of a self-intersecting polygon is triangulated
+        let bow_tie = polygon(&[(0.0, 0.0), (4.0, 4.0), (4.0, 0.0), (0.0, 4.0)]);
+        assert!(bow_tie.triangulate().len() < bow_tie.points.len() - 2);
+    }
+
+    #[test]
+    fn ear_clipping_with_holes() {
+        let section = PolygonWithHoles::new(
+            rectangle(0.0, 0.0, 10.0, 10.0),
+            vec![rectangle(2.0, 2.0, 2.0, 2.0), rectangle(6.0, 5.0, 2.0, 3.0)],
+        );
+        let triangles = section.triangulate();
+        // n + 2h - 2 triangles for n points and h holes
+        assert_eq!(triangles.len(), 12 + 4 - 2);
+        let points: Vec<VpPoint> = std::iter::once(&section.outer)
+            .chain(&section.holes)
+            .flat_map(|p| p.points.clone())
+            .collect();
+        assert_close(total_area(&points, &triangles), 100.0 - 4.0 - 6.0);
+        for triangle in &triangles {
+            let [a, b, c] = triangle.map(|i| points[i]);
+            let centroid = VpPoint::new((a.x + b.x + c.x) / 3.0, (a.y + b.y + c.y) / 3.0);
+            assert_eq!(section.contains(&centroid, 1e-9), Containment::Inside);
+        }
+    }
+
+    #[test]
+    fn delaunay() {
+        // The Delaunay triangulation of a convex polygon has no points inside the circumcircles
+        let points: Vec<VpPoint> = (0..12)
+            .map(|i| {
+                let angle = (i as f64 * 30.0).to_radians();
+                VpPoint::new(10.0 * angle.cos(), 6.0 * angle.sin())
+            })
+            .collect();
+        let ellipse = Polygon::new(points);
+        let mesh = ellipse.triangulate_delaunay(&MeshOptions::default());
+        assert_eq!(mesh.points, ellipse.points);
+        assert_eq!(mesh.triangles.len(), 10);
+        for triangle in &mesh.triangles {
+            let [a, b, c] = triangle.map(|i| mesh.points[i]);
+            let center = circumcenter(&a, &b, &c);
+            let radius = calc_length_between_points(&center, &a);
+            for point in &mesh.points {
+                assert!(calc_length_between_points(&center, point) > radius - 1e-9);
+            }
+        }
+        let mut closed = ellipse.clone();
+        closed.points.push(ellipse.points[0]);
+        assert_close(mesh.area(), calculate_area(&closed));
+    }
+
+    #[test]
+    fn constrained_delaunay_with_holes() {
+        // The edges of the L-shape and the hole are kept
+        let outer = Polygon::new(vec![
+            VpPoint::new(0.0, 0.0),
+            VpPoint::new(20.0, 0.0),
+            VpPoint::new(20.0, 4.0),
+            VpPoint::new(4.0, 4.0),
+            VpPoint::new(4.0, 20.0),
+            VpPoint::new(0.0, 20.0),
+        ]);
+        let section = PolygonWithHoles::new(outer, vec![rectangle(1.0, 1.0, 2.0, 2.0)]);
+        let mesh = section.triangulate_delaunay(&MeshOptions::default());
+        assert_eq!(mesh.points.len(), 10);
+        assert_close(total_area(&mesh.points, &mesh.triangles), 144.0 - 4.0);
+
+        let options = MeshOptions {
+            max_area: Some(2.0),
+            min_angle: Some(25.0),
+        };
+        let mesh = section.triangulate_delaunay(&options);
+        assert!(mesh.points.len() > 40);
+        assert_close(total_area(&mesh.points, &mesh.triangles), 140.0);
+        assert!(mesh.min_angle().unwrap() >= 25.0);
+        for triangle in &mesh.triangles {
+            assert!(triangle_area(&mesh.points, triangle) <= 2.0);
+            let [a, b, c] = triangle.map(|i| mesh.points[i]);
+            let centroid = VpPoint::new((a.x + b.x + c.x) / 3.0, (a.y + b.y + c.y) / 3.0);
+            assert_eq!(section.contains(&centroid, 1e-9), Containment::Inside);
+        }
+    }
+
+    #[test]
+    fn refine_thin_rectangle() {
+        let plate = rectangle(0.0, 0.0, 10.0, 0.5);
+        let coarse = plate.triangulate_delaunay(&MeshOptions::default());
+        assert_eq!(coarse.triangles.len(), 2);
+        let options = MeshOptions {
+            max_area: None,
+            min_angle: Some(30.0),
+        };
+        let mesh = plate.triangulate_delaunay(&options);
+        assert!(mesh.min_angle().unwrap() >= 30.0);
+        assert_close(mesh.area(), 5.0);
+        assert!(!mesh.limit_reached);
+
+        let serialized = serde_json::to_string(&mesh).unwrap();
+        let deserialized: TriangleMesh = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, mesh);
+
+        // The requirements can't be met with the maximum number of points
+        let options = MeshOptions {
+            max_area: Some(1e-4),
+            min_angle: None,
+        };
+        let mesh = constrained_delaunay_limited(&plate.into(), &options, 100);
+        assert!(mesh.limit_reached);
+        assert_eq!(mesh.points.len(), 104);
+        assert_close(mesh.area(), 5.0);
+    }
+}
@@ -0,0 +1,271 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geometry2d::{
+    boolean, calc_length_between_points, LineSegment, Polygon, SegmentIntersection, VpPoint,
+};
+
+/// A problem found by [`validate`]. The edge `i` is the edge `i` of [`Polygon::edges`].
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PolygonIssue {
+    /// The polygon has less than three distinct points
+    TooFewPoints { count: usize },
+    /// The point is the same as the previous point
+    DuplicatePoint { index: usize },
+    /// The edge is shorter than the tolerance but the points aren't the same
+    ZeroLengthEdge { index: usize },
+    /// The last point is within the tolerance from the first point but not the same, so it's
+    /// unclear if the polygon is closed or if the last edge is very short
+    AlmostClosed { distance: f64 },
+    /// The edges cross or touch at the point
+    SelfIntersection {
+        edges: (usize, usize),
+        point: VpPoint,
+    },
+    /// The edges are on top of each other
+    OverlappingEdges {
+        edges: (usize, usize),
+        overlap: LineSegment,
+    },
+}
+
+/// Checks if the polygon is a valid simple polygon. The polygon can be closed or open like in
+/// the area calculation, but if the last point is almost the same as the first point, it's
+/// reported as ambiguous. Points and edges within `epsilon` from each other are considered
+/// touching.
+pub fn validate(polygon: &Polygon, epsilon: f64) -> Vec<PolygonIssue> {
+    let points = &polygon.points;
+    let mut issues = Vec::new();
+
+    let mut distinct: Vec<&VpPoint> = Vec::new();
+    for point in points {
+        if distinct
+            .iter()
+            .all(|p| calc_length_between_points(p, point) > epsilon)
+        {
+            distinct.push(point);
+        }
+    }
+    if distinct.len() < 3 {
+        issues.push(PolygonIssue::TooFewPoints {
+            count: distinct.len(),
+        });
+    }
+    if points.len() < 2 {
+        return issues;
+    }
+
+    let (first, last) = (&points[0], &points[points.len() - 1]);
+    let closed = first == last;
+    let distance = calc_length_between_points(first, last);
+    if !closed && distance <= epsilon {
+        issues.push(PolygonIssue::AlmostClosed { distance });
+    }
+
+    // The edges that are longer than the tolerance. An open polygon has the closing edge from
+    // the last point to the first point.
+    let count = polygon.edges().count();
+    let mut edges: Vec<(usize, LineSegment)> = Vec::new();
+    for (i, (p1, p2)) in polygon.edges().enumerate() {
+        let edge = LineSegment::new(*p1, *p2);
+        let closing = i + 1 == count && !closed;
+        if !closing && edge.start == edge.end {
+            issues.push(PolygonIssue::DuplicatePoint { index: i + 1 });
+        } else if edge.length() <= epsilon {
+            // The closing edge of an almost closed polygon is already reported
+            if !closing {
+                issues.push(PolygonIssue::ZeroLengthEdge { index: i });
+            }
+        } else {
+            edges.push((i, edge));
+        }
+    }
+
+    for j in 0..edges.len() {
+        for k in j + 1..edges.len() {
+            let ((index1, edge1), (index2, edge2)) = (&edges[j], &edges[k]);
+            let adjacent = k == j + 1 || (j == 0 && k == edges.len() - 1);
+            match edge1.intersection(edge2, epsilon) {
+                SegmentIntersection::None => {}
+                // The adjacent edges always touch at the shared point
+                SegmentIntersection::Point(_) if adjacent => {}
+                SegmentIntersection::Point(point) => {
+                    let edges = (*index1, *index2);
+                    issues.push(PolygonIssue::SelfIntersection { edges, point });
+                }
+                SegmentIntersection::Overlap(overlap) => {
+                    let edges = (*index1, *index2);
+                    issues.push(PolygonIssue::OverlappingEdges { edges, overlap });
+                }
+            }
+        }
+    }
+    issues
+}
+
+/// Splits the self intersecting polygon into simple polygons. Areas that are wound around
+/// multiple times are kept once and the zero area parts (e.g. spikes) are removed. Returns the
+/// polygons the same way as the boolean operations: closed polygons with the outer boundaries
+/// counterclockwise and the holes clockwise. See
+/// [`boolean_operation`](boolean::boolean_operation) for the `tolerance`.
+pub fn make_valid(polygon: &Polygon, tolerance: f64) -> Vec<Polygon> {
+    boolean::union(polygon, &Polygon::new_empty(), tolerance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry2d::calculate_area;
+    use crate::geometry2d::test_utils::polygon;
+
+    #[test]
+    fn valid_polygon() {
+        let square = polygon(&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ]);
+        assert!(validate(&square, 1e-9).is_empty());
+        assert!(square.is_valid(1e-9));
+        let mut open = square.clone();
+        open.open();
+        assert!(open.is_valid(1e-9));
+        let valid = square.make_valid(1e-9);
+        assert_eq!(valid.len(), 1);
+        assert_eq!(calculate_area(&valid[0]), 100.0);
+    }
+
+    #[test]
+    fn bow_tie() {
+        let bow_tie = polygon(&[
+            (0.0, 0.0),
+            (25.0, 25.0),
+            (25.0, 0.0),
+            (0.0, 25.0),
+            (0.0, 0.0),
+        ]);
+        assert_eq!(
+            bow_tie.validate(1e-9),
+            vec![PolygonIssue::SelfIntersection {
+                edges: (0, 2),
+                point: VpPoint::new(12.5, 12.5),
+            }]
+        );
+        let parts = bow_tie.make_valid(1e-9);
+        assert_eq!(parts.len(), 2);
+        for part in &parts {
+            assert!(part.is_valid(1e-9));
+            assert_eq!(calculate_area(part), 156.25);
+        }
+    }
+
+    #[test]
+    fn points_and_closing() {
+        let open = polygon(&[(0.0, 0.0), (10.0, 0.0), (10.0, 0.0), (0.0, 10.0)]);
+        assert_eq!(
+            validate(&open, 1e-6),
+            vec![PolygonIssue::DuplicatePoint { index: 2 }]
+        );
+        let mut closed = open.clone();
+        closed.close();
+        assert_eq!(validate(&closed, 1e-6), validate(&open, 1e-6));
+
+        let issues = validate(
+            &polygon(&[
+                (0.0, 0.0),
+                (10.0, 0.0),
+                (10.0, 1e-9),
+                (0.0, 10.0),
+                (1e-9, 0.0),
+            ]),
+            1e-6,
+        );
+        assert_eq!(
+            issues,
+            vec![
+                PolygonIssue::AlmostClosed { distance: 1e-9 },
+                PolygonIssue::ZeroLengthEdge { index: 1 },
+            ]
+        );
+
+        let line = polygon(&[(0.0, 0.0), (10.0, 0.0), (0.0, 0.0)]);
+        let issues = validate(&line, 1e-6);
+        assert_eq!(issues[0], PolygonIssue::TooFewPoints { count: 2 });
+        assert!(matches!(
+            issues[1],
+            PolygonIssue::OverlappingEdges { edges: (0, 1), .. }
+        ));
+        assert!(line.make_valid(1e-6).is_empty());
+        let issues = validate(&Polygon::new_empty(), 1e-6);
+        assert_eq!(issues, vec![PolygonIssue::TooFewPoints { count: 0 }]);
+    }
+
+    #[test]
+    fn touching_and_overlapping_edges() {
+        // The vertex of the notch touches the bottom edge
+        let touching = polygon(&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (6.0, 10.0),
+            (5.0, 0.0),
+            (4.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ]);
+        let notch = VpPoint::new(5.0, 0.0);
+        assert_eq!(
+            touching.validate(1e-9),
+            vec![
+                PolygonIssue::SelfIntersection {
+                    edges: (0, 3),
+                    point: notch
+                },
+                PolygonIssue::SelfIntersection {
+                    edges: (0, 4),
+                    point: notch
+                },
+            ]
+        );
+        let parts = touching.make_valid(1e-9);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts.iter().map(calculate_area).sum::<f64>(), 90.0);
+
+        // A spike that goes back along the previous edge. The edges also touch at the point
+        // that is visited twice.
+        let spike = polygon(&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (15.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 0.0),
+        ]);
+        let touch = VpPoint::new(10.0, 0.0);
+        assert_eq!(
+            spike.validate(1e-9),
+            vec![
+                PolygonIssue::SelfIntersection {
+                    edges: (0, 2),
+                    point: touch
+                },
+                PolygonIssue::SelfIntersection {
+                    edges: (0, 3),
+                    point: touch
+                },
+                PolygonIssue::OverlappingEdges {
+                    edges: (1, 2),
+                    overlap: LineSegment::new_xy(10.0, 0.0, 15.0, 0.0),
+                },
+                PolygonIssue::SelfIntersection {
+                    edges: (1, 3),
+                    point: touch
+                },
+            ]
+        );
+        let parts = spike.make_valid(1e-9);
+        assert_eq!(parts.len(), 1);
+        assert_eq!(calculate_area(&parts[0]), 50.0);
+    }
+}
@@ -1,3 +1,4 @@
 pub mod vputils;
 pub mod equation_handler;
-pub mod geometry2d;
\ No newline at end of file
+pub mod geometry2d;
+mod rng;
\ No newline at end of file
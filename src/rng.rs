@@ -0,0 +1,63 @@
+use std::f64::consts::TAU;
+
+/// SplitMix64 pseudo random number generator. Fast and good enough for the simulations and the
+/// randomized algorithms, but not for cryptography.
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number in range [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a random number from the standard normal distribution (Box-Muller transform)
+    pub(crate) fn next_standard_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64(); // (0, 1], so the logarithm is finite
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (TAU * u2).cos()
+    }
+
+    /// Shuffles the items in random order (Fisher-Yates shuffle)
+    pub(crate) fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_mix_64() {
+        // The same seed gives the same numbers
+        let numbers: Vec<f64> = (0..3).map(|_| SplitMix64::new(5).next_f64()).collect();
+        assert!(numbers.iter().all(|n| *n == numbers[0]));
+        let mut rng = SplitMix64::new(1);
+        assert!((0..1000)
+            .map(|_| rng.next_f64())
+            .all(|n| (0.0..1.0).contains(&n)));
+
+        let mut items: Vec<usize> = (0..100).collect();
+        rng.shuffle(&mut items);
+        assert_ne!(items, (0..100).collect::<Vec<_>>());
+        items.sort();
+        assert_eq!(items, (0..100).collect::<Vec<_>>());
+    }
+}
@@ -10,7 +10,7 @@ struct DoubleBoolTuple {
 
 #[allow(dead_code)]
 #[no_mangle]
-extern fn s_to_double_extern(s: *const c_char) -> f64 {
+extern "C" fn s_to_double_extern(s: *const c_char) -> f64 {
     let c_str = unsafe { CStr::from_ptr(s) };
     let s_str = c_str.to_str().unwrap();
     s_to_double_validation(s_str).0.unwrap_or(0.0)
@@ -18,7 +18,7 @@ extern fn s_to_double_extern(s: *const c_char) -> f64 {
 
 #[allow(dead_code)]
 #[no_mangle]
-extern fn s_to_double_validation_extern(s: *const c_char) -> DoubleBoolTuple {
+extern "C" fn s_to_double_validation_extern(s: *const c_char) -> DoubleBoolTuple {
     let c_str = unsafe { CStr::from_ptr(s) };
     let tuple = s_to_double_validation(c_str.to_str().unwrap());
     let valid : u8 = if tuple.1 {1} else {0};
@@ -98,7 +98,7 @@ pub fn s_to_double_validation(s: &str) -> (Option<f64>, bool) {
 /// - digits \[0-9].
 /// - dash \- (only before any other valid characters. Note, abc-efg0123 is converted to -123).
 /// - Note! Comma and dot are **not** valid characters. They're dropped so 5.0 is converted to 50. 
-/// Use [`s_to_double_validation`] for floating point values.
+///   Use [`s_to_double_validation`] for floating point values.
 ///
 /// Returns None if string is empty or parsing fails even after dropping the invalid characters
 pub fn s_to_int(s: &str) -> Option<isize> {
@@ -111,7 +111,7 @@ pub fn s_to_int(s: &str) -> Option<isize> {
 /// - digits \[0-9].
 /// - dash \- (only before any other valid characters. Note, abc-efg0123 is converted to -123).
 /// - Note! Comma and dot are **not** valid characters. They're dropped so 5.0 is converted to 50. 
-/// Use [`s_to_double_validation`] for floating point values.
+///   Use [`s_to_double_validation`] for floating point values.
 ///
 /// Returns a tuple of Option<isize> and bool
 /// - First item (Option<isize>): None if string is empty or parsing fails even after dropping the invalid characters
@@ -176,9 +176,9 @@ mod tests {
         assert_eq!(s_to_double("abc-efg0123"), Some(-123f64));
 
         let mut is_valid = s_to_double_validation("-5").1;
-        assert_eq!(is_valid, true);
+        assert!(is_valid);
         is_valid = s_to_double_validation("你es-5").1;
-        assert_eq!(is_valid, false);
+        assert!(!is_valid);
     }
 
     #[test]
@@ -199,9 +199,9 @@ mod tests {
         assert_eq!(s_to_int("abc-efg0123"), Some(-123));
 
         let mut is_valid = s_to_int_validation("-5").1;
-        assert_eq!(is_valid, true);
+        assert!(is_valid);
         is_valid = s_to_int_validation("你es-5").1;
-        assert_eq!(is_valid, false);
+        assert!(!is_valid);
     }
 
 }
\ No newline at end of file